@@ -0,0 +1,26 @@
+use capturecard_viewer::convert::{self, PixelFormat, SimdLevel, YuvMatrix};
+use capturecard_viewer::settings::{ColorRange, ColorSpace};
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+
+// YUV -> RGBA 変換を 1080p / 4K のフォーマットごとに、使える命令セットそれぞれで計測
+// (720p 以上は行単位の並列化も効くので、実際のキャプチャ時と同じ条件)
+fn yuv_to_rgba(c: &mut Criterion) {
+    let m = YuvMatrix::new(ColorSpace::Bt709, ColorRange::Limited);
+    for (width, height) in [(1920, 1080), (3840, 2160)] {
+        let mut group = c.benchmark_group(format!("yuv_to_rgba/{}x{}", width, height));
+        group.throughput(Throughput::Elements((width * height) as u64));
+        for format in [PixelFormat::Yuy2, PixelFormat::Nv12, PixelFormat::I420, PixelFormat::P010] {
+            let src: Vec<u8> = (0..format.frame_len(width, height)).map(|i| (i * 7 % 251) as u8).collect();
+            let mut dst = vec![0u8; width * height * 4];
+            for simd in SimdLevel::available() {
+                group.bench_function(BenchmarkId::new(format.name(), format!("{:?}", simd)), |b| {
+                    b.iter(|| convert::yuv_to_rgba_into(format, width, height, &src, &mut dst, &m, simd))
+                });
+            }
+        }
+        group.finish();
+    }
+}
+
+criterion_group!(benches, yuv_to_rgba);
+criterion_main!(benches);
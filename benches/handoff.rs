@@ -0,0 +1,66 @@
+use std::borrow::Cow;
+use std::sync::{mpsc, Arc};
+use std::time::{Duration, Instant};
+use capturecard_viewer::test_pattern::TestPatternSource;
+use capturecard_viewer::video::{VideoCapture, VideoFrame};
+use criterion::{criterion_group, criterion_main, Criterion};
+
+const WIDTH: usize = 1920;
+const HEIGHT: usize = 1080;
+
+// 変更前の実装では受け渡しのたびに画素データを複製していた (1080p で 1 回あたり約 8 MB)
+// UI への受け渡し (get_latest_frame)・録画スレッドへの送り (録画の分岐)・スクリーンショットの元データ
+// それぞれで実際に複製されたバイト数を表示し、複製していた場合の時間と並べて計測する
+fn frame_handoff(c: &mut Criterion) {
+    let mut video = VideoCapture::new();
+    video.start_source(Box::new(TestPatternSource::new(Some((WIDTH as u32, HEIGHT as u32)), Some("YUY2"), Some(60)))).unwrap();
+    let reader = video.frame_reader();
+    let deadline = Instant::now() + Duration::from_secs(5);
+    let frame = loop {
+        assert!(Instant::now() < deadline, "no frame from the test pattern");
+        if let Some(frame) = reader.get_latest_frame() {
+            break frame;
+        }
+        std::thread::sleep(Duration::from_millis(5));
+    };
+    let frame_bytes = frame.image.pixels.len() * 4;
+
+    // 1 フレームあたりの複製量 (画素データを共有していれば 0)
+    let copied = |shared: bool| if shared { 0 } else { frame_bytes };
+    // 同じフレームを 2 回読み出して同じバッファか確かめる (間に次のフレームが届いたら読み直す)
+    let ui_shared = loop {
+        let (first, again) = (reader.get_latest_frame().unwrap(), reader.get_latest_frame().unwrap());
+        if first.generation == again.generation {
+            break Arc::ptr_eq(&first.image, &again.image);
+        }
+    };
+    let (tap, recorded) = mpsc::sync_channel::<(Instant, VideoFrame)>(1);
+    tap.send((Instant::now(), frame.clone())).unwrap();
+    let (_, for_recording) = recorded.recv().unwrap();
+    let screenshot = frame.cropped_rgba((0, 0, WIDTH as u32, HEIGHT as u32));
+    println!(
+        "bytes copied per {}x{} frame: get_latest_frame {}, recording tap {}, screenshot {} (copying: {} each)",
+        WIDTH, HEIGHT,
+        copied(ui_shared),
+        copied(Arc::ptr_eq(&for_recording.image, &frame.image)),
+        copied(matches!(screenshot, Cow::Borrowed(_))),
+        frame_bytes,
+    );
+
+    let mut group = c.benchmark_group(format!("frame_handoff/{}x{}", WIDTH, HEIGHT));
+    group.bench_function("get_latest_frame", |b| b.iter(|| reader.get_latest_frame()));
+    group.bench_function("recording_tap", |b| {
+        b.iter(|| {
+            tap.try_send((Instant::now(), frame.clone())).unwrap();
+            recorded.recv().unwrap()
+        })
+    });
+    group.bench_function("screenshot_source", |b| b.iter(|| frame.cropped_rgba((0, 0, WIDTH as u32, HEIGHT as u32)).len()));
+    // 変更前の受け渡し 1 回分 (画素データの複製)
+    group.bench_function("copy_per_handoff", |b| b.iter(|| frame.image.pixels.clone()));
+    group.finish();
+    video.stop_capture();
+}
+
+criterion_group!(benches, frame_handoff);
+criterion_main!(benches);
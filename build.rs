@@ -1,6 +1,7 @@
 fn main() {
-    #[cfg(target_os = "windows")]
-    {
+    // アイコン・バージョン情報の埋め込みは Windows ターゲットのみ
+    // (build.rs 自体はホスト向けにビルドされるため cfg ではなく環境変数で判定)
+    if std::env::var("CARGO_CFG_TARGET_OS").as_deref() == Ok("windows") {
         embed_resource::compile("app.rc", embed_resource::NONE);
     }
-}
\ No newline at end of file
+}
@@ -3,8 +3,63 @@ use cpal::{Device, SampleFormat, SupportedStreamConfigRange};
 use std::sync::{Arc, Mutex};
 
 use ringbuf::HeapRb;
+use crate::settings::{self, DeviceId, DeviceMatch};
 
+type SampleProducer = ringbuf::Producer<f32, Arc<HeapRb<f32>>>;
+pub type SampleConsumer = ringbuf::Consumer<f32, Arc<HeapRb<f32>>>;
 
+// cpal には固有 ID がないため、同名デバイスの中で何台目かを識別情報にする
+pub fn device_candidates(names: &[String]) -> Vec<(String, DeviceId)> {
+    names.iter()
+        .enumerate()
+        .map(|(i, name)| {
+            let ordinal = names[..i].iter().filter(|n| *n == name).count();
+            (name.clone(), DeviceId { path: String::new(), index: Some(ordinal as u32) })
+        })
+        .collect()
+}
+
+// 録画用に入力音声を分岐する口 (録画中だけリングバッファをつなぐ)
+// 再生用のリングバッファとは別なので、パススルーの音は減らない
+#[derive(Clone, Default)]
+pub struct AudioTap {
+    producer: Arc<Mutex<Option<SampleProducer>>>,
+    // 入力のサンプルレートとチャンネル数 (入力ストリームの動作中のみ)
+    format: Arc<Mutex<Option<(u32, u16)>>>,
+}
+
+impl AudioTap {
+    pub fn format(&self) -> Option<(u32, u16)> {
+        self.format.lock().ok().and_then(|f| *f)
+    }
+
+    pub fn set_format(&self, format: Option<(u32, u16)>) {
+        if let Ok(mut f) = self.format.lock() { *f = format; }
+    }
+
+    // seconds 秒分のリングバッファをつなぎ、読み出し側を返す (入力が止まっていれば None)
+    pub fn open(&self, seconds: u32) -> Option<SampleConsumer> {
+        let (sample_rate, channels) = self.format()?;
+        let (producer, consumer) = HeapRb::<f32>::new((sample_rate * channels as u32 * seconds) as usize).split();
+        *self.producer.lock().ok()? = Some(producer);
+        Some(consumer)
+    }
+
+    pub fn close(&self) {
+        if let Ok(mut producer) = self.producer.lock() { *producer = None; }
+    }
+
+    // 入力コールバックから呼ぶ (録画側が止まっていて溢れた分は捨てる)
+    pub fn push(&self, samples: impl IntoIterator<Item = f32>) {
+        if let Ok(mut producer) = self.producer.try_lock() {
+            if let Some(producer) = producer.as_mut() {
+                for sample in samples {
+                    let _ = producer.push(sample);
+                }
+            }
+        }
+    }
+}
 
 pub struct AudioCapture {
     host: cpal::Host,
@@ -22,10 +77,12 @@ pub struct AudioCapture {
     raw_audio_consumer: Option<Arc<Mutex<ringbuf::Consumer<f32, Arc<ringbuf::HeapRb<f32>>>>>>,
     #[allow(clippy::type_complexity)]
     processed_audio_consumer: Option<Arc<Mutex<ringbuf::Consumer<f32, Arc<ringbuf::HeapRb<f32>>>>>>,
+    // 録画用の分岐
+    tap: AudioTap,
 }
 
 impl AudioCapture {
-    pub fn new() -> Self {
+    pub fn new(tap: AudioTap) -> Self {
         println!("Debug: Creating AudioCapture with WASAPI host");
         let host = cpal::default_host();
         println!("Debug: Host created: {:?}", host.id());
@@ -40,18 +97,19 @@ impl AudioCapture {
             audio_passthrough_enabled: Arc::new(Mutex::new(true)), // デフォルトで音声パススルーを有効化（音が出るようにする）
             raw_audio_consumer: None,
             processed_audio_consumer: None,
+            tap,
         }
     }
 
-    pub fn list_input_devices(&self) -> Vec<String> {
-        match self.host.input_devices() {
+    pub fn list_input_devices() -> Vec<String> {
+        match cpal::default_host().input_devices() {
             Ok(devices) => devices.filter_map(|d| d.name().ok()).collect(),
             Err(_) => Vec::new(),
         }
     }
 
-    pub fn list_output_devices(&self) -> Vec<String> {
-        match self.host.output_devices() {
+    pub fn list_output_devices() -> Vec<String> {
+        match cpal::default_host().output_devices() {
             Ok(devices) => devices.filter_map(|d| d.name().ok()).collect(),
             Err(_) => Vec::new(),
         }
@@ -60,7 +118,9 @@ impl AudioCapture {
     pub fn start_passthrough_with_settings(
         &mut self,
         input_device_name: Option<&str>,
+        input_device_index: Option<usize>,
         output_device_name: Option<&str>,
+        output_device_index: Option<usize>,
         _desired_sample_rate: Option<u32>,
         _desired_channels: Option<u16>,
     ) -> Result<(), String> {
@@ -70,7 +130,7 @@ impl AudioCapture {
         // デバイス取得の簡素化
         let input_device = if let Some(name) = input_device_name {
             println!("Debug: Looking for input device: {}", name);
-            self.find_device_by_name(name, true)?
+            self.find_device_by_name(name, input_device_index, true)?
         } else {
             println!("Debug: Using default input device");
             self.host
@@ -80,7 +140,7 @@ impl AudioCapture {
         
         let output_device = if let Some(name) = output_device_name {
             println!("Debug: Looking for output device: {}", name);
-            self.find_device_by_name(name, false)?
+            self.find_device_by_name(name, output_device_index, false)?
         } else {
             println!("Debug: Using default output device");
             self.host
@@ -122,6 +182,7 @@ impl AudioCapture {
         // 入力ストリーム - F32のみサポート（簡素化）
         let input_stream = if input_config.sample_format() == SampleFormat::F32 {
             let producer_clone = producer.clone();
+            let tap = self.tap.clone();
             input_device.build_input_stream(
                 &input_config.config(),
                 move |data: &[f32], _| {
@@ -130,6 +191,7 @@ impl AudioCapture {
                             let _ = prod.push(sample);
                         }
                     }
+                    tap.push(data.iter().copied());
                 },
                 |e| eprintln!("Input stream error: {}", e),
                 None,
@@ -137,6 +199,7 @@ impl AudioCapture {
         } else {
             // I16をF32に変換
             let producer_clone = producer.clone();
+            let tap = self.tap.clone();
             input_device.build_input_stream(
                 &input_config.config(),
                 move |data: &[i16], _| {
@@ -146,6 +209,7 @@ impl AudioCapture {
                             let _ = prod.push(f32_sample);
                         }
                     }
+                    tap.push(data.iter().map(|&sample| sample as f32 / i16::MAX as f32));
                 },
                 |e| eprintln!("Input stream error: {}", e),
                 None,
@@ -208,6 +272,7 @@ impl AudioCapture {
         self.input_stream = Some(input_stream);
         self.output_stream = Some(output_stream);
         self.is_active = true;
+        self.tap.set_format(Some((sample_rate, channels as u16)));
         
         // 簡素化のため、raw/processedバッファは使用しない
         self.raw_audio_consumer = Some(consumer.clone());
@@ -240,6 +305,7 @@ impl AudioCapture {
         if let Some(s) = self.input_stream.take() { let _ = s.pause(); }
         if let Some(s) = self.output_stream.take() { let _ = s.pause(); }
         self.is_active = false;
+        self.tap.set_format(None);
         self.buffer_capacity = 0;
     }
 
@@ -257,16 +323,39 @@ impl AudioCapture {
 
 
 
-    fn find_device_by_name(&self, name: &str, input: bool) -> Result<Device, String> {
+    // 名前 (同名が複数あれば何台目か) でデバイスを選ぶ
+    fn find_device_by_name(&self, name: &str, index: Option<usize>, input: bool) -> Result<Device, String> {
         let iter = if input { self.host.input_devices() } else { self.host.output_devices() }
             .map_err(|e| format!("enumerate devices: {e}"))?;
-        for d in iter {
-            if let Ok(n) = d.name() { if n == name { return Ok(d); } }
+        let mut devices: Vec<(String, Device)> = iter.filter_map(|d| Some((d.name().ok()?, d))).collect();
+        let names: Vec<String> = devices.iter().map(|(n, _)| n.clone()).collect();
+        let id = DeviceId { path: String::new(), index: index.map(|i| i as u32) };
+        match settings::match_device(&device_candidates(&names), name, Some(&id)) {
+            DeviceMatch::Found(i) => Ok(devices.swap_remove(i).1),
+            DeviceMatch::Ambiguous(count) => Err(format!("{count} devices are named '{name}', select one again in the settings")),
+            DeviceMatch::NotFound => Err(format!("Device '{name}' not found")),
         }
-        Err(format!("Device '{name}' not found"))
     }
 }
 
 impl Drop for AudioCapture {
     fn drop(&mut self) { self.stop_capture(); }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::settings::{match_device, DeviceMatch};
+
+    #[test]
+    fn candidates_number_devices_per_name() {
+        let names: Vec<String> = ["Mic", "Line In", "Mic", "Mic", "Line In"].iter().map(|s| s.to_string()).collect();
+        let candidates = device_candidates(&names);
+        let ordinals: Vec<(&str, Option<u32>)> = candidates.iter().map(|(name, id)| (name.as_str(), id.index)).collect();
+        assert_eq!(ordinals, [("Mic", Some(0)), ("Line In", Some(0)), ("Mic", Some(1)), ("Mic", Some(2)), ("Line In", Some(1))]);
+        assert!(candidates.iter().all(|(_, id)| id.path.is_empty()));
+        // 保存した何台目かで同名のデバイスを選び直せる
+        let saved = DeviceId { path: String::new(), index: Some(2) };
+        assert_eq!(match_device(&candidates, "Mic", Some(&saved)), DeviceMatch::Found(3));
+    }
+}
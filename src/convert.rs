@@ -0,0 +1,850 @@
+use crate::settings::{ColorRange, ColorSpace};
+use rayon::prelude::*;
+use std::sync::OnceLock;
+
+// YUV -> RGBA 変換 (テクスチャにそのまま渡せる不透明 RGBA、スカラー版 + SSE2/AVX2/NEON 版、行単位で並列化)
+
+// YUV -> RGB 変換係数 (固定小数点、1.0 = 1 << YUV_SHIFT)
+// SIMD 版は係数を i16 で扱うため 13bit に抑える (最大係数 ≈ 2.15)
+const YUV_SHIFT: i32 = 13;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct YuvMatrix {
+    y_offset: i32,
+    y_gain: i32,
+    r_v: i32,
+    g_u: i32,
+    g_v: i32,
+    b_u: i32,
+}
+
+impl YuvMatrix {
+    // 色空間 (Auto は解決済みであること) とレンジから係数を算出
+    pub fn new(space: ColorSpace, range: ColorRange) -> Self {
+        let (kr, kb) = match space {
+            ColorSpace::Bt709 => (0.2126, 0.0722),
+            ColorSpace::Bt2020 => (0.2627, 0.0593),
+            ColorSpace::Bt601 | ColorSpace::Auto => (0.299, 0.114),
+        };
+        let kg = 1.0 - kr - kb;
+        // リミテッドレンジ: Y 16-235 (219段階), C 16-240 (224段階)
+        let (y_offset, y_scale, c_scale) = match range {
+            ColorRange::Limited => (16, 255.0 / 219.0, 255.0 / 224.0),
+            ColorRange::Full => (0, 1.0, 1.0),
+        };
+        let fixed = |v: f64| (v * (1 << YUV_SHIFT) as f64).round() as i32;
+        Self {
+            y_offset,
+            y_gain: fixed(y_scale),
+            r_v: fixed(2.0 * (1.0 - kr) * c_scale),
+            g_u: fixed(2.0 * (1.0 - kb) * kb / kg * c_scale),
+            g_v: fixed(2.0 * (1.0 - kr) * kr / kg * c_scale),
+            b_u: fixed(2.0 * (1.0 - kb) * c_scale),
+        }
+    }
+
+    // Auto の場合は解像度で判定 (720p以上は BT.709、それ未満は BT.601)
+    pub fn for_frame(space: ColorSpace, range: ColorRange, width: usize, height: usize) -> Self {
+        let resolved = match space {
+            ColorSpace::Auto if width >= 1280 || height >= 720 => ColorSpace::Bt709,
+            ColorSpace::Auto => ColorSpace::Bt601,
+            other => other,
+        };
+        Self::new(resolved, range)
+    }
+
+    // YUV (8bit) -> RGBA 1ピクセル変換 (アルファは常に 255)
+    #[inline(always)]
+    fn convert(&self, y: i32, u: i32, v: i32, out: &mut [u8]) {
+        let c = (y - self.y_offset) * self.y_gain + (1 << (YUV_SHIFT - 1));
+        let d = u - 128;
+        let e = v - 128;
+        out[0] = ((c + self.r_v * e) >> YUV_SHIFT).clamp(0, 255) as u8;
+        out[1] = ((c - self.g_u * d - self.g_v * e) >> YUV_SHIFT).clamp(0, 255) as u8;
+        out[2] = ((c + self.b_u * d) >> YUV_SHIFT).clamp(0, 255) as u8;
+        out[3] = 255;
+    }
+}
+
+// 映像ピクセルフォーマット (設定ファイル上は name() の文字列で保存)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PixelFormat {
+    Yuy2,
+    Mjpeg,
+    Nv12,
+    I420,
+    P010,
+    Rgb24,
+}
+
+impl PixelFormat {
+    pub fn name(self) -> &'static str {
+        match self {
+            PixelFormat::Yuy2 => "YUY2",
+            PixelFormat::Mjpeg => "MJPEG",
+            PixelFormat::Nv12 => "NV12",
+            PixelFormat::I420 => "I420",
+            PixelFormat::P010 => "P010",
+            PixelFormat::Rgb24 => "RGB24",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "YUY2" | "YUYV" => Some(PixelFormat::Yuy2),
+            "MJPEG" | "MJPG" => Some(PixelFormat::Mjpeg),
+            "NV12" => Some(PixelFormat::Nv12),
+            "I420" | "IYUV" => Some(PixelFormat::I420),
+            "P010" => Some(PixelFormat::P010),
+            "RGB24" => Some(PixelFormat::Rgb24),
+            _ => None,
+        }
+    }
+
+    // 非圧縮フォーマットの1フレーム当たりのバイト数 (MJPEGは可変長のため0)
+    pub fn frame_len(self, width: usize, height: usize) -> usize {
+        let chroma = width.div_ceil(2) * height.div_ceil(2);
+        match self {
+            PixelFormat::Yuy2 => width * height * 2,
+            PixelFormat::Nv12 | PixelFormat::I420 => width * height + chroma * 2,
+            PixelFormat::P010 => (width * height + chroma * 2) * 2,
+            PixelFormat::Rgb24 => width * height * 3,
+            PixelFormat::Mjpeg => 0,
+        }
+    }
+
+    // YUV -> RGB 変換器で扱えるフォーマットか
+    pub fn is_yuv(self) -> bool {
+        matches!(self, PixelFormat::Yuy2 | PixelFormat::Nv12 | PixelFormat::I420 | PixelFormat::P010)
+    }
+}
+
+// 使用する命令セット
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SimdLevel {
+    Scalar,
+    #[cfg(target_arch = "x86_64")]
+    Sse2,
+    #[cfg(target_arch = "x86_64")]
+    Avx2,
+    #[cfg(target_arch = "aarch64")]
+    Neon,
+}
+
+impl SimdLevel {
+    // 実行環境で使える命令セットの一覧 (スカラー版を含む、テスト・ベンチマークでの比較用)
+    pub fn available() -> Vec<SimdLevel> {
+        let mut levels = vec![SimdLevel::Scalar];
+        #[cfg(target_arch = "x86_64")]
+        {
+            levels.push(SimdLevel::Sse2);
+            if is_x86_feature_detected!("avx2") {
+                levels.push(SimdLevel::Avx2);
+            }
+        }
+        #[cfg(target_arch = "aarch64")]
+        levels.push(SimdLevel::Neon);
+        levels
+    }
+}
+
+// 実行環境で使える最速の命令セット (初回のみ判定)
+// 環境変数 CAPTURECARD_VIEWER_NO_SIMD を設定するとスカラー版を強制 (色ずれ調査用)
+pub fn detect_simd() -> SimdLevel {
+    static LEVEL: OnceLock<SimdLevel> = OnceLock::new();
+    *LEVEL.get_or_init(|| {
+        if std::env::var_os("CAPTURECARD_VIEWER_NO_SIMD").is_some() {
+            return SimdLevel::Scalar;
+        }
+        #[cfg(target_arch = "x86_64")]
+        {
+            if is_x86_feature_detected!("avx2") { SimdLevel::Avx2 } else { SimdLevel::Sse2 }
+        }
+        #[cfg(target_arch = "aarch64")]
+        {
+            SimdLevel::Neon
+        }
+        #[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64")))]
+        {
+            SimdLevel::Scalar
+        }
+    })
+}
+
+// 1行分の入力プレーン
+enum RowSrc<'a> {
+    Packed(&'a [u8]),                  // YUY2
+    SemiPlanar(&'a [u8], &'a [u8]),    // NV12 / P010 (Y, UV)
+    Planar(&'a [u8], &'a [u8], &'a [u8]), // I420 (Y, U, V)
+}
+
+fn row_src(format: PixelFormat, width: usize, height: usize, src: &[u8], row: usize) -> RowSrc<'_> {
+    let cw = width.div_ceil(2);
+    let ch = height.div_ceil(2);
+    let crow = row / 2;
+    match format {
+        PixelFormat::Nv12 => {
+            let uv_base = width * height;
+            RowSrc::SemiPlanar(&src[row * width..][..width], &src[uv_base + crow * cw * 2..][..cw * 2])
+        }
+        PixelFormat::P010 => {
+            let uv_base = width * height * 2;
+            RowSrc::SemiPlanar(&src[row * width * 2..][..width * 2], &src[uv_base + crow * cw * 4..][..cw * 4])
+        }
+        PixelFormat::I420 => {
+            let u_base = width * height;
+            let v_base = u_base + cw * ch;
+            RowSrc::Planar(
+                &src[row * width..][..width],
+                &src[u_base + crow * cw..][..cw],
+                &src[v_base + crow * cw..][..cw],
+            )
+        }
+        _ => RowSrc::Packed(&src[row * width * 2..][..width * 2]),
+    }
+}
+
+// ---- スカラー版 (SIMD 版の端数処理と基準実装を兼ねる) ----
+
+// YUY2 1行 (start は偶数)
+fn yuy2_row_scalar(src: &[u8], dst: &mut [u8], m: &YuvMatrix, start: usize) {
+    for (s, d) in src[start * 2..].chunks_exact(4).zip(dst[start * 4..].chunks_exact_mut(8)) {
+        let (px0, px1) = d.split_at_mut(4);
+        m.convert(s[0] as i32, s[1] as i32, s[3] as i32, px0);
+        m.convert(s[2] as i32, s[1] as i32, s[3] as i32, px1);
+    }
+}
+
+// NV12 1行
+fn nv12_row_scalar(y: &[u8], uv: &[u8], dst: &mut [u8], m: &YuvMatrix, start: usize) {
+    for (x, px) in dst.chunks_exact_mut(4).enumerate().skip(start) {
+        let c = (x / 2) * 2;
+        m.convert(y[x] as i32, uv[c] as i32, uv[c + 1] as i32, px);
+    }
+}
+
+// I420 1行
+fn i420_row_scalar(y: &[u8], u: &[u8], v: &[u8], dst: &mut [u8], m: &YuvMatrix, start: usize) {
+    for (x, px) in dst.chunks_exact_mut(4).enumerate().skip(start) {
+        m.convert(y[x] as i32, u[x / 2] as i32, v[x / 2] as i32, px);
+    }
+}
+
+// P010 の 16bit サンプル (10bit 上位詰め、リトルエンディアン) を 8bit に丸める
+#[inline(always)]
+fn p010_sample_to_8bit(s: &[u8], i: usize) -> i32 {
+    let v = u16::from_le_bytes([s[i * 2], s[i * 2 + 1]]) as i32;
+    ((v + 0x80) >> 8).min(255)
+}
+
+// P010 1行 (8bit へダウンコンバート)
+fn p010_row_scalar(y: &[u8], uv: &[u8], dst: &mut [u8], m: &YuvMatrix, start: usize) {
+    for (x, px) in dst.chunks_exact_mut(4).enumerate().skip(start) {
+        let c = (x / 2) * 2;
+        m.convert(p010_sample_to_8bit(y, x), p010_sample_to_8bit(uv, c), p010_sample_to_8bit(uv, c + 1), px);
+    }
+}
+
+// 1行変換 (SIMD で処理できなかった端数はスカラーで処理)
+fn convert_row(format: PixelFormat, row: RowSrc<'_>, dst: &mut [u8], m: &YuvMatrix, simd: SimdLevel) {
+    let width = dst.len() / 4;
+    let done = match simd {
+        SimdLevel::Scalar => 0,
+        #[cfg(target_arch = "x86_64")]
+        SimdLevel::Sse2 => unsafe { x86::convert_row_sse2(format, &row, dst, m, width) },
+        #[cfg(target_arch = "x86_64")]
+        SimdLevel::Avx2 => unsafe { x86::convert_row_avx2(format, &row, dst, m, width) },
+        #[cfg(target_arch = "aarch64")]
+        SimdLevel::Neon => unsafe { neon::convert_row_neon(format, &row, dst, m, width) },
+    };
+    match (format, row) {
+        (PixelFormat::P010, RowSrc::SemiPlanar(y, uv)) => p010_row_scalar(y, uv, dst, m, done),
+        (_, RowSrc::SemiPlanar(y, uv)) => nv12_row_scalar(y, uv, dst, m, done),
+        (_, RowSrc::Planar(y, u, v)) => i420_row_scalar(y, u, v, dst, m, done),
+        (_, RowSrc::Packed(s)) => yuy2_row_scalar(s, dst, m, done),
+    }
+}
+
+// 変換用スレッドプール (論理コア数に応じて最大4スレッド)
+fn convert_pool() -> Option<&'static rayon::ThreadPool> {
+    static POOL: OnceLock<Option<rayon::ThreadPool>> = OnceLock::new();
+    POOL.get_or_init(|| {
+        let threads = std::thread::available_parallelism().map(|n| n.get()).unwrap_or(1).min(4);
+        if threads < 2 {
+            return None;
+        }
+        rayon::ThreadPoolBuilder::new()
+            .num_threads(threads)
+            .thread_name(|i| format!("yuv-convert-{}", i))
+            .build()
+            .map_err(|e| eprintln!("Failed to build conversion thread pool: {}", e))
+            .ok()
+    })
+    .as_ref()
+}
+
+// この画素数未満のフレームは並列化のオーバーヘッドの方が大きい
+const PARALLEL_MIN_PIXELS: usize = 1280 * 720;
+
+// YUV フレームを RGBA に変換して dst (width * height * 4) に書き込む
+// src が足りない・フォーマット非対応の場合は false
+pub fn yuv_to_rgba_into(format: PixelFormat, width: usize, height: usize, src: &[u8], dst: &mut [u8], m: &YuvMatrix, simd: SimdLevel) -> bool {
+    if !format.is_yuv() || width == 0 || height == 0 || src.len() < format.frame_len(width, height) || dst.len() < width * height * 4 {
+        return false;
+    }
+    // YUY2 は2ピクセル単位のため偶数幅のみ
+    if format == PixelFormat::Yuy2 && !width.is_multiple_of(2) {
+        return false;
+    }
+    let dst = &mut dst[..width * height * 4];
+    let row_len = width * 4;
+    let convert_band = |first_row: usize, band: &mut [u8]| {
+        for (i, out_row) in band.chunks_exact_mut(row_len).enumerate() {
+            convert_row(format, row_src(format, width, height, src, first_row + i), out_row, m, simd);
+        }
+    };
+    match convert_pool() {
+        Some(pool) if width * height >= PARALLEL_MIN_PIXELS => {
+            // 4:2:0 の色差行を共有できるよう帯は偶数行単位
+            let rows_per_band = (height.div_ceil(pool.current_num_threads() * 2) * 2).max(2);
+            pool.install(|| {
+                dst.par_chunks_mut(rows_per_band * row_len)
+                    .enumerate()
+                    .for_each(|(band, out)| convert_band(band * rows_per_band, out));
+            });
+        }
+        _ => convert_band(0, dst),
+    }
+    true
+}
+
+// ---- x86_64: SSE2 (常に利用可能) / AVX2 (実行時判定) ----
+#[cfg(target_arch = "x86_64")]
+mod x86 {
+    use super::{PixelFormat, RowSrc, YuvMatrix, YUV_SHIFT};
+    use std::arch::x86_64::*;
+
+    // madd 用に (a, b) の i16 ペアを 32bit にまとめる
+    fn pair(a: i32, b: i32) -> i32 {
+        ((a as i16 as u16 as u32) | ((b as i16 as u16 as u32) << 16)) as i32
+    }
+
+    // 係数ベクトル (128bit / 256bit 共通の 32bit ブロードキャスト値)
+    struct Coeffs {
+        y_off: i16,
+        y_r: i32,   // (y_gain, r_v)
+        y_gu: i32,  // (y_gain, -g_u)
+        v_gr: i32,  // (-g_v, 丸め)
+        y_b: i32,   // (y_gain, b_u)
+        round: i32,
+    }
+
+    impl Coeffs {
+        fn new(m: &YuvMatrix) -> Self {
+            let round = 1 << (YUV_SHIFT - 1);
+            Self {
+                y_off: m.y_offset as i16,
+                y_r: pair(m.y_gain, m.r_v),
+                y_gu: pair(m.y_gain, -m.g_u),
+                v_gr: pair(-m.g_v, round),
+                y_b: pair(m.y_gain, m.b_u),
+                round,
+            }
+        }
+    }
+
+    // SSE2: Y (i16x8) と UV ペア (u0 v0 u1 v1 ... i16x8) から 8ピクセル変換
+    #[inline(always)]
+    unsafe fn block_sse2(y16: __m128i, uv16: __m128i, k: &Coeffs, dst: &mut [u8]) {
+        let y = _mm_sub_epi16(y16, _mm_set1_epi16(k.y_off));
+        let c = _mm_sub_epi16(uv16, _mm_set1_epi16(128));
+        // u0 u0 u1 u1 u2 u2 u3 u3 / v0 v0 v1 v1 ...
+        let u = _mm_shufflehi_epi16::<0b10_10_00_00>(_mm_shufflelo_epi16::<0b10_10_00_00>(c));
+        let v = _mm_shufflehi_epi16::<0b11_11_01_01>(_mm_shufflelo_epi16::<0b11_11_01_01>(c));
+        let one = _mm_set1_epi16(1);
+        let round = _mm_set1_epi32(k.round);
+        let (yv_lo, yv_hi) = (_mm_unpacklo_epi16(y, v), _mm_unpackhi_epi16(y, v));
+        let (yu_lo, yu_hi) = (_mm_unpacklo_epi16(y, u), _mm_unpackhi_epi16(y, u));
+        let (v1_lo, v1_hi) = (_mm_unpacklo_epi16(v, one), _mm_unpackhi_epi16(v, one));
+
+        let c_r = _mm_set1_epi32(k.y_r);
+        let r_lo = _mm_srai_epi32::<YUV_SHIFT>(_mm_add_epi32(_mm_madd_epi16(yv_lo, c_r), round));
+        let r_hi = _mm_srai_epi32::<YUV_SHIFT>(_mm_add_epi32(_mm_madd_epi16(yv_hi, c_r), round));
+        let (c_gu, c_gv) = (_mm_set1_epi32(k.y_gu), _mm_set1_epi32(k.v_gr));
+        let g_lo = _mm_srai_epi32::<YUV_SHIFT>(_mm_add_epi32(_mm_madd_epi16(yu_lo, c_gu), _mm_madd_epi16(v1_lo, c_gv)));
+        let g_hi = _mm_srai_epi32::<YUV_SHIFT>(_mm_add_epi32(_mm_madd_epi16(yu_hi, c_gu), _mm_madd_epi16(v1_hi, c_gv)));
+        let c_b = _mm_set1_epi32(k.y_b);
+        let b_lo = _mm_srai_epi32::<YUV_SHIFT>(_mm_add_epi32(_mm_madd_epi16(yu_lo, c_b), round));
+        let b_hi = _mm_srai_epi32::<YUV_SHIFT>(_mm_add_epi32(_mm_madd_epi16(yu_hi, c_b), round));
+
+        // i32 -> i16 -> u8 (飽和で 0-255 にクランプ、下位8バイトが有効)
+        let zero = _mm_setzero_si128();
+        let r = _mm_packus_epi16(_mm_packs_epi32(r_lo, r_hi), zero);
+        let g = _mm_packus_epi16(_mm_packs_epi32(g_lo, g_hi), zero);
+        let b = _mm_packus_epi16(_mm_packs_epi32(b_lo, b_hi), zero);
+        // r g / b a をバイト単位、さらに16bit単位で交互に並べて RGBA x8
+        let rg = _mm_unpacklo_epi8(r, g);
+        let ba = _mm_unpacklo_epi8(b, _mm_set1_epi8(-1));
+        _mm_storeu_si128(dst[..16].as_mut_ptr() as *mut __m128i, _mm_unpacklo_epi16(rg, ba));
+        _mm_storeu_si128(dst[16..32].as_mut_ptr() as *mut __m128i, _mm_unpackhi_epi16(rg, ba));
+    }
+
+    #[inline(always)]
+    unsafe fn load_u128(s: &[u8]) -> __m128i {
+        _mm_loadu_si128(s[..16].as_ptr() as *const __m128i)
+    }
+
+    #[inline(always)]
+    unsafe fn load_u64(s: &[u8]) -> __m128i {
+        _mm_loadl_epi64(s[..8].as_ptr() as *const __m128i)
+    }
+
+    #[inline(always)]
+    unsafe fn load_u32(s: &[u8]) -> __m128i {
+        _mm_cvtsi32_si128(i32::from_le_bytes([s[0], s[1], s[2], s[3]]))
+    }
+
+    // P010 の 16bit サンプル x8 を丸めて 8bit 相当 (i16) に
+    #[inline(always)]
+    unsafe fn p010_to_8bit_sse2(v: __m128i) -> __m128i {
+        _mm_srli_epi16::<8>(_mm_adds_epu16(v, _mm_set1_epi16(0x80)))
+    }
+
+    // 8ピクセル単位で変換し、処理済みピクセル数を返す
+    pub(super) unsafe fn convert_row_sse2(format: PixelFormat, row: &RowSrc<'_>, dst: &mut [u8], m: &YuvMatrix, width: usize) -> usize {
+        let k = Coeffs::new(m);
+        let zero = _mm_setzero_si128();
+        let blocks = width / 8;
+        for i in 0..blocks {
+            let x = i * 8;
+            let (y16, uv16) = match (format, row) {
+                (PixelFormat::P010, RowSrc::SemiPlanar(y, uv)) => (
+                    p010_to_8bit_sse2(load_u128(&y[x * 2..])),
+                    p010_to_8bit_sse2(load_u128(&uv[x * 2..])),
+                ),
+                (_, RowSrc::SemiPlanar(y, uv)) => (
+                    _mm_unpacklo_epi8(load_u64(&y[x..]), zero),
+                    _mm_unpacklo_epi8(load_u64(&uv[x..]), zero),
+                ),
+                (_, RowSrc::Planar(y, u, v)) => (
+                    _mm_unpacklo_epi8(load_u64(&y[x..]), zero),
+                    _mm_unpacklo_epi8(_mm_unpacklo_epi8(load_u32(&u[x / 2..]), load_u32(&v[x / 2..])), zero),
+                ),
+                (_, RowSrc::Packed(s)) => {
+                    let p = load_u128(&s[x * 2..]);
+                    (_mm_and_si128(p, _mm_set1_epi16(0xFF)), _mm_srli_epi16::<8>(p))
+                }
+            };
+            block_sse2(y16, uv16, &k, &mut dst[x * 4..]);
+        }
+        blocks * 8
+    }
+
+    // AVX2: 16ピクセル変換 (128bit レーン内で処理し、レーン順は pack で元に戻る)
+    #[inline(always)]
+    unsafe fn block_avx2(y16: __m256i, uv16: __m256i, k: &Coeffs, dst: &mut [u8]) {
+        let y = _mm256_sub_epi16(y16, _mm256_set1_epi16(k.y_off));
+        let c = _mm256_sub_epi16(uv16, _mm256_set1_epi16(128));
+        let u = _mm256_shufflehi_epi16::<0b10_10_00_00>(_mm256_shufflelo_epi16::<0b10_10_00_00>(c));
+        let v = _mm256_shufflehi_epi16::<0b11_11_01_01>(_mm256_shufflelo_epi16::<0b11_11_01_01>(c));
+        let one = _mm256_set1_epi16(1);
+        let round = _mm256_set1_epi32(k.round);
+        let (yv_lo, yv_hi) = (_mm256_unpacklo_epi16(y, v), _mm256_unpackhi_epi16(y, v));
+        let (yu_lo, yu_hi) = (_mm256_unpacklo_epi16(y, u), _mm256_unpackhi_epi16(y, u));
+        let (v1_lo, v1_hi) = (_mm256_unpacklo_epi16(v, one), _mm256_unpackhi_epi16(v, one));
+
+        let c_r = _mm256_set1_epi32(k.y_r);
+        let r_lo = _mm256_srai_epi32::<YUV_SHIFT>(_mm256_add_epi32(_mm256_madd_epi16(yv_lo, c_r), round));
+        let r_hi = _mm256_srai_epi32::<YUV_SHIFT>(_mm256_add_epi32(_mm256_madd_epi16(yv_hi, c_r), round));
+        let (c_gu, c_gv) = (_mm256_set1_epi32(k.y_gu), _mm256_set1_epi32(k.v_gr));
+        let g_lo = _mm256_srai_epi32::<YUV_SHIFT>(_mm256_add_epi32(_mm256_madd_epi16(yu_lo, c_gu), _mm256_madd_epi16(v1_lo, c_gv)));
+        let g_hi = _mm256_srai_epi32::<YUV_SHIFT>(_mm256_add_epi32(_mm256_madd_epi16(yu_hi, c_gu), _mm256_madd_epi16(v1_hi, c_gv)));
+        let c_b = _mm256_set1_epi32(k.y_b);
+        let b_lo = _mm256_srai_epi32::<YUV_SHIFT>(_mm256_add_epi32(_mm256_madd_epi16(yu_lo, c_b), round));
+        let b_hi = _mm256_srai_epi32::<YUV_SHIFT>(_mm256_add_epi32(_mm256_madd_epi16(yu_hi, c_b), round));
+
+        // 各レーンの下位8バイトに8ピクセルずつ (0-7 / 8-15)
+        let zero = _mm256_setzero_si256();
+        let r = _mm256_packus_epi16(_mm256_packs_epi32(r_lo, r_hi), zero);
+        let g = _mm256_packus_epi16(_mm256_packs_epi32(g_lo, g_hi), zero);
+        let b = _mm256_packus_epi16(_mm256_packs_epi32(b_lo, b_hi), zero);
+        let rg = _mm256_unpacklo_epi8(r, g);
+        let ba = _mm256_unpacklo_epi8(b, _mm256_set1_epi8(-1));
+        // lo: 0-3 / 8-11, hi: 4-7 / 12-15 -> レーンを入れ替えて 0-7 / 8-15 の順に
+        let lo = _mm256_unpacklo_epi16(rg, ba);
+        let hi = _mm256_unpackhi_epi16(rg, ba);
+        _mm256_storeu_si256(dst[..32].as_mut_ptr() as *mut __m256i, _mm256_permute2x128_si256::<0x20>(lo, hi));
+        _mm256_storeu_si256(dst[32..64].as_mut_ptr() as *mut __m256i, _mm256_permute2x128_si256::<0x31>(lo, hi));
+    }
+
+    #[inline(always)]
+    unsafe fn load_u256(s: &[u8]) -> __m256i {
+        _mm256_loadu_si256(s[..32].as_ptr() as *const __m256i)
+    }
+
+    #[inline(always)]
+    unsafe fn p010_to_8bit_avx2(v: __m256i) -> __m256i {
+        _mm256_srli_epi16::<8>(_mm256_adds_epu16(v, _mm256_set1_epi16(0x80)))
+    }
+
+    // 16ピクセル単位で変換し、残りは SSE2 に任せる
+    #[target_feature(enable = "avx2")]
+    pub(super) unsafe fn convert_row_avx2(format: PixelFormat, row: &RowSrc<'_>, dst: &mut [u8], m: &YuvMatrix, width: usize) -> usize {
+        let k = Coeffs::new(m);
+        let blocks = width / 16;
+        for i in 0..blocks {
+            let x = i * 16;
+            let (y16, uv16) = match (format, row) {
+                (PixelFormat::P010, RowSrc::SemiPlanar(y, uv)) => (
+                    p010_to_8bit_avx2(load_u256(&y[x * 2..])),
+                    p010_to_8bit_avx2(load_u256(&uv[x * 2..])),
+                ),
+                (_, RowSrc::SemiPlanar(y, uv)) => (
+                    _mm256_cvtepu8_epi16(load_u128(&y[x..])),
+                    _mm256_cvtepu8_epi16(load_u128(&uv[x..])),
+                ),
+                (_, RowSrc::Planar(y, u, v)) => (
+                    _mm256_cvtepu8_epi16(load_u128(&y[x..])),
+                    _mm256_cvtepu8_epi16(_mm_unpacklo_epi8(load_u64(&u[x / 2..]), load_u64(&v[x / 2..]))),
+                ),
+                (_, RowSrc::Packed(s)) => {
+                    let p = load_u256(&s[x * 2..]);
+                    (_mm256_and_si256(p, _mm256_set1_epi16(0xFF)), _mm256_srli_epi16::<8>(p))
+                }
+            };
+            block_avx2(y16, uv16, &k, &mut dst[x * 4..]);
+        }
+        let done = blocks * 16;
+        if width - done >= 8 {
+            // 8ピクセル以上の端数は SSE2 で処理 (行の途中から始められるよう部分行を渡す)
+            let rest = match row {
+                RowSrc::Packed(s) => RowSrc::Packed(&s[done * 2..]),
+                RowSrc::SemiPlanar(y, uv) if format == PixelFormat::P010 => RowSrc::SemiPlanar(&y[done * 2..], &uv[done * 2..]),
+                RowSrc::SemiPlanar(y, uv) => RowSrc::SemiPlanar(&y[done..], &uv[done..]),
+                RowSrc::Planar(y, u, v) => RowSrc::Planar(&y[done..], &u[done / 2..], &v[done / 2..]),
+            };
+            return done + convert_row_sse2(format, &rest, &mut dst[done * 4..], m, width - done);
+        }
+        done
+    }
+}
+
+// ---- aarch64: NEON (常に利用可能) ----
+#[cfg(target_arch = "aarch64")]
+mod neon {
+    use super::{PixelFormat, RowSrc, YuvMatrix, YUV_SHIFT};
+    use std::arch::aarch64::*;
+
+    // Y (u16x8) と UV ペア (u0 v0 u1 v1 ... u16x8) から 8ピクセル変換
+    #[inline(always)]
+    unsafe fn block_neon(y16: uint16x8_t, uv16: uint16x8_t, m: &YuvMatrix, dst: &mut [u8]) {
+        let y = vsubq_s16(vreinterpretq_s16_u16(y16), vdupq_n_s16(m.y_offset as i16));
+        let c = vsubq_s16(vreinterpretq_s16_u16(uv16), vdupq_n_s16(128));
+        // u0 u0 u1 u1 u2 u2 u3 u3 / v0 v0 v1 v1 ...
+        let u = vuzp1q_s16(c, c);
+        let v = vuzp2q_s16(c, c);
+        let u = vzip1q_s16(u, u);
+        let v = vzip1q_s16(v, v);
+        let round = vdupq_n_s32(1 << (YUV_SHIFT - 1));
+        let (y_lo, y_hi) = (vget_low_s16(y), vget_high_s16(y));
+        let (u_lo, u_hi) = (vget_low_s16(u), vget_high_s16(u));
+        let (v_lo, v_hi) = (vget_low_s16(v), vget_high_s16(v));
+        let c_lo = vmlal_n_s16(round, y_lo, m.y_gain as i16);
+        let c_hi = vmlal_n_s16(round, y_hi, m.y_gain as i16);
+
+        let r_lo = vmlal_n_s16(c_lo, v_lo, m.r_v as i16);
+        let r_hi = vmlal_n_s16(c_hi, v_hi, m.r_v as i16);
+        let g_lo = vmlal_n_s16(vmlal_n_s16(c_lo, u_lo, -m.g_u as i16), v_lo, -m.g_v as i16);
+        let g_hi = vmlal_n_s16(vmlal_n_s16(c_hi, u_hi, -m.g_u as i16), v_hi, -m.g_v as i16);
+        let b_lo = vmlal_n_s16(c_lo, u_lo, m.b_u as i16);
+        let b_hi = vmlal_n_s16(c_hi, u_hi, m.b_u as i16);
+
+        // i32 -> i16 -> u8 (飽和で 0-255 にクランプ)
+        let r = vqmovun_s16(vcombine_s16(vqshrn_n_s32::<YUV_SHIFT>(r_lo), vqshrn_n_s32::<YUV_SHIFT>(r_hi)));
+        let g = vqmovun_s16(vcombine_s16(vqshrn_n_s32::<YUV_SHIFT>(g_lo), vqshrn_n_s32::<YUV_SHIFT>(g_hi)));
+        let b = vqmovun_s16(vcombine_s16(vqshrn_n_s32::<YUV_SHIFT>(b_lo), vqshrn_n_s32::<YUV_SHIFT>(b_hi)));
+        vst4_u8(dst[..32].as_mut_ptr(), uint8x8x4_t(r, g, b, vdup_n_u8(255)));
+    }
+
+    #[inline(always)]
+    unsafe fn load_u16x8(s: &[u8]) -> uint16x8_t {
+        vreinterpretq_u16_u8(vld1q_u8(s[..16].as_ptr()))
+    }
+
+    #[inline(always)]
+    unsafe fn widen_u8x8(s: &[u8]) -> uint16x8_t {
+        vmovl_u8(vld1_u8(s[..8].as_ptr()))
+    }
+
+    #[inline(always)]
+    unsafe fn p010_to_8bit_neon(v: uint16x8_t) -> uint16x8_t {
+        vshrq_n_u16::<8>(vqaddq_u16(v, vdupq_n_u16(0x80)))
+    }
+
+    // 8ピクセル単位で変換し、処理済みピクセル数を返す
+    pub(super) unsafe fn convert_row_neon(format: PixelFormat, row: &RowSrc<'_>, dst: &mut [u8], m: &YuvMatrix, width: usize) -> usize {
+        let blocks = width / 8;
+        for i in 0..blocks {
+            let x = i * 8;
+            let (y16, uv16) = match (format, row) {
+                (PixelFormat::P010, RowSrc::SemiPlanar(y, uv)) => (
+                    p010_to_8bit_neon(load_u16x8(&y[x * 2..])),
+                    p010_to_8bit_neon(load_u16x8(&uv[x * 2..])),
+                ),
+                (_, RowSrc::SemiPlanar(y, uv)) => (widen_u8x8(&y[x..]), widen_u8x8(&uv[x..])),
+                (_, RowSrc::Planar(y, u, v)) => {
+                    let c = x / 2;
+                    let uv = [u[c], v[c], u[c + 1], v[c + 1], u[c + 2], v[c + 2], u[c + 3], v[c + 3]];
+                    (widen_u8x8(&y[x..]), widen_u8x8(&uv))
+                }
+                (_, RowSrc::Packed(s)) => {
+                    let p = load_u16x8(&s[x * 2..]);
+                    (vandq_u16(p, vdupq_n_u16(0xFF)), vshrq_n_u16::<8>(p))
+                }
+            };
+            block_neon(y16, uv16, m, &mut dst[x * 4..]);
+        }
+        blocks * 8
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 5x3 (幅・高さとも奇数) の 4:2:0 テスト画像: Y は画素ごと、色差は 2x2 ブロックごとに異なる値
+    const W: usize = 5;
+    const H: usize = 3;
+    const CHROMA: [[(u8, u8); 3]; 2] = [
+        [(128, 128), (100, 150), (160, 90)],
+        [(90, 200), (200, 60), (140, 120)],
+    ];
+
+    fn luma(x: usize, y: usize) -> u8 {
+        (30 + 40 * x + 15 * y) as u8
+    }
+
+    // BT.601 フルレンジで R = Y + 1.402 (V-128), G = Y - 0.344136 (U-128) - 0.714136 (V-128), B = Y + 1.772 (U-128)
+    // を浮動小数点で計算して四捨五入した値 (変換器の固定小数点の係数は使っていない)
+    const EXPECTED_420: [[[u8; 3]; W]; H] = [
+        [[30, 30, 30], [70, 70, 70], [141, 104, 60], [181, 144, 100], [137, 206, 247]],
+        [[45, 45, 45], [85, 85, 85], [156, 119, 75], [196, 159, 115], [152, 221, 255]],
+        [[161, 22, 0], [201, 62, 33], [45, 164, 255], [85, 204, 255], [209, 222, 241]],
+    ];
+
+    fn full_range() -> YuvMatrix {
+        YuvMatrix::new(ColorSpace::Bt601, ColorRange::Full)
+    }
+
+    fn convert(format: PixelFormat, width: usize, height: usize, src: &[u8], m: &YuvMatrix, simd: SimdLevel) -> Vec<u8> {
+        let mut dst = vec![0u8; width * height * 4];
+        assert!(yuv_to_rgba_into(format, width, height, src, &mut dst, m, simd), "{:?} {}x{} was rejected", format, width, height);
+        dst
+    }
+
+    fn assert_rgba(actual: &[u8], expected: &[[u8; 3]], label: &str) {
+        for (i, (px, want)) in actual.chunks_exact(4).zip(expected).enumerate() {
+            assert_eq!(px, [want[0], want[1], want[2], 255], "{}: pixel {}", label, i);
+        }
+        assert_eq!(actual.len(), expected.len() * 4, "{}", label);
+    }
+
+    fn nv12_420() -> Vec<u8> {
+        let mut src: Vec<u8> = (0..H).flat_map(|y| (0..W).map(move |x| luma(x, y))).collect();
+        src.extend(CHROMA.iter().flatten().flat_map(|&(u, v)| [u, v]));
+        src
+    }
+
+    fn i420_420() -> Vec<u8> {
+        let mut src: Vec<u8> = (0..H).flat_map(|y| (0..W).map(move |x| luma(x, y))).collect();
+        src.extend(CHROMA.iter().flatten().map(|&(u, _)| u));
+        src.extend(CHROMA.iter().flatten().map(|&(_, v)| v));
+        src
+    }
+
+    // 8bit 値を 10bit 上位詰め (P010) にする
+    fn p010_from_nv12(nv12: &[u8]) -> Vec<u8> {
+        nv12.iter().flat_map(|&v| ((v as u16) << 8).to_le_bytes()).collect()
+    }
+
+    #[test]
+    fn nv12_exact() {
+        let src = nv12_420();
+        assert_eq!(src.len(), PixelFormat::Nv12.frame_len(W, H));
+        let out = convert(PixelFormat::Nv12, W, H, &src, &full_range(), SimdLevel::Scalar);
+        assert_rgba(&out, EXPECTED_420.as_flattened(), "NV12");
+    }
+
+    #[test]
+    fn i420_exact() {
+        let src = i420_420();
+        assert_eq!(src.len(), PixelFormat::I420.frame_len(W, H));
+        let out = convert(PixelFormat::I420, W, H, &src, &full_range(), SimdLevel::Scalar);
+        assert_rgba(&out, EXPECTED_420.as_flattened(), "I420");
+    }
+
+    #[test]
+    fn p010_exact() {
+        let src = p010_from_nv12(&nv12_420());
+        assert_eq!(src.len(), PixelFormat::P010.frame_len(W, H));
+        let out = convert(PixelFormat::P010, W, H, &src, &full_range(), SimdLevel::Scalar);
+        assert_rgba(&out, EXPECTED_420.as_flattened(), "P010");
+    }
+
+    #[test]
+    fn p010_rounds_to_8bit() {
+        // 色差は中立、フルレンジなので出力は丸めた Y そのもの (16bit 値 / 256 を四捨五入、上限 255)
+        let samples: [(u16, u8); 4] = [(0x7F7F, 127), (0x7F80, 128), (0x0040, 0), (0xFFC0, 255)];
+        let mut src: Vec<u8> = samples.iter().flat_map(|&(v, _)| v.to_le_bytes()).collect();
+        src.extend([0x8000u16; 4].iter().flat_map(|v| v.to_le_bytes()));
+        let out = convert(PixelFormat::P010, 4, 1, &src, &full_range(), SimdLevel::Scalar);
+        let expected: Vec<[u8; 3]> = samples.iter().map(|&(_, y)| [y; 3]).collect();
+        assert_rgba(&out, &expected, "P010 rounding");
+    }
+
+    #[test]
+    fn yuy2_exact() {
+        // YUY2 は水平方向だけ色差を共有 (6x3、行ごとに異なる色差)
+        const CHROMA_422: [[(u8, u8); 3]; 3] = [
+            [(128, 128), (100, 150), (160, 90)],
+            [(90, 200), (200, 60), (140, 120)],
+            [(60, 60), (250, 250), (128, 20)],
+        ];
+        const EXPECTED: [[[u8; 3]; 6]; 3] = [
+            [[30, 30, 30], [65, 65, 65], [131, 94, 50], [166, 129, 85], [117, 186, 227], [152, 221, 255]],
+            [[146, 7, 0], [181, 42, 13], [20, 139, 243], [55, 174, 255], [174, 187, 206], [209, 222, 241]],
+            [[0, 132, 0], [0, 167, 0], [255, 1, 255], [255, 36, 255], [49, 255, 200], [84, 255, 235]],
+        ];
+        let y = |x: usize, row: usize| (30 + 35 * x + 15 * row) as u8;
+        let src: Vec<u8> = (0..3)
+            .flat_map(|row| (0..3).flat_map(move |c| {
+                let (u, v) = CHROMA_422[row][c];
+                [y(c * 2, row), u, y(c * 2 + 1, row), v]
+            }))
+            .collect();
+        let out = convert(PixelFormat::Yuy2, 6, 3, &src, &full_range(), SimdLevel::Scalar);
+        assert_rgba(&out, EXPECTED.as_flattened(), "YUY2");
+    }
+
+    // 各色空間・レンジでの黒・白・原色 (R, G, B) の YCbCr 値と、そこから戻した RGB
+    // YCbCr は RGB から規格の式で求めて整数に丸めた値、RGB は浮動小数点の逆変換を四捨五入した値 (変換器の係数とは独立に計算)
+    type Reference = (ColorSpace, ColorRange, [(u8, u8, u8, [u8; 3]); 5]);
+    const REFERENCE_VECTORS: [Reference; 6] = [
+        (ColorSpace::Bt601, ColorRange::Limited, [(16, 128, 128, [0, 0, 0]), (235, 128, 128, [255, 255, 255]), (81, 90, 240, [254, 0, 0]), (145, 54, 34, [0, 255, 1]), (41, 240, 110, [0, 0, 255])]),
+        (ColorSpace::Bt601, ColorRange::Full, [(0, 128, 128, [0, 0, 0]), (255, 128, 128, [255, 255, 255]), (76, 85, 255, [254, 0, 0]), (150, 44, 21, [0, 255, 1]), (29, 255, 107, [0, 0, 254])]),
+        (ColorSpace::Bt709, ColorRange::Limited, [(16, 128, 128, [0, 0, 0]), (235, 128, 128, [255, 255, 255]), (63, 102, 240, [255, 1, 0]), (173, 42, 26, [0, 255, 1]), (32, 240, 118, [1, 0, 255])]),
+        (ColorSpace::Bt709, ColorRange::Full, [(0, 128, 128, [0, 0, 0]), (255, 128, 128, [255, 255, 255]), (54, 99, 255, [254, 0, 0]), (182, 30, 12, [0, 255, 0]), (18, 255, 116, [0, 0, 254])]),
+        (ColorSpace::Bt2020, ColorRange::Limited, [(16, 128, 128, [0, 0, 0]), (235, 128, 128, [255, 255, 255]), (74, 97, 240, [255, 0, 1]), (164, 47, 25, [0, 254, 0]), (29, 240, 119, [0, 0, 255])]),
+        (ColorSpace::Bt2020, ColorRange::Full, [(0, 128, 128, [0, 0, 0]), (255, 128, 128, [255, 255, 255]), (67, 92, 255, [254, 0, 0]), (173, 36, 11, [0, 255, 0]), (15, 255, 118, [0, 0, 254])]),
+    ];
+
+    #[test]
+    fn matrix_reference_vectors() {
+        for (space, range, vectors) in REFERENCE_VECTORS {
+            let m = YuvMatrix::new(space, range);
+            for (name, (y, u, v, expected)) in ["black", "white", "red", "green", "blue"].into_iter().zip(vectors) {
+                let mut px = [0u8; 4];
+                m.convert(y as i32, u as i32, v as i32, &mut px);
+                // 係数は 13bit の固定小数点なので、ちょうど 0.5 付近の値は ±1 ずれることがある
+                let diff = px[..3].iter().zip(expected).map(|(&a, e)| a.abs_diff(e)).max().unwrap();
+                assert!(diff <= 1 && px[3] == 255, "{:?} {:?} {}: YCbCr ({}, {}, {}) gave {:?}, expected {:?}", space, range, name, y, u, v, px, expected);
+            }
+        }
+    }
+
+    #[test]
+    fn matrices_differ() {
+        // 同じ YCbCr でも色空間・レンジごとに異なる RGB になる (設定が係数に反映されている)
+        let mut seen = Vec::new();
+        for (space, range, _) in REFERENCE_VECTORS {
+            let mut px = [0u8; 4];
+            YuvMatrix::new(space, range).convert(81, 90, 240, &mut px);
+            assert!(!seen.contains(&px), "{:?} {:?} gave the same colour as another matrix", space, range);
+            seen.push(px);
+        }
+    }
+
+    #[test]
+    fn auto_matrix_by_resolution() {
+        let range = ColorRange::Limited;
+        assert_eq!(YuvMatrix::for_frame(ColorSpace::Auto, range, 1920, 1080), YuvMatrix::new(ColorSpace::Bt709, range));
+        assert_eq!(YuvMatrix::for_frame(ColorSpace::Auto, range, 1280, 720), YuvMatrix::new(ColorSpace::Bt709, range));
+        assert_eq!(YuvMatrix::for_frame(ColorSpace::Auto, range, 720, 480), YuvMatrix::new(ColorSpace::Bt601, range));
+        // 明示した色空間は解像度に関係なく使う
+        assert_eq!(YuvMatrix::for_frame(ColorSpace::Bt2020, range, 720, 480), YuvMatrix::new(ColorSpace::Bt2020, range));
+    }
+
+    // 再現可能な疑似乱数の入力 (P010 は 16bit 全域、上位詰めでない値も含める)
+    fn noise(len: usize, seed: u32) -> Vec<u8> {
+        let mut state = seed;
+        (0..len)
+            .map(|_| {
+                state = state.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+                (state >> 24) as u8
+            })
+            .collect()
+    }
+
+    // 並列化も SIMD も使わず1行ずつスカラー版で変換した基準
+    fn scalar_reference(format: PixelFormat, width: usize, height: usize, src: &[u8], m: &YuvMatrix) -> Vec<u8> {
+        let mut dst = vec![0u8; width * height * 4];
+        for (row, out) in dst.chunks_exact_mut(width * 4).enumerate() {
+            convert_row(format, row_src(format, width, height, src, row), out, m, SimdLevel::Scalar);
+        }
+        dst
+    }
+
+    const FORMATS: [PixelFormat; 4] = [PixelFormat::Yuy2, PixelFormat::Nv12, PixelFormat::I420, PixelFormat::P010];
+
+    #[test]
+    fn simd_matches_scalar() {
+        let matrices = [
+            YuvMatrix::new(ColorSpace::Bt601, ColorRange::Limited),
+            YuvMatrix::new(ColorSpace::Bt709, ColorRange::Full),
+            YuvMatrix::new(ColorSpace::Bt2020, ColorRange::Limited),
+        ];
+        // 奇数幅と、SSE2/NEON (8画素)・AVX2 (16画素) の倍数でない幅 (端数はスカラー・SSE2 に回る)
+        let widths = [1, 2, 3, 7, 8, 9, 14, 15, 16, 17, 23, 24, 31, 33, 40, 47, 62, 65, 130];
+        for simd in SimdLevel::available() {
+            for format in FORMATS {
+                for &width in widths.iter().filter(|&&w| format != PixelFormat::Yuy2 || w % 2 == 0) {
+                    for height in [1, 2, 3, 5] {
+                        let src = noise(format.frame_len(width, height), (width * 31 + height) as u32);
+                        for m in &matrices {
+                            let expected = scalar_reference(format, width, height, &src, m);
+                            let actual = convert(format, width, height, &src, m, simd);
+                            assert!(actual == expected, "{:?} {:?} {}x{} differs from scalar (matrix {:?})", simd, format, width, height, m);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn parallel_matches_scalar() {
+        // 並列変換される大きさ (帯の境界が 4:2:0 の色差行をまたがないこと、奇数の高さで最後の帯が1行になること)
+        let (width, height) = (1282, 723);
+        assert!(width * height >= PARALLEL_MIN_PIXELS);
+        let m = YuvMatrix::new(ColorSpace::Bt709, ColorRange::Limited);
+        for format in FORMATS {
+            let src = noise(format.frame_len(width, height), 7);
+            let expected = scalar_reference(format, width, height, &src, &m);
+            for simd in SimdLevel::available() {
+                assert!(convert(format, width, height, &src, &m, simd) == expected, "{:?} {:?} differs from scalar", simd, format);
+            }
+        }
+    }
+
+    #[test]
+    fn rejects_invalid_input() {
+        let m = full_range();
+        let mut dst = vec![0u8; W * H * 4];
+        // YUY2 は2画素単位なので奇数幅は扱えない
+        assert!(!yuv_to_rgba_into(PixelFormat::Yuy2, W, H, &[0; W * H * 2 + 2], &mut dst, &m, SimdLevel::Scalar));
+        // 入力が1バイトでも足りなければ変換しない
+        let src = nv12_420();
+        assert!(!yuv_to_rgba_into(PixelFormat::Nv12, W, H, &src[..src.len() - 1], &mut dst, &m, SimdLevel::Scalar));
+        assert!(!yuv_to_rgba_into(PixelFormat::Mjpeg, W, H, &src, &mut dst, &m, SimdLevel::Scalar));
+    }
+}
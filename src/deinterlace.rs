@@ -0,0 +1,243 @@
+use egui::Color32;
+use rayon::prelude::*;
+use std::time::{Duration, Instant};
+use crate::settings::{DeinterlaceMode, FieldOrder};
+
+// インターレース映像 (480i/1080i) のインターレース解除
+// デコード後の RGBA フレームに対して行い、結果を FrameBuffer に渡す
+
+// 動き適応で「動いている」とみなす前フレームとの差 (RGB 各成分の最大差)
+const MOTION_THRESHOLD: u8 = 24;
+// 倍速 bob で2枚目のフィールドを出すまでの最大待ち時間
+const MAX_FIELD_DELAY: Duration = Duration::from_millis(25);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Field {
+    Top,    // 偶数行 (0, 2, 4 ...)
+    Bottom, // 奇数行
+}
+
+impl Field {
+    fn contains(self, y: usize) -> bool {
+        y.is_multiple_of(2) == (self == Field::Top)
+    }
+
+    fn other(self) -> Field {
+        match self {
+            Field::Top => Field::Bottom,
+            Field::Bottom => Field::Top,
+        }
+    }
+}
+
+// 時間的に先のフィールド (Auto: 480 ライン (NTSC SD) は BFF、それ以外は TFF)
+pub fn first_field(order: FieldOrder, height: usize) -> Field {
+    match order {
+        FieldOrder::Tff => Field::Top,
+        FieldOrder::Bff => Field::Bottom,
+        FieldOrder::Auto if height == 480 => Field::Bottom,
+        FieldOrder::Auto => Field::Top,
+    }
+}
+
+fn average(a: Color32, b: Color32) -> Color32 {
+    let mix = |a: u8, b: u8| (a as u16 + b as u16).div_ceil(2) as u8;
+    Color32::from_rgb(mix(a.r(), b.r()), mix(a.g(), b.g()), mix(a.b(), b.b()))
+}
+
+fn max_difference(a: Color32, b: Color32) -> u8 {
+    a.r().abs_diff(b.r()).max(a.g().abs_diff(b.g())).max(a.b().abs_diff(b.b()))
+}
+
+// y 行目の上下の行 (端では反対側の行を使う)
+fn neighbours(y: usize, height: usize) -> (usize, usize) {
+    let above = if y > 0 { y - 1 } else { (y + 1).min(height - 1) };
+    let below = if y + 1 < height { y + 1 } else { y.saturating_sub(1) };
+    (above, below)
+}
+
+// field に含まれない行を上下の行 (= field の行) の平均で埋めた1行
+fn interpolate_row(src: &[Color32], width: usize, height: usize, y: usize, row: &mut [Color32]) {
+    let (above, below) = neighbours(y, height);
+    let above = &src[above * width..][..width];
+    let below = &src[below * width..][..width];
+    for ((out, &a), &b) in row.iter_mut().zip(above).zip(below) {
+        *out = average(a, b);
+    }
+}
+
+// bob: 片方のフィールドだけを使い、もう一方の行は補間する (1フィールド = 1フレーム)
+pub fn bob(src: &[Color32], width: usize, height: usize, field: Field, dst: &mut Vec<Color32>) {
+    dst.resize(width * height, Color32::BLACK);
+    dst.par_chunks_mut(width).enumerate().for_each(|(y, row)| {
+        if field.contains(y) {
+            row.copy_from_slice(&src[y * width..][..width]);
+        } else {
+            interpolate_row(src, width, height, y, row);
+        }
+    });
+}
+
+// 線形ブレンド: 上下の行と (1, 2, 1) の重みで混ぜて櫛状のノイズをぼかす
+pub fn blend(src: &[Color32], width: usize, height: usize, dst: &mut Vec<Color32>) {
+    dst.resize(width * height, Color32::BLACK);
+    dst.par_chunks_mut(width).enumerate().for_each(|(y, row)| {
+        let (above, below) = neighbours(y, height);
+        let rows = (&src[above * width..][..width], &src[y * width..][..width], &src[below * width..][..width]);
+        for (x, out) in row.iter_mut().enumerate() {
+            let (a, c, b) = (rows.0[x], rows.1[x], rows.2[x]);
+            let mix = |a: u8, c: u8, b: u8| ((a as u16 + 2 * c as u16 + b as u16 + 2) / 4) as u8;
+            *out = Color32::from_rgb(mix(a.r(), c.r(), b.r()), mix(a.g(), c.g(), b.g()), mix(a.b(), c.b(), b.b()));
+        }
+    });
+}
+
+// 動き適応: field の行はそのまま、もう一方の行は前フレームから変化のない画素だけ
+// そのまま使い (静止部分は縦解像度を保つ)、動いている画素は補間する
+// 前フレームがない場合はすべて補間 (bob と同じ)
+pub fn motion_adaptive(src: &[Color32], prev: Option<&[Color32]>, width: usize, height: usize, field: Field, dst: &mut Vec<Color32>) {
+    dst.resize(width * height, Color32::BLACK);
+    dst.par_chunks_mut(width).enumerate().for_each(|(y, row)| {
+        let current = &src[y * width..][..width];
+        if field.contains(y) {
+            row.copy_from_slice(current);
+            return;
+        }
+        interpolate_row(src, width, height, y, row);
+        if let Some(prev) = prev {
+            let previous = &prev[y * width..][..width];
+            for ((out, &c), &p) in row.iter_mut().zip(current).zip(previous) {
+                if max_difference(c, p) <= MOTION_THRESHOLD {
+                    *out = c;
+                }
+            }
+        }
+    });
+}
+
+// キャプチャコールバック内で使うインターレース解除の状態 (前フレームとフレーム間隔)
+pub struct Deinterlacer {
+    prev: Vec<Color32>,
+    prev_size: (usize, usize),
+    spare: Vec<Color32>,
+    last_input: Option<Instant>,
+    interval: Duration,
+}
+
+impl Default for Deinterlacer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Deinterlacer {
+    pub fn new() -> Self {
+        Self { prev: Vec::new(), prev_size: (0, 0), spare: Vec::new(), last_input: None, interval: Duration::ZERO }
+    }
+
+    // frame を解除済みの画像に置き換える
+    // 倍速 bob では2枚目のフィールドを second に書き込んで true を返す (field_delay の後に表示する)
+    pub fn process(&mut self, mode: DeinterlaceMode, order: FieldOrder, width: usize, height: usize, frame: &mut Vec<Color32>, second: &mut Vec<Color32>) -> bool {
+        let now = Instant::now();
+        if let Some(last) = self.last_input.replace(now) {
+            self.interval = now.duration_since(last);
+        }
+        if mode == DeinterlaceMode::Weave || width == 0 || height == 0 || frame.len() != width * height {
+            return false;
+        }
+
+        let field = first_field(order, height);
+        let prev = (self.prev_size == (width, height)).then_some(&self.prev[..]);
+        let mut out = std::mem::take(&mut self.spare);
+        match mode {
+            DeinterlaceMode::Bob => {
+                bob(frame, width, height, field, &mut out);
+                bob(frame, width, height, field.other(), second);
+            }
+            DeinterlaceMode::Blend => blend(frame, width, height, &mut out),
+            DeinterlaceMode::MotionAdaptive => motion_adaptive(frame, prev, width, height, field, &mut out),
+            DeinterlaceMode::Weave => unreachable!(),
+        }
+        // 入力は次フレームの比較用に残し、前回の比較用バッファは次の出力先に回す
+        let input = std::mem::replace(frame, out);
+        self.spare = std::mem::replace(&mut self.prev, input);
+        self.prev_size = (width, height);
+        mode == DeinterlaceMode::Bob
+    }
+
+    // 倍速 bob の2枚目を出すまでの待ち時間 (入力フレーム間隔の半分)
+    pub fn field_delay(&self) -> Duration {
+        (self.interval / 2).min(MAX_FIELD_DELAY)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TEST_WIDTH: usize = 16;
+    const TEST_HEIGHT: usize = 12;
+
+    // 偶数行 top、奇数行 bottom のフレーム
+    fn combed_frame(top: Color32, bottom: Color32) -> Vec<Color32> {
+        (0..TEST_WIDTH * TEST_HEIGHT)
+            .map(|i| if (i / TEST_WIDTH).is_multiple_of(2) { top } else { bottom })
+            .collect()
+    }
+
+    // 全画素が expected (各成分の誤差 1 まで) か確認
+    fn assert_uniform(frame: &[Color32], expected: Color32, what: &str) {
+        if let Some(i) = frame.iter().position(|&p| max_difference(p, expected) > 1) {
+            panic!("{}: pixel ({}, {}) is {:?}, expected {:?}", what, i % TEST_WIDTH, i / TEST_WIDTH, frame[i], expected);
+        }
+    }
+
+    #[test]
+    fn bob_splits_fields() {
+        let src = combed_frame(Color32::WHITE, Color32::BLACK);
+        let mut deinterlacer = Deinterlacer::new();
+        let (mut first, mut second) = (src.clone(), Vec::new());
+        assert!(deinterlacer.process(DeinterlaceMode::Bob, FieldOrder::Tff, TEST_WIDTH, TEST_HEIGHT, &mut first, &mut second));
+        // TFF: 1枚目は上のフィールド (白)、2枚目は下のフィールド (黒)
+        assert_uniform(&first, Color32::WHITE, "first field");
+        assert_uniform(&second, Color32::BLACK, "second field");
+    }
+
+    #[test]
+    fn blend_averages_fields() {
+        let src = combed_frame(Color32::WHITE, Color32::BLACK);
+        let mut out = Vec::new();
+        blend(&src, TEST_WIDTH, TEST_HEIGHT, &mut out);
+        assert_uniform(&out, Color32::from_gray(128), "blend");
+    }
+
+    #[test]
+    fn motion_adaptive_weaves_static_areas() {
+        let src = combed_frame(Color32::WHITE, Color32::BLACK);
+        let mut out = Vec::new();
+        // 静止 (前フレームと同じ) なら両フィールドをそのまま使う
+        motion_adaptive(&src, Some(&src), TEST_WIDTH, TEST_HEIGHT, Field::Top, &mut out);
+        assert_eq!(out, src, "static frame was not woven");
+        // 下のフィールドだけ変化した (前フレームは全面白) なら補間される
+        let prev = vec![Color32::WHITE; TEST_WIDTH * TEST_HEIGHT];
+        motion_adaptive(&src, Some(&prev), TEST_WIDTH, TEST_HEIGHT, Field::Top, &mut out);
+        assert_uniform(&out, Color32::WHITE, "moving frame");
+    }
+
+    #[test]
+    fn weave_leaves_frame_untouched() {
+        let src = combed_frame(Color32::WHITE, Color32::BLACK);
+        let mut deinterlacer = Deinterlacer::new();
+        let (mut frame, mut second) = (src.clone(), Vec::new());
+        assert!(!deinterlacer.process(DeinterlaceMode::Weave, FieldOrder::Auto, TEST_WIDTH, TEST_HEIGHT, &mut frame, &mut second));
+        assert_eq!(frame, src);
+    }
+
+    #[test]
+    fn field_order() {
+        let cases = [(FieldOrder::Auto, 480, Field::Bottom), (FieldOrder::Auto, 1080, Field::Top), (FieldOrder::Tff, 480, Field::Top), (FieldOrder::Bff, 1080, Field::Bottom)];
+        for (order, height, expected) in cases {
+            assert_eq!(first_field(order, height), expected, "{:?} at {} lines", order, height);
+        }
+    }
+}
@@ -0,0 +1,510 @@
+use std::path::PathBuf;
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+use crate::audio::{AudioCapture, AudioTap};
+use crate::lut::{self, LutProcessor};
+use crate::settings::{AudioSettings, VideoSettings, DeviceId, LutInterpolation, LutSettings, PictureAdjustments};
+use crate::video::{self, Capabilities, FrameReader, ModeChange, SignalState, VideoCapture};
+use crate::watchdog::{self, SignalWatchdog};
+
+// 映像・音声デバイスの接続/切断を行うバックグラウンドワーカー
+// UI スレッドはコマンドを送って状態を表示するだけにし、接続待ちでウィンドウが固まらないようにする
+
+// 信号断の監視間隔 (コマンドがなくてもこの間隔で確認する)
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+// デバイスの状態
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum DeviceState {
+    // デバイス未選択・停止中
+    #[default]
+    Idle,
+    Connecting,
+    Running,
+    // 接続失敗 (理由)
+    Failed(String),
+}
+
+// UI に公開するワーカーの状態
+#[derive(Debug, Clone, Default)]
+pub struct DeviceStatus {
+    pub video: DeviceState,
+    pub audio: DeviceState,
+    // 実際に開いた映像デバイスの (設定上の名前, 識別情報) (設定への保存用)
+    pub opened_video: Option<(String, DeviceId)>,
+    // 信号断で再接続中なら (再接続回数, 次の試行の予定時刻)
+    pub reconnecting: Option<(u32, Option<Instant>)>,
+    // LUT ファイルを読み込めなかった理由
+    pub lut_error: Option<String>,
+}
+
+enum Command {
+    // 設定を反映 (変更のあったデバイスだけ開き直す、force なら両方開き直す)
+    Apply { video: Box<VideoSettings>, audio: AudioSettings, force: bool },
+    SetVolume(f32),
+    // 画質調整だけを反映 (設定画面のスライダー操作中)
+    SetPicture(PictureAdjustments),
+    // LUT の設定だけを反映 (ファイルは変更があったときだけ読み直す)
+    SetLut(LutSettings),
+    Shutdown,
+}
+
+pub struct DeviceWorker {
+    commands: Sender<Command>,
+    status: Arc<Mutex<DeviceStatus>>,
+    frames: FrameReader,
+    // 録画用の入力音声の分岐 (音声デバイスを開き直しても同じもの)
+    audio_tap: AudioTap,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl DeviceWorker {
+    pub fn spawn() -> Self {
+        let video = VideoCapture::new();
+        let frames = video.frame_reader();
+        let status = Arc::new(Mutex::new(DeviceStatus::default()));
+        let (commands, receiver) = mpsc::channel();
+        let audio_tap = AudioTap::default();
+        let worker_tap = audio_tap.clone();
+
+        let worker_status = status.clone();
+        let thread = std::thread::Builder::new()
+            .name("device-worker".to_string())
+            .spawn(move || {
+                // cpal のストリームはスレッドをまたげないため、音声はワーカー内で作成する
+                Worker::new(video, worker_tap, worker_status).run(receiver);
+            });
+        let thread = match thread {
+            Ok(thread) => Some(thread),
+            Err(e) => {
+                println!("Warning: Failed to start device worker: {}", e);
+                None
+            }
+        };
+        Self { commands, status, frames, audio_tap, thread }
+    }
+
+    // 設定を反映する (接続はワーカー側で行い、すぐに戻る)
+    pub fn apply(&self, video: &VideoSettings, audio: &AudioSettings, force: bool) {
+        let _ = self.commands.send(Command::Apply { video: Box::new(video.clone()), audio: audio.clone(), force });
+    }
+
+    pub fn set_volume(&self, volume: f32) {
+        let _ = self.commands.send(Command::SetVolume(volume));
+    }
+
+    pub fn set_picture(&self, adjustments: PictureAdjustments) {
+        let _ = self.commands.send(Command::SetPicture(adjustments));
+    }
+
+    pub fn set_lut(&self, settings: LutSettings) {
+        let _ = self.commands.send(Command::SetLut(settings));
+    }
+
+    pub fn status(&self) -> DeviceStatus {
+        self.status.lock().map(|s| s.clone()).unwrap_or_default()
+    }
+
+    pub fn frames(&self) -> &FrameReader {
+        &self.frames
+    }
+
+    pub fn audio_tap(&self) -> &AudioTap {
+        &self.audio_tap
+    }
+}
+
+impl Drop for DeviceWorker {
+    fn drop(&mut self) {
+        let _ = self.commands.send(Command::Shutdown);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+// 映像デバイスを開き直す必要がある設定変更か
+// 識別情報は、名前だけの設定に実際に開いたデバイスの情報が補完された場合を変更とみなさない
+fn video_needs_restart(applied: &VideoSettings, requested: &VideoSettings, opened: Option<&DeviceId>) -> bool {
+    applied.device_name != requested.device_name
+        || (applied.device_id != requested.device_id && requested.device_id.as_ref() != opened)
+        || applied.resolution != requested.resolution
+        || applied.format != requested.format
+        || applied.fps != requested.fps
+        || applied.backend != requested.backend
+}
+
+fn audio_needs_restart(applied: &AudioSettings, requested: &AudioSettings) -> bool {
+    applied.input_device_name != requested.input_device_name
+        || applied.input_device_index != requested.input_device_index
+        || applied.output_device_name != requested.output_device_name
+        || applied.output_device_index != requested.output_device_index
+        || applied.sample_rate != requested.sample_rate
+        || applied.channels != requested.channels
+}
+
+// 検出した実際の解像度に最も近い対応モードを選ぶ
+// 実際の解像度が推定できない場合は None (設定どおりに開き直せばデバイス側で現在のモードが選ばれる)
+fn renegotiated_mode(settings: &VideoSettings, change: &ModeChange) -> Option<(u32, u32, u32)> {
+    let (width, height) = change.detected.map(|(w, h)| (w as u32, h as u32))?;
+    let fps = settings.fps.unwrap_or(60);
+    let caps = settings.device_name.as_deref()
+        .map(|name| VideoCapture::device_capabilities(settings.backend, name, settings.device_id.as_ref()));
+    match caps {
+        Some(Capabilities::Known(caps)) => video::closest_mode(&caps, change.format.name(), width, height, fps),
+        _ => Some((width, height, fps)),
+    }
+}
+
+// 音声の接続に失敗したときの次の再試行 (再試行回数, 予定時刻)
+// 映像のウォッチドッグと同じ間隔 (2秒から倍々、最大30秒) で伸ばしていく
+fn next_audio_retry(previous: Option<(u32, Instant)>, now: Instant) -> (u32, Instant) {
+    let attempts = previous.map_or(0, |(n, _)| n) + 1;
+    (attempts, now + watchdog::retry_delay(attempts))
+}
+
+// 音声の再試行の予定時刻を過ぎたか (失敗していなければ false)
+fn audio_retry_due(retry: Option<(u32, Instant)>, now: Instant) -> bool {
+    retry.is_some_and(|(_, next)| now >= next)
+}
+
+// ワーカースレッド側の状態
+struct Worker {
+    video: VideoCapture,
+    audio: AudioCapture,
+    status: Arc<Mutex<DeviceStatus>>,
+    watchdog: SignalWatchdog,
+    // 最後に適用した設定 (未適用なら None)
+    video_settings: Option<VideoSettings>,
+    audio_settings: Option<AudioSettings>,
+    // 入力側の解像度変更に合わせて選び直したモード (幅, 高さ, FPS)、設定を変更するまで優先する
+    source_mode: Option<(u32, u32, u32)>,
+    // 最後に開き直しの対象にした映像モード変更
+    renegotiated_for: Option<ModeChange>,
+    // 音声デバイスの接続に失敗している間の (再試行回数, 次の試行の予定時刻)
+    audio_retry: Option<(u32, Instant)>,
+    // 読み込んだ LUT (ファイル, 補間方法, 読み込み結果)
+    lut: Option<(PathBuf, LutInterpolation, Result<LutProcessor, String>)>,
+}
+
+impl Worker {
+    fn new(video: VideoCapture, audio_tap: AudioTap, status: Arc<Mutex<DeviceStatus>>) -> Self {
+        Self {
+            video,
+            audio: AudioCapture::new(audio_tap),
+            status,
+            watchdog: SignalWatchdog::new(watchdog::SIGNAL_TIMEOUT),
+            video_settings: None,
+            audio_settings: None,
+            source_mode: None,
+            renegotiated_for: None,
+            audio_retry: None,
+            lut: None,
+        }
+    }
+
+    fn run(&mut self, commands: Receiver<Command>) {
+        loop {
+            match commands.recv_timeout(POLL_INTERVAL) {
+                Ok(first) => {
+                    // 溜まったコマンドをまとめて処理し、設定は最新のものだけ適用する
+                    let mut apply: Option<(VideoSettings, AudioSettings, bool)> = None;
+                    for command in std::iter::once(first).chain(commands.try_iter()) {
+                        match command {
+                            Command::Apply { video, audio, force } => {
+                                let force = force || apply.as_ref().is_some_and(|(_, _, f)| *f);
+                                apply = Some((*video, audio, force));
+                            }
+                            Command::SetVolume(volume) => self.audio.set_volume(volume),
+                            Command::SetPicture(adjustments) => self.video.set_picture(&adjustments),
+                            Command::SetLut(settings) => self.apply_lut(&settings),
+                            Command::Shutdown => return self.shutdown(),
+                        }
+                    }
+                    if let Some((video, audio, force)) = apply {
+                        self.apply(video, audio, force);
+                    }
+                }
+                Err(RecvTimeoutError::Timeout) => {}
+                Err(RecvTimeoutError::Disconnected) => return self.shutdown(),
+            }
+            self.check_mode_change();
+            self.check_signal();
+            self.check_audio();
+        }
+    }
+
+    fn apply_lut(&mut self, settings: &LutSettings) {
+        let Some(path) = &settings.path else {
+            self.lut = None;
+            self.video.set_lut(None, false);
+            self.update_status(|status| status.lut_error = None);
+            return;
+        };
+        let loaded = self.lut.as_ref().is_some_and(|(p, interpolation, _)| p == path && *interpolation == settings.interpolation);
+        if !loaded {
+            let processor = lut::load_cube(path).map(|cube| {
+                println!("Debug: Loaded {}-point 3D LUT from {}", cube.size, path.display());
+                LutProcessor::new(&cube, settings.interpolation)
+            });
+            if let Err(e) = &processor {
+                println!("Warning: Failed to load LUT: {}", e);
+            }
+            self.lut = Some((path.clone(), settings.interpolation, processor));
+        }
+        let processor = match self.lut.as_ref().map(|(_, _, processor)| processor) {
+            Some(Ok(processor)) => Ok(processor.clone().with_strength(settings.strength)),
+            Some(Err(e)) => Err(e.clone()),
+            None => return,
+        };
+        let error = processor.as_ref().err().cloned();
+        // 比較表示中は補正前の画像も表示用に残す
+        self.video.set_lut(processor.ok(), settings.split_view);
+        self.update_status(|status| status.lut_error = error);
+    }
+
+    fn shutdown(&mut self) {
+        println!("Debug: Device worker shutting down");
+        self.video.stop_capture();
+        self.audio.stop_capture();
+    }
+
+    fn update_status(&self, f: impl FnOnce(&mut DeviceStatus)) {
+        if let Ok(mut status) = self.status.lock() {
+            f(&mut status);
+        }
+    }
+
+    fn apply(&mut self, video: VideoSettings, audio: AudioSettings, force: bool) {
+        // 色空間・レンジは再接続なしで反映
+        self.video.set_color_format(video.color_space, video.color_range);
+        self.video.set_deinterlace(video.deinterlace, video.field_order);
+        self.video.set_picture(&video.profile().picture);
+        self.apply_lut(&video.lut);
+
+        let opened = self.video.device_id();
+        let settings_changed = self.video_settings.as_ref()
+            .is_none_or(|applied| video_needs_restart(applied, &video, opened.as_ref()));
+        if settings_changed {
+            self.source_mode = None;
+            self.renegotiated_for = None;
+        }
+        let restart_video = force || settings_changed;
+        // 接続に失敗したままの音声は check_audio が間隔を伸ばしながら再試行する
+        let first_audio = self.audio_settings.is_none();
+        let restart_audio = force || self.audio_settings.as_ref()
+            .is_none_or(|applied| audio_needs_restart(applied, &audio));
+
+        if restart_video {
+            self.start_video(&video);
+        }
+        if restart_audio {
+            self.audio_retry = None;
+            self.start_audio(&audio, first_audio);
+        }
+        self.audio.set_audio_passthrough_enabled(audio.passthrough_enabled);
+
+        self.video_settings = Some(video);
+        self.audio_settings = Some(audio);
+    }
+
+    fn start_video(&mut self, settings: &VideoSettings) {
+        if settings.device_name.is_none() {
+            self.video.stop_capture();
+            self.update_status(|s| { s.video = DeviceState::Idle; s.opened_video = None; });
+            return;
+        }
+
+        println!("Debug: Starting video device connection: {:?}", settings.device_name);
+        self.update_status(|s| s.video = DeviceState::Connecting);
+        self.video.set_backend(settings.backend);
+        let (resolution, fps) = match self.source_mode {
+            Some((w, h, fps)) => (Some((w, h)), Some(fps)),
+            None => (settings.resolution, settings.fps),
+        };
+        let result = self.video.start_capture(
+            settings.device_name.as_deref(),
+            settings.device_id.as_ref(),
+            resolution,
+            settings.format.as_deref(),
+            fps,
+        );
+        let state = match result {
+            Ok(()) => {
+                println!("Debug: Video device connected successfully");
+                DeviceState::Running
+            }
+            Err(e) => {
+                // 再試行は信号断と同じくウォッチドッグに任せる (バックオフ付き)
+                println!("Warning: Video capture failed: {}", e);
+                DeviceState::Failed(e)
+            }
+        };
+        let opened = self.video.device_id().zip(settings.device_name.clone()).map(|(id, name)| (name, id));
+        self.update_status(|s| { s.video = state; s.opened_video = opened; });
+    }
+
+    fn start_audio(&mut self, settings: &AudioSettings, first: bool) {
+        println!("Debug: Starting audio device connection");
+        // 再試行のたびにデバイス一覧を取り直さない
+        if self.audio_retry.is_none() {
+            println!("Debug: Input device: {:?}", settings.input_device_name);
+            println!("Debug: Output device: {:?}", settings.output_device_name);
+            println!("Debug: Available input devices: {:?}", AudioCapture::list_input_devices());
+            println!("Debug: Available output devices: {:?}", AudioCapture::list_output_devices());
+        }
+        self.update_status(|s| s.audio = DeviceState::Connecting);
+
+        let mut result = self.audio.start_passthrough_with_settings(
+            settings.input_device_name.as_deref(),
+            settings.input_device_index,
+            settings.output_device_name.as_deref(),
+            settings.output_device_index,
+            settings.sample_rate,
+            settings.channels,
+        );
+        // 起動直後に失敗した場合はデフォルトデバイスを試行
+        if first && result.is_err() {
+            println!("Debug: Configured audio devices failed, trying with default devices...");
+            if self.audio.start_passthrough_with_settings(None, None, None, None, None, None).is_ok() {
+                result = Ok(());
+            }
+        }
+        let state = match result {
+            Ok(()) => {
+                println!("Debug: Audio devices connected successfully");
+                self.audio_retry = None;
+                DeviceState::Running
+            }
+            Err(e) => {
+                let now = Instant::now();
+                let retry = next_audio_retry(self.audio_retry, now);
+                println!("Warning: Audio capture failed: {} (retrying in {:.0}s)", e, (retry.1 - now).as_secs_f32());
+                self.audio_retry = Some(retry);
+                DeviceState::Failed(e)
+            }
+        };
+        self.update_status(|s| s.audio = state);
+    }
+
+    // 接続に失敗した音声デバイスを予定時刻になったら開き直す
+    fn check_audio(&mut self) {
+        if let Some(audio) = self.audio_settings.clone().filter(|_| audio_retry_due(self.audio_retry, Instant::now())) {
+            self.start_audio(&audio, false);
+        }
+    }
+
+    // 入力側で解像度が変わったら、最も近い対応モードで映像を開き直す
+    fn check_mode_change(&mut self) {
+        let Some(change) = self.video.take_mode_change() else {
+            // 開き直した後に映像が届いていれば、次の変更に備えて記録を消す
+            if matches!(self.video.signal_state(), SignalState::Receiving(_)) {
+                self.renegotiated_for = None;
+            }
+            return;
+        };
+        let Some(settings) = self.video_settings.clone() else { return };
+        // 開き直しても同じ食い違いが続く場合はウォッチドッグの再接続 (バックオフ付き) に任せる
+        if self.renegotiated_for == Some(change) {
+            println!("Warning: Source still does not match the negotiated mode after renegotiation");
+            return;
+        }
+        let mode = renegotiated_mode(&settings, &change);
+        println!("Debug: Renegotiating video mode: {:?}", mode);
+        self.source_mode = mode;
+        self.renegotiated_for = Some(change);
+        self.start_video(&settings);
+    }
+
+    // 映像が途絶えていたら映像・音声を開き直す (間隔はウォッチドッグが管理)
+    fn check_signal(&mut self) {
+        let has_device = self.video_settings.as_ref().is_some_and(|v| v.device_name.is_some());
+        if !has_device {
+            self.watchdog.reset();
+        } else if self.watchdog.poll(self.video.signal_state(), Instant::now()) {
+            if let (Some(video), Some(audio)) = (self.video_settings.clone(), self.audio_settings.clone()) {
+                self.start_video(&video);
+                self.start_audio(&audio, false);
+            }
+        }
+        let reconnecting = self.watchdog.is_lost().then(|| (self.watchdog.attempts(), self.watchdog.next_attempt()));
+        self.update_status(|s| s.reconnecting = reconnecting);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::file_source;
+    use crate::test_pattern::{TestPatternSource, TEST_PATTERN_DEVICE};
+
+    fn worker() -> (Worker, Arc<Mutex<DeviceStatus>>) {
+        let status = Arc::new(Mutex::new(DeviceStatus::default()));
+        (Worker::new(VideoCapture::new(), AudioTap::default(), status.clone()), status)
+    }
+
+    fn test_pattern_settings() -> VideoSettings {
+        VideoSettings { device_name: Some(TEST_PATTERN_DEVICE.to_string()), resolution: Some((1280, 720)), fps: Some(60), ..VideoSettings::default() }
+    }
+
+    #[test]
+    fn video_state_follows_connection_result() {
+        let (mut worker, status) = worker();
+        let video_state = || status.lock().unwrap().video.clone();
+        assert_eq!(video_state(), DeviceState::Idle);
+        let mut settings = test_pattern_settings();
+        worker.start_video(&settings);
+        assert_eq!(video_state(), DeviceState::Running);
+        // 開けないデバイスは理由付きで失敗になり、開き直せば戻る
+        settings.device_name = Some(file_source::file_device_name(std::path::Path::new("/nonexistent/capture.y4m")));
+        worker.start_video(&settings);
+        assert!(matches!(video_state(), DeviceState::Failed(e) if e.contains("Failed to open")));
+        worker.start_video(&test_pattern_settings());
+        assert_eq!(video_state(), DeviceState::Running);
+        // デバイス未選択なら停止
+        settings.device_name = None;
+        worker.start_video(&settings);
+        assert_eq!(video_state(), DeviceState::Idle);
+        assert!(worker.video.device_id().is_none() && status.lock().unwrap().opened_video.is_none());
+        worker.shutdown();
+    }
+
+    #[test]
+    fn renegotiates_after_source_mode_change() {
+        // 途中で 1920x1200 に切り替わるテストパターンを流し、ワーカーが対応モード (1920x1080) で開き直すことを確認する
+        let (mut worker, status) = worker();
+        worker.video_settings = Some(test_pattern_settings());
+        let switching = TestPatternSource::new(Some((1280, 720)), None, Some(60)).with_resolution_change(5, (1920, 1200));
+        worker.video.start_source(Box::new(switching)).unwrap();
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while worker.source_mode.is_none() {
+            assert!(Instant::now() < deadline, "mode change was not handled");
+            worker.check_mode_change();
+            std::thread::sleep(Duration::from_millis(5));
+        }
+        assert_eq!(worker.source_mode, Some((1920, 1080, 60)));
+        assert_eq!(status.lock().unwrap().video, DeviceState::Running);
+        worker.shutdown();
+    }
+
+    #[test]
+    fn audio_retry_backs_off() {
+        let start = Instant::now();
+        let mut retry = None;
+        let mut delays = Vec::new();
+        for _ in 0..7 {
+            let next = next_audio_retry(retry, start);
+            delays.push((next.1 - start).as_secs());
+            retry = Some(next);
+        }
+        assert_eq!(delays, [2, 4, 8, 16, 30, 30, 30]);
+        assert_eq!(retry.map(|(n, _)| n), Some(7));
+        // 予定時刻になるまでは再試行しない (成功して None になれば再試行しない)
+        let retry = Some(next_audio_retry(None, start));
+        assert!(!audio_retry_due(retry, start + Duration::from_millis(1999)));
+        assert!(audio_retry_due(retry, start + Duration::from_secs(2)));
+        assert!(!audio_retry_due(None, start + Duration::from_secs(60)));
+    }
+}
@@ -0,0 +1,411 @@
+use std::fs::File;
+use std::io::{BufRead, BufReader, Read, Seek, SeekFrom};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use crate::convert::PixelFormat;
+use crate::source::{FramePacer, FrameSink, RawFrame, VideoSource};
+use crate::video::DeviceCapabilities;
+
+// 録画済みファイル (YUV4MPEG2 / 生の YUYV・NV12 ダンプ) を再生するソース
+// 色や表示タイミングの不具合をユーザーのキャプチャから再現するためのもの
+
+// デバイス名の接頭辞 ("file:" + パス で設定ファイルに保存される)
+pub const FILE_DEVICE_PREFIX: &str = "file:";
+
+pub fn file_device_name(path: &Path) -> String {
+    format!("{}{}", FILE_DEVICE_PREFIX, path.display())
+}
+
+// ファイルデバイスならパスを返す
+pub fn file_path_of(device_name: &str) -> Option<&str> {
+    device_name.strip_prefix(FILE_DEVICE_PREFIX)
+}
+
+// ファイル選択ダイアログ用の拡張子
+pub const FILE_EXTENSIONS: [&str; 5] = ["y4m", "yuyv", "yuy2", "nv12", "yuv"];
+
+// ファイルから読み取ったストリーム情報
+#[derive(Debug, Clone, Copy)]
+struct StreamInfo {
+    width: usize,
+    height: usize,
+    format: PixelFormat,
+    fps: f64,
+    // Y4M はフレームごとに "FRAME" ヘッダが付く
+    y4m: bool,
+    // 最初のフレームの位置 (ループ時に戻る位置)
+    data_start: u64,
+}
+
+// YUV4MPEG2 のストリームヘッダを解析
+fn parse_y4m_header(line: &str) -> Result<(usize, usize, PixelFormat, f64), String> {
+    let mut tokens = line.split_ascii_whitespace();
+    if tokens.next() != Some("YUV4MPEG2") {
+        return Err("Not a YUV4MPEG2 file".to_string());
+    }
+    let (mut width, mut height, mut fps) = (0, 0, 30.0);
+    let mut format = PixelFormat::I420;
+    for token in tokens {
+        let mut chars = token.chars();
+        let tag = chars.next();
+        let value = chars.as_str();
+        match tag {
+            Some('W') => width = value.parse().map_err(|_| format!("Invalid width '{}'", value))?,
+            Some('H') => height = value.parse().map_err(|_| format!("Invalid height '{}'", value))?,
+            Some('F') => {
+                let (num, den) = value.split_once(':').ok_or_else(|| format!("Invalid frame rate '{}'", value))?;
+                let (num, den): (f64, f64) = (num.parse().unwrap_or(0.0), den.parse().unwrap_or(0.0));
+                if num > 0.0 && den > 0.0 { fps = num / den; }
+            }
+            Some('C') => {
+                format = match value {
+                    "420" | "420jpeg" | "420paldv" | "420mpeg2" => PixelFormat::I420,
+                    other => return Err(format!("Unsupported Y4M colour space C{}", other)),
+                };
+            }
+            _ => {} // インターレース・アスペクト比等は無視
+        }
+    }
+    if width == 0 || height == 0 {
+        return Err("Y4M header has no frame size".to_string());
+    }
+    Ok((width, height, format, fps))
+}
+
+// ファイル名から "1920x1080" 形式の解像度と "60fps" 形式のフレームレートを拾う
+fn size_and_fps_from_name(path: &Path) -> (Option<(usize, usize)>, Option<f64>) {
+    let stem = path.file_stem().map(|s| s.to_string_lossy().to_lowercase()).unwrap_or_default();
+    let mut size = None;
+    let mut fps = None;
+    // "59.94fps" のような小数を残すため '.' では区切らない (拡張子は file_stem で除かれている)
+    for part in stem.split(['_', '-', ' ']) {
+        if let Some((w, h)) = part.split_once('x') {
+            if let (Ok(w), Ok(h)) = (w.parse(), h.parse()) {
+                size = Some((w, h));
+            }
+        } else if let Some(n) = part.strip_suffix("fps") {
+            if let Ok(n) = n.parse::<f64>() {
+                if n > 0.0 { fps = Some(n); }
+            }
+        }
+    }
+    (size, fps)
+}
+
+// 生ダンプのフォーマット (拡張子優先、不明なら設定のフォーマット)
+fn raw_format_of(path: &Path, format: Option<&str>) -> Result<PixelFormat, String> {
+    let ext = path.extension().map(|e| e.to_string_lossy().to_lowercase()).unwrap_or_default();
+    let from_ext = match ext.as_str() {
+        "yuyv" | "yuy2" => Some(PixelFormat::Yuy2),
+        "nv12" => Some(PixelFormat::Nv12),
+        _ => None,
+    };
+    match from_ext.or_else(|| format.and_then(PixelFormat::from_name)) {
+        Some(f @ (PixelFormat::Yuy2 | PixelFormat::Nv12)) => Ok(f),
+        Some(other) => Err(format!("Raw {} files are not supported (use YUYV or NV12)", other.name())),
+        None => Err(format!("Unknown raw format for '{}'", path.display())),
+    }
+}
+
+// ファイルを開いてストリーム情報を取得
+// 生ダンプの解像度・FPS はファイル名 -> 設定値 の順で決定
+fn probe(path: &Path, resolution: Option<(u32, u32)>, format: Option<&str>, fps: Option<u32>) -> Result<StreamInfo, String> {
+    let file = File::open(path).map_err(|e| format!("Failed to open '{}': {}", path.display(), e))?;
+    let is_y4m = path.extension().is_some_and(|e| e.eq_ignore_ascii_case("y4m"));
+    if is_y4m {
+        let mut reader = BufReader::new(file);
+        let mut header = String::new();
+        reader.read_line(&mut header).map_err(|e| format!("Failed to read Y4M header: {}", e))?;
+        let (width, height, format, fps) = parse_y4m_header(header.trim_end())?;
+        return Ok(StreamInfo { width, height, format, fps, y4m: true, data_start: header.len() as u64 });
+    }
+    let (name_size, name_fps) = size_and_fps_from_name(path);
+    let (width, height) = name_size
+        .or(resolution.map(|(w, h)| (w as usize, h as usize)))
+        .ok_or_else(|| format!("Cannot determine frame size of '{}' (name it like capture_1920x1080_60fps.yuyv)", path.display()))?;
+    let format = raw_format_of(path, format)?;
+    let fps = name_fps.or(fps.map(|f| f as f64)).unwrap_or(60.0);
+    let frame_len = format.frame_len(width, height) as u64;
+    let file_len = file.metadata().map(|m| m.len()).unwrap_or(0);
+    if file_len < frame_len {
+        return Err(format!("'{}' is smaller than one {}x{} {} frame", path.display(), width, height, format.name()));
+    }
+    if file_len % frame_len != 0 {
+        println!("Warning: '{}' has {} trailing bytes, ignoring partial frame", path.display(), file_len % frame_len);
+    }
+    Ok(StreamInfo { width, height, format, fps, y4m: false, data_start: 0 })
+}
+
+// 設定画面用: ファイルの実際のフォーマット・解像度を1件だけ返す
+// 生ダンプで解像度が分からない場合は不明 (設定画面で手入力) とする
+pub fn capabilities(path: &str) -> Result<DeviceCapabilities, String> {
+    let path = Path::new(path);
+    if path.extension().is_some_and(|e| e.eq_ignore_ascii_case("y4m")) {
+        let info = probe(path, None, None, None)?;
+        return Ok(vec![(info.format.name().to_string(), vec![(info.width as u32, info.height as u32, info.fps.round() as u32)])]);
+    }
+    let (size, fps) = size_and_fps_from_name(path);
+    let (w, h) = size.ok_or_else(|| format!("Frame size of '{}' is not in the file name", path.display()))?;
+    let modes = vec![(w as u32, h as u32, fps.unwrap_or(60.0).round() as u32)];
+    let formats = match raw_format_of(path, None) {
+        Ok(f) => vec![f],
+        Err(_) => vec![PixelFormat::Yuy2, PixelFormat::Nv12],
+    };
+    Ok(formats.iter().map(|f| (f.name().to_string(), modes.clone())).collect())
+}
+
+// 1フレーム読み込み (終端なら false)
+fn read_frame(reader: &mut BufReader<File>, info: &StreamInfo, buf: &mut [u8]) -> std::io::Result<bool> {
+    if info.y4m {
+        let mut header = Vec::new();
+        if reader.read_until(b'\n', &mut header)? == 0 {
+            return Ok(false);
+        }
+        if !header.starts_with(b"FRAME") {
+            return Err(std::io::Error::new(std::io::ErrorKind::InvalidData, "missing FRAME marker"));
+        }
+    }
+    match reader.read_exact(buf) {
+        Ok(()) => Ok(true),
+        Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => Ok(false),
+        Err(e) => Err(e),
+    }
+}
+
+pub struct FileSource {
+    path: String,
+    info: StreamInfo,
+    running: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl FileSource {
+    pub fn new(path: &str, resolution: Option<(u32, u32)>, format: Option<&str>, fps: Option<u32>) -> Result<Self, String> {
+        let info = probe(Path::new(path), resolution, format, fps)?;
+        Ok(Self { path: path.to_string(), info, running: Arc::new(AtomicBool::new(false)), thread: None })
+    }
+}
+
+impl VideoSource for FileSource {
+    fn start(&mut self, mut sink: FrameSink) -> Result<(), String> {
+        self.stop();
+        let info = self.info;
+        let file = File::open(&self.path).map_err(|e| format!("Failed to open '{}': {}", self.path, e))?;
+        println!("Debug: Playing '{}' ({}x{} {} @{:.3}fps, looping)", self.path, info.width, info.height, info.format.name(), info.fps);
+        let running = self.running.clone();
+        running.store(true, Ordering::SeqCst);
+        let thread = std::thread::Builder::new()
+            .name("file-source".to_string())
+            .spawn(move || {
+                let mut reader = BufReader::new(file);
+                let mut buf = vec![0u8; info.format.frame_len(info.width, info.height)];
+                let mut pacer = FramePacer::new(info.fps);
+                let mut frames_in_pass = 0u64;
+                if reader.seek(SeekFrom::Start(info.data_start)).is_err() {
+                    return;
+                }
+                while running.load(Ordering::SeqCst) {
+                    match read_frame(&mut reader, &info, &mut buf) {
+                        Ok(true) => {
+                            frames_in_pass += 1;
+                            sink(RawFrame { width: info.width, height: info.height, format: Some(info.format), data: &buf, fallback: None });
+                        }
+                        Ok(false) => {
+                            // 終端: 先頭に戻ってループ (1フレームもなければ停止)
+                            if frames_in_pass == 0 || reader.seek(SeekFrom::Start(info.data_start)).is_err() {
+                                println!("Warning: No frames could be read from file, stopping playback");
+                                break;
+                            }
+                            frames_in_pass = 0;
+                            continue;
+                        }
+                        Err(e) => {
+                            println!("Warning: Failed to read frame: {}", e);
+                            break;
+                        }
+                    }
+                    pacer.wait();
+                }
+            })
+            .map_err(|e| format!("Failed to start file playback thread: {}", e))?;
+        self.thread = Some(thread);
+        Ok(())
+    }
+
+    fn stop(&mut self) {
+        self.running.store(false, Ordering::SeqCst);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+impl Drop for FileSource {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+    use std::sync::Mutex;
+    use std::time::{Duration, Instant};
+
+    // テストごとの一時ファイル (拡張子でフォーマットが決まる)、drop で削除
+    struct TempFile(PathBuf);
+
+    impl TempFile {
+        fn new(name: &str, contents: &[u8]) -> Self {
+            let path = std::env::temp_dir().join(format!("capturecard_viewer_{}_{}", std::process::id(), name));
+            std::fs::write(&path, contents).unwrap();
+            Self(path)
+        }
+    }
+
+    impl Drop for TempFile {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_file(&self.0);
+        }
+    }
+
+    // 2x2 の I420 フレーム (6 バイト) を fill で埋めた Y4M
+    fn y4m(header: &str, fills: &[u8]) -> Vec<u8> {
+        let mut data = format!("{}\n", header).into_bytes();
+        for &fill in fills {
+            data.extend_from_slice(b"FRAME\n");
+            data.extend_from_slice(&[fill; 6]);
+        }
+        data
+    }
+
+    fn open(path: &Path, info: &StreamInfo) -> BufReader<File> {
+        let mut reader = BufReader::new(File::open(path).unwrap());
+        reader.seek(SeekFrom::Start(info.data_start)).unwrap();
+        reader
+    }
+
+    #[test]
+    fn y4m_header_tags() {
+        let (w, h, format, fps) = parse_y4m_header("YUV4MPEG2 W1920 H1080 F30000:1001 It A1:1 C420jpeg").unwrap();
+        assert_eq!((w, h, format), (1920, 1080, PixelFormat::I420));
+        assert!((fps - 29.97).abs() < 0.001);
+        // F がなければ 30fps、不正な F は無視
+        assert_eq!(parse_y4m_header("YUV4MPEG2 W2 H2").unwrap().3, 30.0);
+        assert_eq!(parse_y4m_header("YUV4MPEG2 W2 H2 F0:0").unwrap().3, 30.0);
+    }
+
+    #[test]
+    fn y4m_header_errors() {
+        let cases = [
+            ("P5 W2 H2", "Not a YUV4MPEG2 file"),
+            ("YUV4MPEG2 W2 H2 C444", "Unsupported Y4M colour space C444"),
+            ("YUV4MPEG2 W2 H2 C422", "Unsupported Y4M colour space C422"),
+            ("YUV4MPEG2 W2", "Y4M header has no frame size"),
+            ("YUV4MPEG2 F30:1", "Y4M header has no frame size"),
+            ("YUV4MPEG2 Wabc H2", "Invalid width 'abc'"),
+            ("YUV4MPEG2 W2 H2 F30", "Invalid frame rate '30'"),
+        ];
+        for (line, expected) in cases {
+            assert_eq!(parse_y4m_header(line).err().as_deref(), Some(expected), "{:?}", line);
+        }
+    }
+
+    #[test]
+    fn size_and_fps_in_file_name() {
+        let cases = [
+            ("capture_1920x1080_60fps.yuyv", (Some((1920, 1080)), Some(60.0))),
+            ("Switch-1280X720-59.94fps.nv12", (Some((1280, 720)), Some(59.94))),
+            ("dump 720x480.yuv", (Some((720, 480)), None)),
+            ("capture.yuyv", (None, None)),
+            ("boxed_0fps_axb.yuyv", (None, None)),
+        ];
+        for (name, expected) in cases {
+            assert_eq!(size_and_fps_from_name(Path::new(name)), expected, "{}", name);
+        }
+    }
+
+    #[test]
+    fn raw_format_from_extension_or_setting() {
+        assert_eq!(raw_format_of(Path::new("a.yuy2"), Some("NV12")), Ok(PixelFormat::Yuy2));
+        assert_eq!(raw_format_of(Path::new("a.NV12"), None), Ok(PixelFormat::Nv12));
+        assert_eq!(raw_format_of(Path::new("a.yuv"), Some("NV12")), Ok(PixelFormat::Nv12));
+        assert_eq!(raw_format_of(Path::new("a.yuv"), Some("I420")).err().as_deref(), Some("Raw I420 files are not supported (use YUYV or NV12)"));
+        assert_eq!(raw_format_of(Path::new("a.yuv"), None).err().as_deref(), Some("Unknown raw format for 'a.yuv'"));
+    }
+
+    #[test]
+    fn raw_dump_ignores_trailing_partial_frame() {
+        // 4x2 YUYV (16 バイト) が2枚と、途中で切れた3枚目
+        let mut data = vec![1u8; 16];
+        data.extend_from_slice(&[2; 16]);
+        data.extend_from_slice(&[3; 5]);
+        let file = TempFile::new("4x2_120fps.yuyv", &data);
+        let info = probe(&file.0, None, None, None).unwrap();
+        assert_eq!((info.width, info.height, info.format, info.fps, info.data_start), (4, 2, PixelFormat::Yuy2, 120.0, 0));
+        let mut reader = open(&file.0, &info);
+        let mut buf = [0u8; 16];
+        assert!(read_frame(&mut reader, &info, &mut buf).unwrap() && buf == [1; 16]);
+        assert!(read_frame(&mut reader, &info, &mut buf).unwrap() && buf == [2; 16]);
+        assert!(!read_frame(&mut reader, &info, &mut buf).unwrap());
+    }
+
+    #[test]
+    fn raw_dump_size_from_settings() {
+        let file = TempFile::new("no_size.nv12", &[0; 12]);
+        assert!(probe(&file.0, None, None, None).is_err_and(|e| e.contains("Cannot determine frame size")));
+        let info = probe(&file.0, Some((4, 2)), None, Some(50)).unwrap();
+        assert_eq!((info.width, info.height, info.format, info.fps), (4, 2, PixelFormat::Nv12, 50.0));
+        // 1フレームに満たないファイル
+        assert!(probe(&file.0, Some((8, 8)), None, None).is_err_and(|e| e.contains("smaller than one 8x8 NV12 frame")));
+    }
+
+    #[test]
+    fn y4m_frames_and_end_of_file() {
+        let file = TempFile::new("frames.y4m", &y4m("YUV4MPEG2 W2 H2 F25:1", &[10, 20]));
+        let info = probe(&file.0, None, None, None).unwrap();
+        assert_eq!((info.width, info.height, info.fps, info.data_start), (2, 2, 25.0, 22));
+        let mut reader = open(&file.0, &info);
+        let mut buf = [0u8; 6];
+        assert!(read_frame(&mut reader, &info, &mut buf).unwrap() && buf == [10; 6]);
+        assert!(read_frame(&mut reader, &info, &mut buf).unwrap() && buf == [20; 6]);
+        assert!(!read_frame(&mut reader, &info, &mut buf).unwrap());
+    }
+
+    #[test]
+    fn y4m_missing_frame_marker() {
+        let mut data = y4m("YUV4MPEG2 W2 H2", &[10]);
+        data.extend_from_slice(b"FRAMX\n");
+        data.extend_from_slice(&[20; 6]);
+        let file = TempFile::new("broken.y4m", &data);
+        let info = probe(&file.0, None, None, None).unwrap();
+        let mut reader = open(&file.0, &info);
+        let mut buf = [0u8; 6];
+        assert!(read_frame(&mut reader, &info, &mut buf).unwrap());
+        let error = read_frame(&mut reader, &info, &mut buf).unwrap_err();
+        assert_eq!((error.kind(), error.to_string()), (std::io::ErrorKind::InvalidData, "missing FRAME marker".to_string()));
+    }
+
+    #[test]
+    fn y4m_playback_loops_to_first_frame() {
+        // 終端まで再生したらヘッダの後ろ (data_start) に戻り、ヘッダを読み直さずに1枚目から繰り返す
+        let file = TempFile::new("loop.y4m", &y4m("YUV4MPEG2 W2 H2 F1000:1 C420", &[10, 20, 30]));
+        let mut source = FileSource::new(file.0.to_str().unwrap(), None, None, None).unwrap();
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let sink_received = received.clone();
+        source.start(Box::new(move |frame: RawFrame<'_>| {
+            assert_eq!((frame.width, frame.height, frame.format), (2, 2, Some(PixelFormat::I420)));
+            sink_received.lock().unwrap().push(frame.data[0]);
+        })).unwrap();
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while received.lock().unwrap().len() < 7 {
+            assert!(Instant::now() < deadline, "playback did not loop");
+            std::thread::sleep(Duration::from_millis(5));
+        }
+        source.stop();
+        assert_eq!(received.lock().unwrap()[..7], [10, 20, 30, 10, 20, 30, 10]);
+    }
+}
@@ -0,0 +1,262 @@
+use egui::Color32;
+use crate::video::VideoFrame;
+
+// 黒帯 (レターボックス・ピラーボックス) の検出
+// 映像の各辺から内側へ1行 (1列) ずつ調べ、ほぼ真っ黒な行が続く幅を黒帯とみなす
+// 1フレームだけで判断すると暗い場面で帯が広がって見えるため、LetterboxDetector で
+// 同じ結果がしばらく続いた時だけクロップを変える
+
+// 黒とみなす輝度 (ノイズの多いアナログ入力でも黒帯を拾えるよう少し余裕を持たせる)
+const BLACK_LEVEL: u8 = 32;
+// 1行のうち黒以外の画素がこの割合 (1/n) 以下なら黒い行とみなす (テロップのちらつき・ノイズ対策)
+const BRIGHT_TOLERANCE: usize = 50;
+// 行・列を調べる時の画素の間引き
+const SAMPLE_STEP: usize = 4;
+// 黒帯とみなす最大の幅 (各辺で幅・高さの 1/n まで、それより暗い範囲が広ければ判定しない)
+const MAX_BAR_DIVISOR: usize = 3;
+// 検出結果の揺れとみなす差 (ピクセル)
+const TOLERANCE: u32 = 4;
+// 黒帯が広がった (映像を多く切る) 場合に適用するまでの連続検出回数
+const GROW_COUNT: u32 = 8;
+// 黒帯が狭まった (映像が切れている) 場合はすぐに戻す
+const SHRINK_COUNT: u32 = 2;
+// 切り抜いた後に最低限残す大きさ (ピクセル)
+const MIN_CONTENT_SIZE: u32 = 16;
+
+// 各辺の黒帯の幅 (ピクセル)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct BarInsets {
+    pub left: u32,
+    pub top: u32,
+    pub right: u32,
+    pub bottom: u32,
+}
+
+impl BarInsets {
+    fn is_close(&self, other: &BarInsets) -> bool {
+        self.left.abs_diff(other.left) <= TOLERANCE
+            && self.top.abs_diff(other.top) <= TOLERANCE
+            && self.right.abs_diff(other.right) <= TOLERANCE
+            && self.bottom.abs_diff(other.bottom) <= TOLERANCE
+    }
+
+    // どこかの辺で self より other の方が狭い (self のままだと映像を切ってしまう)
+    fn exceeds(&self, other: &BarInsets) -> bool {
+        self.left > other.left + TOLERANCE
+            || self.top > other.top + TOLERANCE
+            || self.right > other.right + TOLERANCE
+            || self.bottom > other.bottom + TOLERANCE
+    }
+
+    // 手動クロップの範囲 (x, y, 幅, 高さ) から黒帯をさらに除いた範囲
+    // 重なりが小さすぎる場合は手動クロップの範囲のまま
+    pub fn crop_rect(&self, (x, y, w, h): (u32, u32, u32, u32), width: u32, height: u32) -> (u32, u32, u32, u32) {
+        let left = x.max(self.left);
+        let top = y.max(self.top);
+        let right = (x + w).min(width.saturating_sub(self.right));
+        let bottom = (y + h).min(height.saturating_sub(self.bottom));
+        if right < left + MIN_CONTENT_SIZE || bottom < top + MIN_CONTENT_SIZE {
+            return (x, y, w, h);
+        }
+        (left, top, right - left, bottom - top)
+    }
+}
+
+fn luma(p: Color32) -> u8 {
+    ((p.r() as u32 * 77 + p.g() as u32 * 150 + p.b() as u32 * 29) >> 8) as u8
+}
+
+// 画素の並びがほぼ黒か
+fn is_black_line(pixels: impl Iterator<Item = Color32>) -> bool {
+    let (mut samples, mut bright) = (0, 0);
+    for p in pixels.step_by(SAMPLE_STEP) {
+        samples += 1;
+        if luma(p) > BLACK_LEVEL {
+            bright += 1;
+        }
+    }
+    bright * BRIGHT_TOLERANCE <= samples
+}
+
+// lines 本の行 (列) を外側から順に調べ、黒が続く本数 (上限を超えたら None)
+fn count_black(lines: impl Iterator<Item = usize>, limit: usize, is_black: impl Fn(usize) -> bool) -> Option<u32> {
+    let mut count = 0;
+    for i in lines {
+        if !is_black(i) {
+            return Some(count);
+        }
+        count += 1;
+        if count as usize > limit {
+            return None;
+        }
+    }
+    None
+}
+
+// フレームの黒帯を検出する
+// 映像全体が暗い (どこかの辺で黒が上限より広い) 場合は判定できないので None
+pub fn detect_bars(frame: &VideoFrame) -> Option<BarInsets> {
+    let (width, height) = (frame.width, frame.height);
+    let pixels = &frame.image.pixels;
+    if width == 0 || height == 0 || pixels.len() != width * height {
+        return None;
+    }
+    let row = |y: usize| is_black_line(pixels[y * width..][..width].iter().copied());
+    // 左右の帯は上下の帯を除いた範囲で調べる (レターボックスとピラーボックスが同時にある場合)
+    let top = count_black(0..height, height / MAX_BAR_DIVISOR, row)?;
+    let bottom = count_black((0..height).rev(), height / MAX_BAR_DIVISOR, row)?;
+    let rows = top as usize..height - bottom as usize;
+    let column = |x: usize| is_black_line(rows.clone().map(|y| pixels[y * width + x]));
+    let left = count_black(0..width, width / MAX_BAR_DIVISOR, column)?;
+    let right = count_black((0..width).rev(), width / MAX_BAR_DIVISOR, column)?;
+    Some(BarInsets { left, top, right, bottom })
+}
+
+// 検出結果にヒステリシスをかけて、クロップに使う黒帯を決める
+pub struct LetterboxDetector {
+    applied: BarInsets,
+    candidate: Option<(BarInsets, u32)>,
+    size: (usize, usize),
+    last_generation: Option<u64>,
+}
+
+impl Default for LetterboxDetector {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl LetterboxDetector {
+    pub fn new() -> Self {
+        Self { applied: BarInsets::default(), candidate: None, size: (0, 0), last_generation: None }
+    }
+
+    // 現在クロップに使っている黒帯
+    pub fn bars(&self) -> BarInsets {
+        self.applied
+    }
+
+    pub fn reset(&mut self) {
+        *self = Self::new();
+    }
+
+    // 新しいフレームを調べる (同じフレームは1回だけ)
+    // 適用する黒帯が変わったら true
+    pub fn update(&mut self, frame: &VideoFrame) -> bool {
+        if self.last_generation.replace(frame.generation) == Some(frame.generation) {
+            return false;
+        }
+        // 解像度が変わったら前の結果は使えない
+        if self.size != (frame.width, frame.height) {
+            let changed = self.applied != BarInsets::default();
+            self.reset();
+            self.size = (frame.width, frame.height);
+            self.last_generation = Some(frame.generation);
+            return changed | self.feed(detect_bars(frame));
+        }
+        self.feed(detect_bars(frame))
+    }
+
+    fn feed(&mut self, detected: Option<BarInsets>) -> bool {
+        // 暗い場面では判定しない (今の帯を維持)
+        let Some(detected) = detected else {
+            return false;
+        };
+        if detected.is_close(&self.applied) {
+            self.candidate = None;
+            return false;
+        }
+        let count = match self.candidate {
+            Some((candidate, count)) if candidate.is_close(&detected) => count + 1,
+            _ => 1,
+        };
+        self.candidate = Some((detected, count));
+        let required = if self.applied.exceeds(&detected) { SHRINK_COUNT } else { GROW_COUNT };
+        if count < required {
+            return false;
+        }
+        println!("Debug: Letterbox bars changed {:?} -> {:?}", self.applied, detected);
+        self.applied = detected;
+        self.candidate = None;
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    const TEST_WIDTH: usize = 1920;
+    const TEST_HEIGHT: usize = 1080;
+
+    // bars の部分を黒 (ノイズ入り)、残りを模様にしたフレーム
+    fn synthetic_frame(bars: BarInsets, generation: u64) -> VideoFrame {
+        let content_x = bars.left as usize..TEST_WIDTH - bars.right as usize;
+        let content_y = bars.top as usize..TEST_HEIGHT - bars.bottom as usize;
+        let pixels = (0..TEST_WIDTH * TEST_HEIGHT)
+            .map(|i| {
+                let (x, y) = (i % TEST_WIDTH, i / TEST_WIDTH);
+                if content_x.contains(&x) && content_y.contains(&y) {
+                    Color32::from_rgb((x % 200) as u8 + 40, (y % 150) as u8 + 60, 128)
+                } else {
+                    // 黒帯にも入力のノイズ程度の揺れを入れる
+                    Color32::from_gray(((x * 7 + y * 13) % 20) as u8)
+                }
+            })
+            .collect();
+        VideoFrame {
+            width: TEST_WIDTH,
+            height: TEST_HEIGHT,
+            image: Arc::new(egui::ColorImage { size: [TEST_WIDTH, TEST_HEIGHT], pixels }),
+            ungraded: None,
+            generation,
+        }
+    }
+
+    #[test]
+    fn detects_bars() {
+        for bars in [
+            BarInsets { left: 240, top: 0, right: 240, bottom: 0 },
+            BarInsets { left: 0, top: 138, right: 0, bottom: 138 },
+            BarInsets { left: 160, top: 60, right: 160, bottom: 60 },
+            BarInsets::default(),
+        ] {
+            assert_eq!(detect_bars(&synthetic_frame(bars, 0)), Some(bars));
+        }
+    }
+
+    #[test]
+    fn ignores_dark_frame() {
+        let frame = synthetic_frame(BarInsets { left: 0, top: 540, right: 0, bottom: 540 }, 0);
+        assert_eq!(detect_bars(&frame), None);
+    }
+
+    #[test]
+    fn hysteresis() {
+        let pillarbox = BarInsets { left: 240, top: 0, right: 240, bottom: 0 };
+        let mut detector = LetterboxDetector::new();
+        let mut generation = 0;
+        let mut feed = |detector: &mut LetterboxDetector, bars: BarInsets| {
+            generation += 1;
+            detector.update(&synthetic_frame(bars, generation))
+        };
+        // 1回だけの検出ではクロップしない
+        feed(&mut detector, pillarbox);
+        assert_eq!(detector.bars(), BarInsets::default(), "bars applied after a single frame");
+        // 続けて検出されたら適用する
+        for _ in 1..GROW_COUNT {
+            feed(&mut detector, pillarbox);
+        }
+        assert_eq!(detector.bars(), pillarbox);
+        // 同じフレームを何度渡しても数えない
+        let frame = synthetic_frame(BarInsets::default(), 1000);
+        for _ in 0..GROW_COUNT {
+            detector.update(&frame);
+        }
+        assert_eq!(detector.bars(), pillarbox, "repeated frame was counted more than once");
+        // 映像が広がったらすぐに戻す
+        feed(&mut detector, BarInsets::default());
+        assert_eq!(detector.bars(), BarInsets::default());
+    }
+}
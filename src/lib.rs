@@ -0,0 +1,23 @@
+// 映像・音声の取得と処理 (ウィンドウは main.rs)
+// ベンチマーク (benches/) と結合テスト (tests/) からも使えるようにライブラリにしている
+
+pub mod settings;
+pub mod convert;
+pub mod source;
+pub mod test_pattern;
+pub mod file_source;
+pub mod deinterlace;
+pub mod picture;
+pub mod lut;
+pub mod video;
+pub mod letterbox;
+pub mod orientation;
+pub mod scaling;
+pub mod retro;
+pub mod zoom;
+pub mod watchdog;
+pub mod audio;
+pub mod device_worker;
+pub mod screenshot;
+pub mod recording;
+pub mod ui;
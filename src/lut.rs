@@ -0,0 +1,292 @@
+use egui::Color32;
+use rayon::prelude::*;
+use std::path::Path;
+use std::sync::Arc;
+use crate::settings::LutInterpolation;
+
+// 3D LUT (.cube) による色補正
+// 画質調整の後、キャプチャスレッドで RGBA に対して行う
+// 格子点の値は固定小数点に変換しておき、画素ごとの補間は整数演算で行う
+
+// LUT_3D_SIZE として受け付ける範囲
+const MIN_LUT_SIZE: usize = 2;
+const MAX_LUT_SIZE: usize = 256;
+// 補間の重みの桁 (1.0 = 1 << FRAC_BITS)
+const FRAC_BITS: u32 = 8;
+const ONE: i32 = 1 << FRAC_BITS;
+// 並列処理の単位 (行数)
+const ROWS_PER_TASK: usize = 16;
+
+// 読み込んだ 3D LUT (赤が最も速く変わる順の格子点)
+#[derive(Debug, Clone)]
+pub struct Lut3d {
+    pub size: usize,
+    pub table: Vec<[f32; 3]>,
+    pub domain_min: [f32; 3],
+    pub domain_max: [f32; 3],
+}
+
+fn parse_floats<const N: usize>(values: &[&str], line_no: usize) -> Result<[f32; N], String> {
+    if values.len() != N {
+        return Err(format!("line {}: expected {} values, found {}", line_no, N, values.len()));
+    }
+    let mut out = [0.0; N];
+    for (v, s) in out.iter_mut().zip(values) {
+        *v = s.parse::<f32>()
+            .ok()
+            .filter(|v| v.is_finite())
+            .ok_or_else(|| format!("line {}: invalid number '{}'", line_no, s))?;
+    }
+    Ok(out)
+}
+
+// .cube 形式のテキストを読み込む
+pub fn parse_cube(text: &str) -> Result<Lut3d, String> {
+    let mut size = None;
+    let mut domain_min = [0.0; 3];
+    let mut domain_max = [1.0; 3];
+    let mut table = Vec::new();
+    for (i, line) in text.lines().enumerate() {
+        let line_no = i + 1;
+        let line = line.split('#').next().unwrap_or("").trim();
+        if line.is_empty() {
+            continue;
+        }
+        let mut words = line.split_whitespace();
+        let keyword = words.next().unwrap_or("");
+        let values: Vec<&str> = words.collect();
+        match keyword {
+            "TITLE" => {}
+            "LUT_3D_SIZE" => {
+                let [n] = parse_floats::<1>(&values, line_no)?;
+                if n.fract() != 0.0 || !(MIN_LUT_SIZE as f32..=MAX_LUT_SIZE as f32).contains(&n) {
+                    return Err(format!("line {}: LUT_3D_SIZE {} is out of range ({}-{})", line_no, values[0], MIN_LUT_SIZE, MAX_LUT_SIZE));
+                }
+                size = Some(n as usize);
+            }
+            "LUT_1D_SIZE" => return Err(format!("line {}: 1D LUTs are not supported", line_no)),
+            "DOMAIN_MIN" => domain_min = parse_floats::<3>(&values, line_no)?,
+            "DOMAIN_MAX" => domain_max = parse_floats::<3>(&values, line_no)?,
+            // DaVinci Resolve 形式の入力範囲 (全チャンネル共通)
+            "LUT_3D_INPUT_RANGE" => {
+                let [min, max] = parse_floats::<2>(&values, line_no)?;
+                domain_min = [min; 3];
+                domain_max = [max; 3];
+            }
+            _ if keyword.starts_with(|c: char| c.is_ascii_alphabetic()) => {
+                println!("Warning: Ignoring unknown .cube keyword '{}' on line {}", keyword, line_no);
+            }
+            _ => {
+                let mut all = vec![keyword];
+                all.extend(values);
+                table.push(parse_floats::<3>(&all, line_no)?);
+            }
+        }
+    }
+    let size = size.ok_or_else(|| "missing LUT_3D_SIZE".to_string())?;
+    let expected = size * size * size;
+    if table.len() != expected {
+        return Err(format!("expected {} entries for LUT_3D_SIZE {}, found {}", expected, size, table.len()));
+    }
+    if (0..3).any(|c| domain_max[c] <= domain_min[c]) {
+        return Err("DOMAIN_MAX must be greater than DOMAIN_MIN".to_string());
+    }
+    Ok(Lut3d { size, table, domain_min, domain_max })
+}
+
+pub fn load_cube(path: &Path) -> Result<Lut3d, String> {
+    let text = std::fs::read_to_string(path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    parse_cube(&text).map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+// キャプチャスレッドで使う LUT の適用処理 (格子点は共有して強さ・分割表示だけ変えられる)
+#[derive(Clone)]
+pub struct LutProcessor {
+    size: usize,
+    // 格子点の値 (0 ~ 255 を 1 << FRAC_BITS 倍したもの、範囲外の値もそのまま持つ)
+    lattice: Arc<Vec<[i32; 3]>>,
+    // 入力値 (チャンネルごと 0 ~ 255) -> (格子の位置, 次の格子点への重み)
+    index: Arc<[[(u16, u16); 256]; 3]>,
+    interpolation: LutInterpolation,
+    // 元の色との混合の割合 (0 ~ ONE)
+    strength: i32,
+}
+
+impl LutProcessor {
+    pub fn new(lut: &Lut3d, interpolation: LutInterpolation) -> Self {
+        let fixed = |v: f32| (v * 255.0 * ONE as f32).round() as i32;
+        let lattice = lut.table.iter().map(|c| c.map(fixed)).collect();
+        let last = (lut.size - 1) as f32;
+        let mut index = [[(0u16, 0u16); 256]; 3];
+        for (c, table) in index.iter_mut().enumerate() {
+            let (min, max) = (lut.domain_min[c], lut.domain_max[c]);
+            for (v, entry) in table.iter_mut().enumerate() {
+                let position = ((v as f32 / 255.0 - min) / (max - min)).clamp(0.0, 1.0) * last;
+                // 最後の格子点は1つ手前の区間の重み 1.0 として扱う
+                let cell = (position.floor() as usize).min(lut.size - 2);
+                let weight = ((position - cell as f32) * ONE as f32).round() as u16;
+                *entry = (cell as u16, weight);
+            }
+        }
+        Self { size: lut.size, lattice: Arc::new(lattice), index: Arc::new(index), interpolation, strength: ONE }
+    }
+
+    // strength: 0 ~ 100 (%)
+    pub fn with_strength(mut self, strength: f32) -> Self {
+        self.strength = ((strength / 100.0).clamp(0.0, 1.0) * ONE as f32).round() as i32;
+        self
+    }
+
+    #[inline(always)]
+    fn at(&self, r: usize, g: usize, b: usize) -> [i32; 3] {
+        self.lattice[(b * self.size + g) * self.size + r]
+    }
+
+    #[inline(always)]
+    fn lookup(&self, p: Color32) -> [i32; 3] {
+        let (r, fr) = self.index[0][p.r() as usize];
+        let (g, fg) = self.index[1][p.g() as usize];
+        let (b, fb) = self.index[2][p.b() as usize];
+        let (r, g, b) = (r as usize, g as usize, b as usize);
+        let (fr, fg, fb) = (fr as i32, fg as i32, fb as i32);
+        let c000 = self.at(r, g, b);
+        let c111 = self.at(r + 1, g + 1, b + 1);
+        let mut out = [0; 3];
+        match self.interpolation {
+            LutInterpolation::Tetrahedral => {
+                // 重みの大きい順に辿る四面体の頂点 (c000 -> c_a -> c_b -> c111)
+                let (f1, f2, f3, ca, cb) = if fr > fg {
+                    if fg > fb {
+                        (fr, fg, fb, self.at(r + 1, g, b), self.at(r + 1, g + 1, b))
+                    } else if fr > fb {
+                        (fr, fb, fg, self.at(r + 1, g, b), self.at(r + 1, g, b + 1))
+                    } else {
+                        (fb, fr, fg, self.at(r, g, b + 1), self.at(r + 1, g, b + 1))
+                    }
+                } else if fb > fg {
+                    (fb, fg, fr, self.at(r, g, b + 1), self.at(r, g + 1, b + 1))
+                } else if fb > fr {
+                    (fg, fb, fr, self.at(r, g + 1, b), self.at(r, g + 1, b + 1))
+                } else {
+                    (fg, fr, fb, self.at(r, g + 1, b), self.at(r + 1, g + 1, b))
+                };
+                for (c, v) in out.iter_mut().enumerate() {
+                    *v = (c000[c] * (ONE - f1) + ca[c] * (f1 - f2) + cb[c] * (f2 - f3) + c111[c] * f3 + ONE / 2) >> FRAC_BITS;
+                }
+            }
+            LutInterpolation::Trilinear => {
+                let lerp = |a: i32, b: i32, t: i32| a + (((b - a) * t + ONE / 2) >> FRAC_BITS);
+                let (c100, c010, c110) = (self.at(r + 1, g, b), self.at(r, g + 1, b), self.at(r + 1, g + 1, b));
+                let (c001, c101, c011) = (self.at(r, g, b + 1), self.at(r + 1, g, b + 1), self.at(r, g + 1, b + 1));
+                for (c, v) in out.iter_mut().enumerate() {
+                    let front = lerp(lerp(c000[c], c100[c], fr), lerp(c010[c], c110[c], fr), fg);
+                    let back = lerp(lerp(c001[c], c101[c], fr), lerp(c011[c], c111[c], fr), fg);
+                    *v = lerp(front, back, fb);
+                }
+            }
+        }
+        out
+    }
+
+    #[inline(always)]
+    fn apply_pixel(&self, p: Color32) -> Color32 {
+        let lut = self.lookup(p);
+        let channel = |c: usize, original: u8| {
+            let original = (original as i32) << FRAC_BITS;
+            let mixed = original + (((lut[c] - original) * self.strength) >> FRAC_BITS);
+            ((mixed + ONE / 2) >> FRAC_BITS).clamp(0, 255) as u8
+        };
+        Color32::from_rgb(channel(0, p.r()), channel(1, p.g()), channel(2, p.b()))
+    }
+
+    // フレーム全体に適用 (補正前後の比較は表示側で描き分ける)
+    pub fn apply(&self, pixels: &mut [Color32], width: usize) {
+        if width == 0 || self.strength == 0 {
+            return;
+        }
+        pixels.par_chunks_mut(width * ROWS_PER_TASK).for_each(|rows| {
+            for row in rows.chunks_exact_mut(width) {
+                for p in row {
+                    *p = self.apply_pixel(*p);
+                }
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // f(r, g, b) を格子点に書き出した .cube テキスト
+    fn cube_text(size: usize, f: impl Fn(f32, f32, f32) -> [f32; 3]) -> String {
+        let mut text = format!("# test\nTITLE \"test\"\nLUT_3D_SIZE {}\n", size);
+        let last = (size - 1) as f32;
+        for b in 0..size {
+            for g in 0..size {
+                for r in 0..size {
+                    let [x, y, z] = f(r as f32 / last, g as f32 / last, b as f32 / last);
+                    text.push_str(&format!("{:.6} {:.6} {:.6}\n", x, y, z));
+                }
+            }
+        }
+        text
+    }
+
+    // 17/33/65 点の LUT を生成して読み込み、両方の補間で期待どおりの色になるか確認する
+    #[test]
+    fn applies_generated_cubes() {
+        let samples = [Color32::from_rgb(0, 0, 0), Color32::from_rgb(255, 255, 255), Color32::from_rgb(12, 200, 77), Color32::from_rgb(250, 3, 129)];
+        // 恒等変換は元の色のまま、チャンネル入れ替え + 反転は (255 - b, r, g) になる
+        type Case = (&'static str, fn(f32, f32, f32) -> [f32; 3], fn(Color32) -> Color32);
+        let cases: [Case; 2] = [
+            ("identity", |r, g, b| [r, g, b], |p| p),
+            ("swap and invert", |r, g, b| [1.0 - b, r, g], |p| Color32::from_rgb(255 - p.b(), p.r(), p.g())),
+        ];
+        for size in [17, 33, 65] {
+            for interpolation in [LutInterpolation::Tetrahedral, LutInterpolation::Trilinear] {
+                for (name, f, expected) in cases {
+                    let lut = parse_cube(&cube_text(size, f)).unwrap();
+                    let processor = LutProcessor::new(&lut, interpolation);
+                    let mut pixels = samples;
+                    processor.apply(&mut pixels, samples.len());
+                    for (actual, input) in pixels.iter().zip(samples) {
+                        let expected = expected(input);
+                        // 線形な変換なので補間の誤差は丸め分だけ
+                        let close = [(actual.r(), expected.r()), (actual.g(), expected.g()), (actual.b(), expected.b())]
+                            .iter()
+                            .all(|(a, e)| a.abs_diff(*e) <= 1);
+                        assert!(close, "{}-point {:?} {}: {:?} became {:?}, expected {:?}", size, interpolation, name, input, actual, expected);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn parse_errors() {
+        let cases = [
+            ("0 0 0\n", "missing LUT_3D_SIZE"),
+            ("LUT_3D_SIZE 1\n", "line 1: LUT_3D_SIZE 1 is out of range (2-256)"),
+            ("LUT_1D_SIZE 1024\n", "line 1: 1D LUTs are not supported"),
+            ("LUT_3D_SIZE 2\n0 0 0\n1 0\n", "line 3: expected 3 values, found 2"),
+            ("LUT_3D_SIZE 2\n0 0 abc\n", "line 2: invalid number 'abc'"),
+            ("LUT_3D_SIZE 2\n0 0 0\n", "expected 8 entries for LUT_3D_SIZE 2, found 1"),
+        ];
+        for (text, expected) in cases {
+            assert_eq!(parse_cube(text).err().as_deref(), Some(expected), "{:?}", text);
+        }
+        let domain = "LUT_3D_SIZE 2\nDOMAIN_MIN 0 0 0\nDOMAIN_MAX 1 0 1\n".to_string() + &"0 0 0\n".repeat(8);
+        assert!(parse_cube(&domain).err().is_some_and(|e| e.contains("DOMAIN_MAX")));
+    }
+
+    #[test]
+    fn strength() {
+        // 全体を白にする LUT を半分の強さでかける
+        let lut = parse_cube(&cube_text(2, |_, _, _| [1.0, 1.0, 1.0])).unwrap();
+        let processor = LutProcessor::new(&lut, LutInterpolation::Tetrahedral).with_strength(50.0);
+        let mut pixels = [Color32::BLACK; 4];
+        processor.apply(&mut pixels, 4);
+        assert_eq!(pixels, [Color32::from_gray(128); 4]);
+    }
+}
@@ -1,4 +1,4 @@
-#![windows_subsystem = "windows"]
+#![cfg_attr(windows, windows_subsystem = "windows")]
 
 use eframe::egui;
 use chrono::Local;
@@ -7,21 +7,25 @@ use image::GenericImageView;
 use std::time::Instant;
 use std::panic::AssertUnwindSafe;
 
-mod settings;
-mod video;
-mod audio;
-mod screenshot;
-mod ui;
+use capturecard_viewer::{audio, device_worker, file_source, letterbox, orientation, recording, retro, scaling, screenshot, settings, ui, video, zoom};
 
-use settings::AppSettings;
+use settings::{AppSettings, AudioSettings, CropEdge, CropSettings, DisplayAspect, LutSettings, PictureAdjustments, PixelAspect, RetroSettings, ScalingMode, VideoSettings};
 use video::VideoCapture;
+use letterbox::LetterboxDetector;
+use orientation::Orientation;
+use retro::RetroWorker;
+use zoom::ZoomView;
 use audio::AudioCapture;
+use device_worker::{DeviceState, DeviceStatus, DeviceWorker};
 use screenshot::ScreenshotManager;
+use recording::{RecordOptions, Recorder};
 
 pub struct CaptureCardViewer {
     settings: Arc<Mutex<AppSettings>>,
-    video_capture: Arc<Mutex<VideoCapture>>,
-    audio_capture: Arc<Mutex<AudioCapture>>,
+    // デバイスの接続・切断はワーカースレッドで行う
+    devices: DeviceWorker,
+    // ワーカーから取得した最新の状態 (表示用)
+    device_status: DeviceStatus,
     screenshot_manager: Arc<Mutex<ScreenshotManager>>,
     
     // UI状態管理
@@ -33,27 +37,59 @@ pub struct CaptureCardViewer {
     maintain_aspect_ratio: bool,
     volume: f32,
     last_volume_sent: f32,
-    last_settings_applied: Instant,
+    // 最後にワーカーへ送った画質調整
+    last_picture_sent: Option<PictureAdjustments>,
+    // 最後にワーカーへ送った LUT の設定
+    last_lut_sent: Option<LutSettings>,
+    // LUT の補正前後を比較している (左半分に補正前の映像を重ねて境界線を描く)
+    lut_split: bool,
+    // 選択中のデバイスのクロップ設定 (毎フレーム設定から取得)
+    crop: CropSettings,
+    // クロップ調整モード (映像全体を表示して範囲の辺をドラッグで動かす)
+    crop_mode: bool,
+    crop_drag: Option<CropEdge>,
+    // 拡大表示 (Ctrl+ホイールで拡大、ドラッグで移動)
+    zoom: ZoomView,
+    // 黒帯の自動検出 (有効な時だけ最新フレームを調べる)
+    letterbox: LetterboxDetector,
+    last_letterbox_check: Instant,
+    // 表示の回転・反転と拡大方式 (毎フレーム設定から取得)
+    orientation: Orientation,
+    scaling: ScalingMode,
+    pixel_aspect: PixelAspect,
+    // 表示の縦横比の指定と、カスタムの比率 (幅, 高さ)
+    display_aspect: DisplayAspect,
+    custom_aspect: (u32, u32),
+    // シャープバイリニアでテクスチャを事前に拡大する倍率 (前回の描画時の表示サイズから決める)
+    prescale: usize,
+    // レトロフィルタとシャープバイリニアの事前拡大 (ワーカースレッドで処理、設定は選択中のデバイスのもの)
+    retro: RetroWorker,
+    retro_settings: RetroSettings,
+    // ワーカーに渡した最後のフレーム番号と設定・倍率 (設定が変われば同じフレームも渡し直す)
+    retro_submitted: Option<(u64, RetroSettings, usize)>,
+    // ワーカーに最後に送ったデバイス設定 (変更がなければ送り直さない)
+    applied_devices: Option<(VideoSettings, AudioSettings)>,
     
     // 映像表示関連
     video_texture: Option<egui::TextureHandle>,
+    // LUT の補正前後の比較用の補正前の映像 (比較していない・レトロフィルタ中は None)
+    ungraded_texture: Option<egui::TextureHandle>,
+    // テクスチャに反映済みのフレーム番号と拡大方式 (同じものは再アップロードしない)
+    video_texture_generation: u64,
+    texture_scaling: (ScalingMode, usize, bool),
+    // 入力映像の大きさ (事前拡大したテクスチャの大きさではなく元のフレームの大きさ)
+    video_size: [usize; 2],
     pending_hotkey: Option<String>,
     temp_hotkey: String, // ホットキーダイアログ用の一時保存
-    // 最後に適用した実行時パラメータ（差分ベースの再起動回避用）
-    last_video_device: Option<String>,
-    last_video_res: Option<(u32,u32)>,
-    last_video_format: Option<String>,
-    last_audio_device: Option<String>,
-    last_audio_rate: Option<u32>,
-    last_audio_channels: Option<u16>,
     last_fullscreen_toggle: Option<Instant>,
-    last_video_fps: Option<u32>,
+    // 入力解像度が変わった時刻 (オーバーレイ表示用)
+    last_resolution_change: Option<Instant>,
+    // 録画中の状態と、録画の開始・終了のメッセージ (オーバーレイ表示用)
+    recorder: Option<Recorder>,
+    recording_status: Option<(String, Instant)>,
 
-    audio_last_error: Option<String>,
-    
-    // 起動時遅延接続
-    startup_time: Option<Instant>,
-    delayed_connection_triggered: bool,
+    // 起動後最初のフレームで接続を開始したか
+    started: bool,
     
     // UI性能向上のためのデバイスリストキャッシュ
     cached_input_devices: Vec<String>,
@@ -67,15 +103,12 @@ pub struct CaptureCardViewer {
 impl Default for CaptureCardViewer {
     fn default() -> Self {
         let settings = Arc::new(Mutex::new(AppSettings::load()));
-        let video_capture = Arc::new(Mutex::new(VideoCapture::new()));
-        #[allow(clippy::arc_with_non_send_sync)] // 音声キャプチャは非同期処理で必要
-        let audio_capture = Arc::new(Mutex::new(AudioCapture::new()));
         let screenshot_manager = Arc::new(Mutex::new(ScreenshotManager::new()));
         
         let app = Self {
             settings,
-            video_capture,
-            audio_capture,
+            devices: DeviceWorker::spawn(),
+            device_status: DeviceStatus::default(),
             screenshot_manager,
             show_settings: false,
             show_context_menu: false,
@@ -85,23 +118,38 @@ impl Default for CaptureCardViewer {
             maintain_aspect_ratio: true,
             volume: 100.0,
             last_volume_sent: -1.0,
-            last_settings_applied: Instant::now(),
+            last_picture_sent: None,
+            last_lut_sent: None,
+            lut_split: false,
+            crop: CropSettings::default(),
+            crop_mode: false,
+            crop_drag: None,
+            zoom: ZoomView::default(),
+            letterbox: LetterboxDetector::new(),
+            last_letterbox_check: Instant::now(),
+            orientation: Orientation::default(),
+            scaling: ScalingMode::Auto,
+            pixel_aspect: PixelAspect::Square,
+            display_aspect: DisplayAspect::Auto,
+            custom_aspect: (16, 10),
+            prescale: 1,
+            retro: RetroWorker::spawn(),
+            retro_settings: RetroSettings::default(),
+            retro_submitted: None,
+            applied_devices: None,
             video_texture: None,
+            ungraded_texture: None,
+            video_texture_generation: 0,
+            texture_scaling: (ScalingMode::Auto, 1, false),
+            video_size: [0, 0],
             pending_hotkey: None,
             temp_hotkey: String::new(),
-            last_video_device: None,
-            last_video_res: None,
-            last_video_format: None,
-            last_audio_device: None,
-            last_audio_rate: None,
-            last_audio_channels: None,
             last_fullscreen_toggle: None,
-            last_video_fps: None,
+            last_resolution_change: None,
+            recorder: None,
+            recording_status: None,
 
-            audio_last_error: None,
-            // 起動時遅延接続
-            startup_time: Some(Instant::now()),
-            delayed_connection_triggered: false,
+            started: false,
             
             // UI性能向上のためのデバイスリストキャッシュ
             cached_input_devices: Vec::new(),
@@ -116,12 +164,14 @@ impl Default for CaptureCardViewer {
         {
             if let Ok(mut s) = app.settings.lock() {
                 if s.video.device_name.is_none() {
-                    let devices = VideoCapture::list_devices();
-                    if let Some((name,_)) = devices.first() { s.video.device_name = Some(name.clone()); }
+                    let devices = VideoCapture::list_devices(s.video.backend);
+                    if let Some(device) = devices.first() {
+                        s.video.device_name = Some(device.name.clone());
+                        s.video.device_id = device.id.clone();
+                    }
                 }
                 if s.audio.input_device_name.is_none() {
-                    let ac = AudioCapture::new();
-                    let list = ac.list_input_devices();
+                    let list = AudioCapture::list_input_devices();
                     println!("Debug: Available input devices: {:?}", list);
                     if let Some(name) = list.first() { 
                         s.audio.input_device_name = Some(name.clone()); 
@@ -135,68 +185,98 @@ impl Default for CaptureCardViewer {
                 }
                 s.save();            }
         }
-        // 注: デバイス接続は起動から2秒後に遅延実行される
+        // 注: デバイス接続は最初のフレームでワーカースレッドに依頼する
         app
     }
 }
 
 impl eframe::App for CaptureCardViewer {
     fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
-        // 遅延デバイス接続（起動から3秒後に実行し、画面投影問題を解決）
-        if !self.delayed_connection_triggered {
-            if let Some(startup_time) = self.startup_time {
-                if startup_time.elapsed().as_secs_f32() >= 2.0 {
-                    println!("Starting delayed device connection and auto-refresh (2 seconds after startup)");
-                    // 強制リフレッシュのため、last_*をクリアしてからapply_settings
-                    println!("Debug: Clearing last device states for forced refresh");
-                    self.last_video_device = None;
-                    self.last_audio_device = None;
-                    println!("Debug: Calling apply_settings(initial=true)");
-                    self.apply_settings(true);
-                    
-                    // 初期設定後にエラーハンドリング付きでウィンドウレベル設定を適用
-                    if let Err(e) = std::panic::catch_unwind(AssertUnwindSafe(|| {
-                        ctx.send_viewport_cmd(egui::ViewportCommand::WindowLevel(
-                            if self.always_on_top {
-                                egui::WindowLevel::AlwaysOnTop
-                            } else {
-                                egui::WindowLevel::Normal
-                            }
-                        ));
-                    })) {
-                        eprintln!("Warning: Failed to set window level: {:?}", e);
+        // デバイス接続を開始 (接続はワーカースレッドで行われるため、ウィンドウの表示は待たされない)
+        if !self.started {
+            self.started = true;
+            println!("Debug: Requesting initial device connection");
+            self.apply_settings(false);
+            
+            // 初期設定後にエラーハンドリング付きでウィンドウレベル設定を適用
+            if let Err(e) = std::panic::catch_unwind(AssertUnwindSafe(|| {
+                ctx.send_viewport_cmd(egui::ViewportCommand::WindowLevel(
+                    if self.always_on_top {
+                        egui::WindowLevel::AlwaysOnTop
+                    } else {
+                        egui::WindowLevel::Normal
                     }
-                    
-                    self.delayed_connection_triggered = true;
-                    println!("Debug: Delayed connection sequence completed");
-                }
+                ));
+            })) {
+                eprintln!("Warning: Failed to set window level: {:?}", e);
             }
         }
         
         // ビデオフレームを更新
         self.update_video_texture(ctx);
         
+        // デバイスの接続状態を取得
+        self.update_device_status();
+        
         // グローバルホットキーを処理
         self.handle_hotkeys();
-        
-        // 定期的に実行時設定が保存設定と一致することを確認（外部変更に対応）
-        if self.last_settings_applied.elapsed().as_secs_f32() > 2.0 {
-            if let Err(e) = std::panic::catch_unwind(AssertUnwindSafe(|| {
-                self.apply_settings(false);
-            })) {
-                eprintln!("Warning: Failed to apply settings: {:?}", e);
-                // タイマーをリセットして連続的なエラー出力を防止
-                self.last_settings_applied = Instant::now();
-            }
+
+        // 0 キーで拡大表示をリセット (文字入力中は除く)
+        if !ctx.wants_keyboard_input() && ctx.input(|i| i.key_pressed(egui::Key::Num0)) {
+            self.zoom.reset();
         }
 
+        // 録画の開始・停止キー (ウィンドウがアクティブな時のみ、文字入力中は除く)
+        let recording_hotkey = self.settings.lock().map(|s| s.recording.hotkey.clone()).unwrap_or_default();
+        if !ctx.wants_keyboard_input() && !self.show_hotkey_dialog {
+            if let Some((modifiers, key)) = parse_key_shortcut(&recording_hotkey) {
+                if ctx.input_mut(|i| i.consume_key(modifiers, key)) {
+                    self.toggle_recording();
+                }
+            }
+        }
+        // 書き込みエラーや大きさの上限で録画スレッドが止まった
+        if self.recorder.as_ref().is_some_and(|r| r.is_finished()) {
+            self.stop_recording();
+        }
+        
         // 音量が変更された場合、オーディオバックエンドに伝播
         if (self.volume - self.last_volume_sent).abs() > 0.5 {
-            if let Ok(mut audio) = self.audio_capture.lock() {
-                audio.set_volume(self.volume);
-            }
+            self.devices.set_volume(self.volume);
             self.last_volume_sent = self.volume;
         }
+
+        // 画質調整は設定画面のスライダーを動かしている間も即座に反映
+        let mut aspect_changed = false;
+        if let Ok(settings) = self.settings.lock() {
+            self.orientation = Orientation::from_settings(&settings.video);
+            self.scaling = settings.ui.scaling;
+            aspect_changed = (settings.ui.pixel_aspect, settings.ui.display_aspect, settings.ui.custom_aspect)
+                != (self.pixel_aspect, self.display_aspect, self.custom_aspect);
+            self.pixel_aspect = settings.ui.pixel_aspect;
+            self.display_aspect = settings.ui.display_aspect;
+            self.custom_aspect = settings.ui.custom_aspect;
+            if self.last_lut_sent.as_ref() != Some(&settings.video.lut) {
+                self.devices.set_lut(settings.video.lut.clone());
+                self.last_lut_sent = Some(settings.video.lut.clone());
+            }
+            self.lut_split = settings.video.lut.path.is_some() && settings.video.lut.split_view;
+        }
+        if let Ok(profile) = self.settings.lock().map(|s| s.video.profile()) {
+            if self.last_picture_sent != Some(profile.picture) {
+                self.devices.set_picture(profile.picture);
+                self.last_picture_sent = Some(profile.picture);
+            }
+            self.crop = profile.crop;
+            self.retro_settings = profile.retro;
+        }
+
+        // 黒帯の自動クロップ
+        self.update_letterbox();
+        if aspect_changed && self.video_texture.is_some() {
+            let (_, image_size) = self.display_region(self.video_size);
+            self.fit_window_to_image(ctx, image_size);
+        }
         
         // ウィンドウサイズと位置を監視して設定に保存
         let viewport = ctx.input(|i| i.viewport().clone());
@@ -240,8 +320,10 @@ impl eframe::App for CaptureCardViewer {
         if self.show_settings {
             let input_devices = self.get_cached_input_devices().clone();
             let output_devices = self.get_cached_output_devices().clone();
-            let applied = ui::show_settings_dialog(ctx, &mut self.show_settings, &self.settings, &mut self.show_hotkey_dialog, &input_devices, &output_devices);
-            if applied { self.apply_settings(false); }
+            let lut_error = self.device_status.lut_error.clone();
+            let applied = ui::show_settings_dialog(ctx, &mut self.show_settings, &self.settings, &mut self.show_hotkey_dialog, &input_devices, &output_devices, lut_error.as_deref());
+            // キャンセルで元に戻した設定も反映する (変更がなければワーカーには送らない)
+            if applied || !self.show_settings { self.apply_settings(false); }
         }
         
         // ホットキーキャプチャダイアログ
@@ -287,6 +369,38 @@ impl eframe::App for CaptureCardViewer {
             }
         }
 
+        // 入力解像度変更オーバーレイ (2秒表示)
+        if let (Some(t), Some(texture)) = (self.last_resolution_change, &self.video_texture) {
+            if t.elapsed().as_secs_f32() < 2.0 {
+                let [w, h] = texture.size();
+                egui::Area::new("resolution_overlay")
+                    .order(egui::Order::Foreground)
+                    .anchor(egui::Align2::LEFT_BOTTOM, egui::vec2(20.0, -20.0))
+                    .show(ctx, |ui| {
+                        egui::Frame::none().fill(egui::Color32::from_black_alpha(160)).rounding(5.0).show(ui, |ui| {
+                            ui.label(format!("入力解像度: {}x{}", w, h));
+                        });
+                    });
+            }
+        }
+
+        // 録画中の表示 (左上で REC が点滅) と開始・終了のメッセージ (3秒表示)
+        self.paint_recording_overlay(ctx);
+
+        // 信号断オーバーレイ (再接続中)
+        if let Some((attempts, next_attempt)) = self.device_status.reconnecting {
+            let retry_in = next_attempt.map(|t| t.saturating_duration_since(Instant::now())).unwrap_or_default();
+            egui::Area::new("no_signal_overlay")
+                .order(egui::Order::Foreground)
+                .anchor(egui::Align2::CENTER_TOP, egui::vec2(0.0, 20.0))
+                .show(ctx, |ui| {
+                    egui::Frame::none().fill(egui::Color32::from_black_alpha(160)).rounding(5.0).inner_margin(8.0).show(ui, |ui| {
+                        ui.label(egui::RichText::new("信号なし - 再接続中").color(egui::Color32::YELLOW));
+                        ui.label(format!("{}回目 / 次の再試行まで {:.0} 秒", attempts, retry_in.as_secs_f32().ceil()));
+                    });
+                });
+        }
+
         // 新しくキャプチャされたホットキーを即座に登録
         if let Some(hk) = self.pending_hotkey.take() {
             println!("Registering new hotkey: {}", hk);
@@ -300,7 +414,7 @@ impl eframe::App for CaptureCardViewer {
             }
         }
         // テストサウンドリクエストを処理
-        if crate::ui::should_play_test_sound() {
+        if ui::should_play_test_sound() {
             if let Ok(settings) = self.settings.lock() {
                 let volume = settings.screenshot.sound_volume;
                 if let Ok(ss) = self.screenshot_manager.lock() { 
@@ -311,6 +425,8 @@ impl eframe::App for CaptureCardViewer {
     }
     
     fn on_exit(&mut self, _gl: Option<&eframe::glow::Context>) {
+        // 録画中なら索引を書いてファイルを閉じる
+        self.stop_recording();
         // 終了時に最新のウィンドウサイズと位置を取得して保存
         if let Ok(settings) = self.settings.lock() {
             // 最新の設定が反映されていることを確認してから保存
@@ -320,31 +436,123 @@ impl eframe::App for CaptureCardViewer {
 }
 
 impl CaptureCardViewer {
+    // コマンドラインで指定されたファイルを映像デバイスとして選択
+    fn with_input_file(self, path: Option<String>) -> Self {
+        if let Some(path) = path {
+            let path = std::fs::canonicalize(&path).unwrap_or_else(|_| std::path::PathBuf::from(&path));
+            println!("Debug: Using input file {:?}", path);
+            if let Ok(mut s) = self.settings.lock() {
+                s.video.device_name = Some(file_source::file_device_name(&path));
+                s.video.device_id = None;
+            }
+        }
+        self
+    }
+
+    fn update_device_status(&mut self) {
+        self.device_status = self.devices.status();
+        // 実際に開いたデバイスの識別情報を保存 (名前だけの古い設定もここで補完される)
+        // 識別情報が揃っている設定は選び直された直後の可能性があるため上書きしない
+        if let Some((name, id)) = &self.device_status.opened_video {
+            if let Ok(mut settings) = self.settings.lock() {
+                let legacy = settings.video.device_id.as_ref().is_none_or(|saved| saved.path.is_empty());
+                if legacy && settings.video.device_name.as_ref() == Some(name) && settings.video.device_id.as_ref() != Some(id) {
+                    settings.video.device_id = Some(id.clone());
+                    settings.save();
+                }
+            }
+        }
+    }
+
+    // 映像がない時に表示する文言
+    fn no_video_message(&self) -> String {
+        match &self.device_status.video {
+            DeviceState::Idle => "映像デバイスが選択されていません".to_string(),
+            DeviceState::Connecting => "接続中...".to_string(),
+            DeviceState::Failed(e) => format!("映像デバイスに接続できません\n{}", e),
+            DeviceState::Running => "映像信号がありません".to_string(),
+        }
+    }
+
     fn update_video_texture(&mut self, ctx: &egui::Context) {
-        if let Ok(video) = self.video_capture.lock() {
-            if let Some(frame) = video.get_latest_frame() {
-                // 最適化: テクスチャオプションをNearest（補間なし）に設定し、性能向上
-                let texture_options = egui::TextureOptions {
-                    magnification: egui::TextureFilter::Nearest,
-                    minification: egui::TextureFilter::Linear,
-                    wrap_mode: egui::TextureWrapMode::ClampToEdge,
-                };
-                
-                let image = egui::ColorImage::from_rgb([frame.width, frame.height], &frame.data);
-                if let Some(texture) = &mut self.video_texture {
-                    texture.set(image, texture_options);
-                } else {
-                    self.video_texture = Some(ctx.load_texture("video_frame", image, texture_options));
+        let latest = self.devices.frames().get_latest_frame();
+        // レトロフィルタの出力は拡大済みなので事前拡大しない
+        let retro = self.retro_settings.is_active();
+        let prescale = if retro { 1 } else { self.prescale };
+        // レトロフィルタとシャープバイリニアの事前拡大はワーカースレッドで処理し、処理済みのフレームを表示する
+        // (処理が終わるまでは前の結果のまま)
+        let frame = if retro || prescale > 1 {
+            if let Some(frame) = latest {
+                let job = (frame.generation, self.retro_settings, prescale);
+                if self.retro_submitted != Some(job) && self.retro.submit(&frame, self.retro_settings, prescale) {
+                    self.retro_submitted = Some(job);
                 }
+            }
+            self.retro.latest(&self.retro_settings, prescale).map(|output| (output.source_size, output.frame))
+        } else {
+            latest.map(|frame| ([frame.width, frame.height], frame))
+        };
 
-                // より積極的な再描画要求
-                ctx.request_repaint();
+        // 拡大方式が変わった場合は同じフレームでも作り直す
+        let texture_scaling = (self.scaling, prescale, retro);
+        if let Some((size, frame)) = frame.filter(|(_, f)| f.generation != self.video_texture_generation || texture_scaling != self.texture_scaling) {
+            self.video_texture_generation = frame.generation;
+            self.texture_scaling = texture_scaling;
+            // 補間方法は拡大方式で決まる (既定は拡大: ニアレスト / 縮小: バイリニア)
+            let texture_options = scaling::texture_options(self.scaling);
+            
+            // 入力解像度が変わった場合 (テクスチャは同じハンドルのままサイズが変わる)
+            if self.video_texture.is_some() && self.video_size != size {
+                self.on_video_size_changed(ctx, size);
+            }
+            self.video_size = size;
+
+            // キャプチャスレッド・ワーカーで変換済みの画像をそのまま渡す (UI スレッドでは変換・コピーしない)
+            let image = frame.image.clone();
+            if let Some(texture) = &mut self.video_texture {
+                texture.set(image, texture_options);
+            } else {
+                self.video_texture = Some(ctx.load_texture("video_frame", image, texture_options));
+            }
+            // 補正前の映像も同じようにアップロード (レトロフィルタの出力には付かない)
+            match &frame.ungraded {
+                Some(ungraded) => {
+                    if let Some(texture) = &mut self.ungraded_texture {
+                        texture.set(ungraded.clone(), texture_options);
+                    } else {
+                        self.ungraded_texture = Some(ctx.load_texture("video_frame_ungraded", ungraded.clone(), texture_options));
+                    }
+                }
+                None => self.ungraded_texture = None,
             }
+
+            // より積極的な再描画要求
+            ctx.request_repaint();
         }
         // フレームがない場合でも定期的に再チェック
         ctx.request_repaint_after(std::time::Duration::from_millis(16)); // ~60fps
     }
     
+    fn on_video_size_changed(&mut self, ctx: &egui::Context, size: [usize; 2]) {
+        println!("Debug: Video size changed to {}x{}", size[0], size[1]);
+        self.last_resolution_change = Some(Instant::now());
+        // 縦横比の変更時と同じく、クロップ・黒帯を除いた表示範囲に合わせる
+        let (_, image_size) = self.display_region(size);
+        self.fit_window_to_image(ctx, image_size);
+    }
+
+    // 設定が有効なら、幅はそのままで高さを映像の縦横比 (画素・表示の縦横比と回転を反映したもの) に合わせる
+    fn fit_window_to_image(&self, ctx: &egui::Context, image_size: egui::Vec2) {
+        let fit_window = self.settings.lock().is_ok_and(|s| s.ui.fit_window_to_source);
+        if fit_window && !self.is_fullscreen && image_size.x > 0.0 && image_size.y > 0.0 {
+            if let Some(rect) = ctx.input(|i| i.viewport().inner_rect) {
+                let size = self.displayed_image_size(image_size);
+                let height = rect.width() * size.y / size.x;
+                ctx.send_viewport_cmd(egui::ViewportCommand::InnerSize(egui::vec2(rect.width(), height)));
+            }
+        }
+    }
+
     fn handle_hotkeys(&mut self) {
         let should_screenshot = {
             if let Ok(screenshot_manager) = self.screenshot_manager.lock() {
@@ -368,50 +576,146 @@ impl CaptureCardViewer {
     fn take_screenshot(&mut self) {
         println!("take_screenshot: Starting screenshot process");
         
-        // 最新フレームの生データを抽出
-        if let Ok(video) = self.video_capture.lock() {
-            if let Some(frame) = video.get_latest_frame() {
-                println!("take_screenshot: Got video frame {}x{}", frame.width, frame.height);
+        // 最新フレームの生データを抽出 (レトロフィルタをスクリーンショットにも適用する場合は処理済みのフレームと拡大率)
+        let retro = self.retro_settings.apply_to_screenshots && self.retro_settings.is_active();
+        let latest = match retro.then(|| self.retro.latest(&self.retro_settings, 1)).flatten() {
+            Some(output) if output.source_size[0] > 0 => {
+                let scale = (output.frame.width / output.source_size[0]) as u32;
+                Some((output.frame, output.source_size, scale))
+            }
+            _ => self.devices.frames().get_latest_frame().map(|frame| {
+                let size = [frame.width, frame.height];
+                (frame, size, 1)
+            }),
+        };
+        if let Some((frame, source_size, scale)) = latest {
+            println!("take_screenshot: Got video frame {}x{}", frame.width, frame.height);
+            
+            // タイムスタンプとパスを構築
+            let timestamp = Local::now().format("%Y-%m-%d_%H-%M-%S-%3f").to_string();
+            if let Ok(settings) = self.settings.lock() {
+                let path = settings.get_screenshot_path(&timestamp);
+                println!("take_screenshot: Saving to {:?}", path);
                 
-                // タイムスタンプとパスを構築
-                let timestamp = Local::now().format("%Y-%m-%d_%H-%M-%S-%3f").to_string();
-                if let Ok(settings) = self.settings.lock() {
-                    let path = settings.get_screenshot_path(&timestamp);
-                    println!("take_screenshot: Saving to {:?}", path);
-                    
-                    // 親ディレクトリを作成
-                    if let Some(parent) = path.parent() { 
-                        if let Err(e) = std::fs::create_dir_all(parent) {
-                            println!("take_screenshot: Failed to create directories: {}", e);
-                        }
+                // 親ディレクトリを作成
+                if let Some(parent) = path.parent() { 
+                    if let Err(e) = std::fs::create_dir_all(parent) {
+                        println!("take_screenshot: Failed to create directories: {}", e);
                     }
-                    
-                    // RGBデータを画像に変換して保存
-                    if let Some(img_buf) = image::RgbImage::from_raw(frame.width as u32, frame.height as u32, frame.data.clone()) {
-                        match img_buf.save(&path) {
-                            Ok(()) => {
-                                println!("take_screenshot: Screenshot saved successfully to {:?}", path);
-                                let volume = settings.screenshot.sound_volume;
-                                if let Ok(ss) = self.screenshot_manager.lock() { 
-                                    ss.play_screenshot_sound(volume); 
-                                }
+                }
+                
+                // RGBAデータを画像に変換して保存 (クロップ範囲だけ、クロップなしなら共有バッファをそのまま渡す、JPEGではアルファは無視される)
+                if frame.image.pixels.len() == frame.width * frame.height {
+                    // クロップ範囲は元のフレームの座標なので拡大率を掛ける
+                    let (x, y, w, h) = self.crop_rect(source_size[0] as u32, source_size[1] as u32);
+                    let rect = (x * scale, y * scale, w * scale, h * scale);
+                    let (pixels, width, height) = self.orientation.apply_to_rgba(frame.cropped_rgba(rect), rect.2, rect.3);
+                    // 表示と同じ縦横比に引き伸ばす (入力の画素数のまま保存する設定でなければ)
+                    let (pixels, width, height) = if settings.screenshot.keep_raw_pixels {
+                        (pixels, width, height)
+                    } else {
+                        let size = self.displayed_image_size(egui::vec2(w as f32, h as f32));
+                        scaling::resize_to_aspect(pixels, width, height, size.x / size.y)
+                    };
+                    match image::save_buffer(&path, &pixels, width, height, image::ColorType::Rgba8) {
+                        Ok(()) => {
+                            println!("take_screenshot: Screenshot saved successfully to {:?}", path);
+                            let volume = settings.screenshot.sound_volume;
+                            if let Ok(ss) = self.screenshot_manager.lock() { 
+                                ss.play_screenshot_sound(volume); 
                             }
-                            Err(e) => println!("take_screenshot: Failed to save image: {}", e)
                         }
-                    } else {
-                        println!("take_screenshot: Failed to create RgbImage from raw data");
+                        Err(e) => println!("take_screenshot: Failed to save image: {}", e)
                     }
                 } else {
-                    println!("take_screenshot: Failed to lock settings");
+                    println!("take_screenshot: Frame data size does not match dimensions");
                 }
             } else {
-                println!("take_screenshot: No video frame available");
+                println!("take_screenshot: Failed to lock settings");
             }
         } else {
-            println!("take_screenshot: Failed to lock video_capture");
+            println!("take_screenshot: No video frame available");
         }
     }
     
+    // 録画の開始・停止
+    fn toggle_recording(&mut self) {
+        if self.recorder.is_some() {
+            self.stop_recording();
+            return;
+        }
+        let Ok(settings) = self.settings.lock() else { return };
+        // フレームレートは実際の入力の間隔から (まだ測れていなければ設定値)
+        let fps = self.devices.frames().measured_fps()
+            .map(|fps| fps.round() as u32)
+            .or(settings.video.fps)
+            .unwrap_or(60)
+            .max(1);
+        let options = RecordOptions {
+            fps,
+            quality: settings.recording.quality,
+            crop: self.crop,
+            bars: self.letterbox.bars(),
+            orientation: self.orientation,
+        };
+        let timestamp = Local::now().format("%Y-%m-%d_%H-%M-%S-%3f").to_string();
+        let path = settings.get_recording_path(&timestamp);
+        let audio = settings.recording.record_audio.then(|| self.devices.audio_tap());
+        drop(settings);
+        if audio.is_some_and(|tap| tap.format().is_none()) {
+            println!("Warning: Audio input is not running, recording video only");
+        }
+        match Recorder::start(path, self.devices.frames(), audio, options) {
+            Ok(recorder) => {
+                self.recording_status = Some(("録画開始".to_string(), Instant::now()));
+                self.recorder = Some(recorder);
+            }
+            Err(e) => {
+                println!("Warning: Failed to start recording: {}", e);
+                self.recording_status = Some((format!("録画を開始できません: {}", e), Instant::now()));
+            }
+        }
+    }
+
+    fn stop_recording(&mut self) {
+        let Some(recorder) = self.recorder.take() else { return };
+        let message = match recorder.stop() {
+            Ok(summary) => format!("録画を保存しました: {}", summary.path.display()),
+            Err(e) => format!("録画に失敗しました: {}", e),
+        };
+        self.recording_status = Some((message, Instant::now()));
+    }
+
+    fn paint_recording_overlay(&self, ctx: &egui::Context) {
+        let painter = ctx.layer_painter(egui::LayerId::new(egui::Order::Foreground, egui::Id::new("recording_overlay")));
+        let origin = ctx.screen_rect().min + egui::vec2(16.0, 16.0);
+        if let Some(recorder) = &self.recorder {
+            let elapsed = recorder.elapsed();
+            // 0.5秒ごとに点滅
+            if elapsed.as_millis() / 500 % 2 == 0 {
+                painter.circle_filled(origin + egui::vec2(8.0, 8.0), 6.0, egui::Color32::RED);
+            }
+            let secs = elapsed.as_secs();
+            painter.text(
+                origin + egui::vec2(20.0, 8.0),
+                egui::Align2::LEFT_CENTER,
+                format!("REC {:02}:{:02}", secs / 60, secs % 60),
+                egui::FontId::proportional(16.0),
+                egui::Color32::WHITE,
+            );
+            // 点滅と経過時間の更新
+            ctx.request_repaint_after(std::time::Duration::from_millis(100));
+        }
+        if let Some((message, t)) = &self.recording_status {
+            if t.elapsed().as_secs_f32() < 3.0 {
+                let galley = painter.layout_no_wrap(message.clone(), egui::FontId::proportional(14.0), egui::Color32::WHITE);
+                let pos = origin + egui::vec2(0.0, 24.0);
+                painter.rect_filled(egui::Rect::from_min_size(pos, galley.size()).expand(5.0), 5.0, egui::Color32::from_black_alpha(160));
+                painter.galley(pos, galley, egui::Color32::WHITE);
+            }
+        }
+    }
+
     fn show_windowed_ui(&mut self, ctx: &egui::Context) {
         egui::CentralPanel::default()
             .frame(egui::Frame::none().inner_margin(egui::Margin::same(2.0))) // マージンを2pxに設定
@@ -420,23 +724,23 @@ impl CaptureCardViewer {
             let available_size = ui.available_size();
             
             if let Some(texture) = &self.video_texture {
-                let image_size = texture.size_vec2();
-                let display_size = if self.maintain_aspect_ratio {
-                    self.calculate_aspect_ratio_size(image_size, available_size)
-                } else {
-                    available_size
-                };
-                
-                let rect = egui::Rect::from_center_size(
-                    ui.available_rect_before_wrap().center(),
-                    display_size
-                );
+                let texture_id = texture.id();
+                let texture_size = self.video_size;
+                let (uv, image_size) = self.display_region(texture_size);
+                let (rect, prescale) = self.video_rect(ui, image_size, available_size);
                 
                 let response = ui.allocate_rect(rect, egui::Sense::click_and_drag());
-                ui.painter().image(texture.id(), rect, egui::Rect::from_min_size(egui::Pos2::ZERO, egui::Vec2::splat(1.0)), egui::Color32::WHITE);
+                let panning = self.update_zoom(ctx, rect, &response);
+                let view_uv = self.zoom.apply_to_uv(self.orientation, uv);
+                self.orientation.paint(ui.painter(), texture_id, rect, view_uv);
+                self.paint_lut_split(ui.painter(), rect, view_uv);
+                self.zoom.paint_minimap(ui.painter(), texture_id, self.orientation, rect, uv);
+                self.prescale = prescale;
                 
-                // ウィンドウドラッグを処理（設定が有効な場合のみ）
-                if response.dragged() {
+                if self.crop_mode {
+                    self.crop_editor(ui, rect, &response, texture_size);
+                } else if response.dragged() && !panning {
+                    // ウィンドウドラッグを処理（設定が有効な場合のみ）
                     if let Ok(settings) = self.settings.lock() {
                         if settings.ui.enable_drag_move {
                             ctx.send_viewport_cmd(egui::ViewportCommand::StartDrag);
@@ -454,8 +758,8 @@ impl CaptureCardViewer {
                     self.context_menu_pos = ctx.input(|i| i.pointer.latest_pos().unwrap_or_default());
                 }
                 
-                // 音量調整のためのスクロールを処理
-                if response.hovered() {
+                // 音量調整のためのスクロールを処理 (Ctrl+ホイールは拡大)
+                if response.hovered() && !ctx.input(|i| i.modifiers.ctrl) {
                     ctx.input(|i| {
                         if i.raw_scroll_delta.y > 0.0 {
                             self.volume = (self.volume + 10.0).min(200.0);
@@ -476,8 +780,9 @@ impl CaptureCardViewer {
                 }
             } else {
                 let response = ui.allocate_response(available_size, egui::Sense::click_and_drag());
+                let message = self.no_video_message();
                 ui.centered_and_justified(|ui| {
-                    ui.label("映像信号がありません");
+                    ui.label(message);
                 });
                 
                 // 空エリアでのウィンドウドラッグを処理（設定が有効な場合のみ）
@@ -506,23 +811,24 @@ impl CaptureCardViewer {
                 let available_size = ui.available_size();
                 
                 if let Some(texture) = &self.video_texture {
-                    let image_size = texture.size_vec2();
-                    let display_size = if self.maintain_aspect_ratio {
-                        self.calculate_aspect_ratio_size(image_size, available_size)
-                    } else {
-                        available_size
-                    };
-                    
-                    let rect = egui::Rect::from_center_size(
-                        ui.available_rect_before_wrap().center(),
-                        display_size
-                    );
+                    let texture_id = texture.id();
+                    let texture_size = self.video_size;
+                    let (uv, image_size) = self.display_region(texture_size);
+                    let (rect, prescale) = self.video_rect(ui, image_size, available_size);
                     
                     let response = ui.allocate_rect(rect, egui::Sense::click_and_drag());
-                    ui.painter().image(texture.id(), rect, egui::Rect::from_min_size(egui::Pos2::ZERO, egui::Vec2::splat(1.0)), egui::Color32::WHITE);
+                    self.update_zoom(ctx, rect, &response);
+                    let view_uv = self.zoom.apply_to_uv(self.orientation, uv);
+                    self.orientation.paint(ui.painter(), texture_id, rect, view_uv);
+                    self.paint_lut_split(ui.painter(), rect, view_uv);
+                    self.zoom.paint_minimap(ui.painter(), texture_id, self.orientation, rect, uv);
+                    self.prescale = prescale;
                     
                     // フルスクリーンではドラッグ移動を完全に無効化
                     // （フルスクリーンでは画面の移動自体が意味をなさないため）
+                    if self.crop_mode {
+                        self.crop_editor(ui, rect, &response, texture_size);
+                    }
                     
                     // ダブルクリックでウィンドウモードに戻る
                     if response.double_clicked() { self.toggle_fullscreen(ctx, false); }
@@ -533,8 +839,8 @@ impl CaptureCardViewer {
                         self.context_menu_pos = ctx.input(|i| i.pointer.latest_pos().unwrap_or_default());
                     }
                     
-                    // マウススクロールでの音量調整（ウィンドウ版と同じ機能）
-                    if response.hovered() {
+                    // マウススクロールでの音量調整（ウィンドウ版と同じ機能、Ctrl+ホイールは拡大）
+                    if response.hovered() && !ctx.input(|i| i.modifiers.ctrl) {
                         ctx.input(|i| {
                             if i.raw_scroll_delta.y > 0.0 {
                                 self.volume = (self.volume + 10.0).min(200.0);
@@ -556,8 +862,9 @@ impl CaptureCardViewer {
                 } else {
                     // 映像信号がない場合
                     let response = ui.allocate_response(available_size, egui::Sense::click_and_drag());
+                    let message = self.no_video_message();
                     ui.centered_and_justified(|ui| {
-                        ui.label("映像信号がありません");
+                        ui.label(message);
                     });
                     
                     // フルスクリーンではドラッグ移動を完全に無効化
@@ -656,12 +963,70 @@ impl CaptureCardViewer {
                         }
                     }
 
+                    // 拡大方式・画素の縦横比 (クリックで順に切り替え)
+                    let (scaling, pixel_aspect) = (self.scaling, self.pixel_aspect);
+                    if ui.button(format!("拡大方式: {}", ui::scaling_label(scaling))).clicked() {
+                        let next = ui::SCALING_MODES[(ui::SCALING_MODES.iter().position(|&m| m == scaling).unwrap_or(0) + 1) % ui::SCALING_MODES.len()];
+                        if let Ok(mut settings) = self.settings.lock() {
+                            settings.ui.scaling = next;
+                            settings.save();
+                        }
+                    }
+                    if ui.button(format!("画素の縦横比: {}", ui::pixel_aspect_label(pixel_aspect))).clicked() {
+                        let next = ui::PIXEL_ASPECTS[(ui::PIXEL_ASPECTS.iter().position(|&a| a == pixel_aspect).unwrap_or(0) + 1) % ui::PIXEL_ASPECTS.len()];
+                        if let Ok(mut settings) = self.settings.lock() {
+                            settings.ui.pixel_aspect = next;
+                            settings.save();
+                        }
+                    }
+                    let display_aspect = self.display_aspect;
+                    if ui.button(format!("表示の縦横比: {}", ui::display_aspect_label(display_aspect, self.custom_aspect))).clicked() {
+                        let next = ui::DISPLAY_ASPECTS[(ui::DISPLAY_ASPECTS.iter().position(|&a| a == display_aspect).unwrap_or(0) + 1) % ui::DISPLAY_ASPECTS.len()];
+                        if let Ok(mut settings) = self.settings.lock() {
+                            settings.ui.display_aspect = next;
+                            settings.save();
+                        }
+                    }
+
+                    // 黒帯の自動クロップ
+                    let mut auto_crop_bars = self.settings.lock().map(|s| s.ui.auto_crop_bars).unwrap_or(false);
+                    if ui.checkbox(&mut auto_crop_bars, "黒帯を自動でクロップ").changed() {
+                        if let Ok(mut settings) = self.settings.lock() {
+                            settings.ui.auto_crop_bars = auto_crop_bars;
+                            settings.save();
+                        }
+                    }
+
+                    // クロップ調整モード (設定は辺を離した時に保存)
+                    if ui.checkbox(&mut self.crop_mode, "クロップ調整").changed() && !self.crop_mode {
+                        self.crop_drag = None;
+                    }
+                    if self.zoom.is_zoomed() && ui.button(format!("拡大表示をリセット (x{:.1}) [0]", self.zoom.zoom())).clicked() {
+                        self.zoom.reset();
+                    }
+                    if !self.crop.is_none() && ui.button("クロップをリセット").clicked() {
+                        self.crop = CropSettings::default();
+                        if let Ok(mut settings) = self.settings.lock() {
+                            settings.video.profile_mut().crop = self.crop;
+                            settings.save();
+                        }
+                    }
+
                     ui.separator();
+                    let recording_label = match &self.recorder {
+                        Some(recorder) => {
+                            let secs = recorder.elapsed().as_secs();
+                            format!("録画停止 ({:02}:{:02})", secs / 60, secs % 60)
+                        }
+                        None => format!("録画開始 ({})", self.settings.lock().map(|s| s.recording.hotkey.clone()).unwrap_or_default()),
+                    };
+                    if ui.button(recording_label).clicked() {
+                        self.toggle_recording();
+                        close_menu = true;
+                    }
                     if ui.button("デバイス再接続").clicked() {
-                        // 強制的にデバイス再接続（last_*をクリアして強制再接続）
-                        self.last_video_device = None;
-                        self.last_audio_device = None;
-                        self.apply_settings(false);
+                        // 設定に変更がなくても映像・音声の両方を開き直す
+                        self.apply_settings(true);
                         close_menu = true;
                     }
                     ui.separator();
@@ -691,7 +1056,194 @@ impl CaptureCardViewer {
         if close_menu { self.show_context_menu = false; }
     }
     
+    // 黒帯の検出は負荷を抑えるため一定間隔で行う (ちらつき防止のヒステリシスは LetterboxDetector 側)
+    fn update_letterbox(&mut self) {
+        let enabled = self.settings.lock().map(|s| s.ui.auto_crop_bars).unwrap_or(false);
+        if !enabled {
+            self.letterbox.reset();
+            return;
+        }
+        if self.last_letterbox_check.elapsed().as_millis() < 250 {
+            return;
+        }
+        self.last_letterbox_check = Instant::now();
+        if let Some(frame) = self.devices.frames().get_latest_frame() {
+            self.letterbox.update(&frame);
+        }
+    }
+
+    // 手動クロップと自動検出した黒帯を合わせた範囲 (x, y, 幅, 高さ)
+    fn crop_rect(&self, width: u32, height: u32) -> (u32, u32, u32, u32) {
+        self.letterbox.bars().crop_rect(self.crop.rect(width, height), width, height)
+    }
+
+    // 表示する範囲 (UV) とその大きさ (ピクセル)、クロップ調整中は全体を表示する
+    fn display_region(&self, size: [usize; 2]) -> (egui::Rect, egui::Vec2) {
+        let (width, height) = (size[0] as u32, size[1] as u32);
+        let full = egui::Rect::from_min_max(egui::Pos2::ZERO, egui::pos2(1.0, 1.0));
+        if self.crop_mode || width == 0 || height == 0 {
+            return (full, egui::vec2(width as f32, height as f32));
+        }
+        let (x, y, w, h) = self.crop_rect(width, height);
+        let uv = egui::Rect::from_min_size(
+            egui::pos2(x as f32 / width as f32, y as f32 / height as f32),
+            egui::vec2(w as f32 / width as f32, h as f32 / height as f32),
+        );
+        (uv, egui::vec2(w as f32, h as f32))
+    }
+
+    // 拡大表示の操作 (Ctrl+ホイール・ピンチで拡大、拡大中のドラッグで移動)、移動中なら true
+    // クロップ調整中は映像全体を表示するので拡大しない
+    fn update_zoom(&mut self, ctx: &egui::Context, rect: egui::Rect, response: &egui::Response) -> bool {
+        if self.crop_mode {
+            self.zoom.reset();
+            return false;
+        }
+        if response.hovered() {
+            let (zoom_delta, pointer) = ctx.input(|i| (i.zoom_delta(), i.pointer.hover_pos()));
+            if zoom_delta != 1.0 {
+                let anchor = pointer.map_or(egui::pos2(0.5, 0.5), |p| ((p - rect.min) / rect.size()).to_pos2());
+                self.zoom.zoom_at(zoom_delta, anchor);
+            }
+        }
+        let panning = self.zoom.is_zoomed() && response.dragged_by(egui::PointerButton::Primary);
+        if panning {
+            self.zoom.pan(response.drag_delta() / rect.size());
+        }
+        panning
+    }
+
+    // LUT の補正前後の比較: 元の映像の左半分に補正前の映像を重ね、中央に境界線を描く
+    // 位置は元の映像の座標で決めて、クロップ・ズーム・回転後の表示位置に合わせる
+    // (キャプチャしたフレームはすべて補正済みなので、スクリーンショット・録画には影響しない)
+    fn paint_lut_split(&self, painter: &egui::Painter, rect: egui::Rect, uv: egui::Rect) {
+        let (true, Some(ungraded)) = (self.lut_split, &self.ungraded_texture) else {
+            return;
+        };
+        // 表示範囲の中での元の映像の中央の位置
+        let x = ((0.5 - uv.min.x) / uv.width()).clamp(0.0, 1.0);
+        let to_screen = |p: egui::Pos2| rect.min + self.orientation.to_display(p).to_vec2() * rect.size();
+        if x > 0.0 {
+            let mut mesh = egui::Mesh::with_texture(ungraded.id());
+            for source in [egui::pos2(0.0, 0.0), egui::pos2(x, 0.0), egui::pos2(x, 1.0), egui::pos2(0.0, 1.0)] {
+                mesh.vertices.push(egui::epaint::Vertex {
+                    pos: to_screen(source),
+                    uv: uv.min + source.to_vec2() * uv.size(),
+                    color: egui::Color32::WHITE,
+                });
+            }
+            mesh.add_triangle(0, 1, 2);
+            mesh.add_triangle(0, 2, 3);
+            painter.add(egui::Shape::mesh(mesh));
+        }
+        if x > 0.0 && x < 1.0 {
+            let [top, bottom] = [0.0, 1.0].map(|y| to_screen(egui::pos2(x, y)));
+            painter.line_segment([top, bottom], egui::Stroke::new(1.0, egui::Color32::from_white_alpha(160)));
+        }
+    }
+
+    // クロップ調整: 切り落とす部分を暗くして範囲の枠を描き、辺のドラッグで範囲を変える
+    // クロップは元の映像の座標で持つので、回転・反転している場合は表示との間で座標を変換する
+    fn crop_editor(&mut self, ui: &egui::Ui, rect: egui::Rect, response: &egui::Response, size: [usize; 2]) {
+        let (width, height) = (size[0] as u32, size[1] as u32);
+        if width == 0 || height == 0 {
+            return;
+        }
+        let orientation = self.orientation;
+        let (x, y, w, h) = self.crop.rect(width, height);
+        let source_size = egui::vec2(width as f32, height as f32);
+        // 元の映像のピクセル座標 <-> 画面上の座標
+        let to_screen = |p: egui::Pos2| rect.min + orientation.to_display((p.to_vec2() / source_size).to_pos2()).to_vec2() * rect.size();
+        let to_pixels = |p: egui::Pos2| (orientation.to_source(((p - rect.min) / rect.size()).to_pos2()).to_vec2() * source_size).to_pos2();
+        let crop_rect = egui::Rect::from_two_pos(
+            to_screen(egui::pos2(x as f32, y as f32)),
+            to_screen(egui::pos2((x + w) as f32, (y + h) as f32)),
+        );
+
+        let painter = ui.painter();
+        let shade = egui::Color32::from_black_alpha(160);
+        painter.rect_filled(egui::Rect::from_min_max(rect.min, egui::pos2(rect.max.x, crop_rect.min.y)), 0.0, shade);
+        painter.rect_filled(egui::Rect::from_min_max(egui::pos2(rect.min.x, crop_rect.max.y), rect.max), 0.0, shade);
+        painter.rect_filled(egui::Rect::from_min_max(egui::pos2(rect.min.x, crop_rect.min.y), egui::pos2(crop_rect.min.x, crop_rect.max.y)), 0.0, shade);
+        painter.rect_filled(egui::Rect::from_min_max(egui::pos2(crop_rect.max.x, crop_rect.min.y), egui::pos2(rect.max.x, crop_rect.max.y)), 0.0, shade);
+        painter.rect_stroke(crop_rect, 0.0, egui::Stroke::new(2.0, egui::Color32::YELLOW));
+        painter.text(
+            crop_rect.center_top() + egui::vec2(0.0, 10.0),
+            egui::Align2::CENTER_TOP,
+            format!("クロップ調整: 枠の辺をドラッグ ({}x{})", w, h),
+            egui::FontId::proportional(16.0),
+            egui::Color32::YELLOW,
+        );
+
+        // ドラッグ開始位置に最も近い辺を掴む (枠から離れすぎている場合は何もしない)
+        if response.drag_started() {
+            // 元の映像の 1 ピクセルあたりの画面上のピクセル数 (元の映像の横方向, 縦方向)
+            let displayed = orientation.oriented_size(rect.size());
+            let scale = egui::vec2(displayed.x / source_size.x, displayed.y / source_size.y);
+            self.crop_drag = response.interact_pointer_pos().map(to_pixels).and_then(|pos| {
+                [
+                    (CropEdge::Left, (pos.x - x as f32).abs() * scale.x),
+                    (CropEdge::Right, (pos.x - (x + w) as f32).abs() * scale.x),
+                    (CropEdge::Top, (pos.y - y as f32).abs() * scale.y),
+                    (CropEdge::Bottom, (pos.y - (y + h) as f32).abs() * scale.y),
+                ]
+                .into_iter()
+                .filter(|(_, distance)| *distance < 24.0)
+                .min_by(|a, b| a.1.total_cmp(&b.1))
+                .map(|(edge, _)| edge)
+            });
+        }
+        if let (Some(edge), Some(pos)) = (self.crop_drag.filter(|_| response.dragged()), response.interact_pointer_pos()) {
+            let pos = to_pixels(pos);
+            let position = match edge {
+                CropEdge::Left | CropEdge::Right => pos.x,
+                CropEdge::Top | CropEdge::Bottom => pos.y,
+            };
+            self.crop.set_edge(edge, position.round().max(0.0) as u32, width, height);
+            if let Ok(mut settings) = self.settings.lock() {
+                settings.video.profile_mut().crop = self.crop;
+            }
+        }
+        if response.drag_released() && self.crop_drag.take().is_some() {
+            if let Ok(settings) = self.settings.lock() {
+                settings.save();
+            }
+        }
+    }
+
+    // 画素の縦横比 (表示の縦横比が指定されていればその比率) に補正し、回転していれば縦横を入れ替えた表示上の大きさ
+    fn displayed_image_size(&self, image_size: egui::Vec2) -> egui::Vec2 {
+        let width = match self.display_aspect.ratio(self.custom_aspect) {
+            Some(ratio) => image_size.y * ratio,
+            None => image_size.x * self.pixel_aspect.ratio(),
+        };
+        self.orientation.oriented_size(egui::vec2(width, image_size.y))
+    }
+
+    // 映像を描く範囲 (中央寄せ) と、シャープバイリニアで事前に拡大する倍率
+    fn video_rect(&self, ui: &egui::Ui, image_size: egui::Vec2, available_size: egui::Vec2) -> (egui::Rect, usize) {
+        let pixels_per_point = ui.ctx().pixels_per_point();
+        let center = ui.available_rect_before_wrap().center();
+        // 整数倍は物理ピクセル単位で倍率を決め、端を画素の境界に揃える (1倍でも収まらなければ縮小表示)
+        if self.scaling == ScalingMode::Integer {
+            if let Some(size) = scaling::integer_size(self.displayed_image_size(image_size), available_size * pixels_per_point) {
+                let size = size / pixels_per_point;
+                let min = ui.painter().round_pos_to_pixels(center - size / 2.0);
+                return (egui::Rect::from_min_size(min, size), 1);
+            }
+        }
+        let display_size = if self.maintain_aspect_ratio || self.scaling == ScalingMode::Integer {
+            self.calculate_aspect_ratio_size(image_size, available_size)
+        } else {
+            available_size
+        };
+        let prescale = scaling::prescale_factor(self.scaling, self.displayed_image_size(image_size), display_size * pixels_per_point);
+        (egui::Rect::from_center_size(center, display_size), prescale)
+    }
+
     fn calculate_aspect_ratio_size(&self, image_size: egui::Vec2, available_size: egui::Vec2) -> egui::Vec2 {
+        // 画素の縦横比を補正し、90度・270度回転している場合は縦横比を入れ替える
+        let image_size = self.displayed_image_size(image_size);
         let image_aspect = image_size.x / image_size.y;
         let available_aspect = available_size.x / available_size.y;
         
@@ -705,7 +1257,26 @@ impl CaptureCardViewer {
     }
 }
 
+// "F9" や "Ctrl+Shift+R" のようなキーの組み合わせ (ウィンドウ内のキー入力用)
+fn parse_key_shortcut(text: &str) -> Option<(egui::Modifiers, egui::Key)> {
+    let mut modifiers = egui::Modifiers::NONE;
+    let mut key = None;
+    for part in text.split('+').map(str::trim) {
+        match part.to_lowercase().as_str() {
+            "ctrl" | "control" => modifiers.ctrl = true,
+            "shift" => modifiers.shift = true,
+            "alt" => modifiers.alt = true,
+            _ => key = Some(egui::Key::from_name(part)?),
+        }
+    }
+    Some((modifiers, key?))
+}
+
 fn main() -> Result<(), eframe::Error> {
+    let args: Vec<String> = std::env::args().collect();
+    // --input <ファイル> : 録画ファイル (.y4m / 生の YUYV・NV12) を映像入力にする
+    let input_file = args.iter().position(|a| a == "--input").and_then(|i| args.get(i + 1)).cloned();
+
     // 設定から保存されたウィンドウサイズと位置を読み込む
     let settings = AppSettings::load();
     let mut viewport_builder = egui::ViewportBuilder::default()
@@ -733,29 +1304,39 @@ fn main() -> Result<(), eframe::Error> {
         options,
         Box::new(|cc| {
             configure_japanese_font(&cc.egui_ctx);
-            Box::new(CaptureCardViewer::default())
+            Box::new(CaptureCardViewer::default().with_input_file(input_file))
         }),
     )
 }
 
 fn configure_japanese_font(ctx: &egui::Context) {
-    // WindowsフォントディレクトリからMeiryoの読み込みを試行
-    #[cfg(target_os = "windows")] {
-        let candidate_paths = [
-            "C:/Windows/Fonts/meiryo.ttc",
-            "C:/Windows/Fonts/Meiryo.ttc",
-            "C:/Windows/Fonts/meiryob.ttc",
-        ];
-        for p in candidate_paths.iter() {
-            if let Ok(data) = std::fs::read(p) {
-                let mut fonts = egui::FontDefinitions::default();
-                fonts.font_data.insert("meiryo".to_string(), egui::FontData::from_owned(data));
-                // 優先度のためにプロポーショナル・等幅フォントファミリーの先頭に挿入
-                if let Some(fam) = fonts.families.get_mut(&egui::FontFamily::Proportional) { fam.insert(0, "meiryo".to_string()); }
-                if let Some(fam) = fonts.families.get_mut(&egui::FontFamily::Monospace) { fam.insert(0, "meiryo".to_string()); }
-                ctx.set_fonts(fonts);
-                break;
-            }
+    // OSのフォントディレクトリから日本語フォントの読み込みを試行
+    // Windows: Meiryo
+    #[cfg(target_os = "windows")]
+    let candidate_paths: &[&str] = &[
+        "C:/Windows/Fonts/meiryo.ttc",
+        "C:/Windows/Fonts/Meiryo.ttc",
+        "C:/Windows/Fonts/meiryob.ttc",
+    ];
+    // Linux: Noto Sans CJK (ディストリビューションごとに配置が異なる)
+    #[cfg(target_os = "linux")]
+    let candidate_paths: &[&str] = &[
+        "/usr/share/fonts/opentype/noto/NotoSansCJK-Regular.ttc",
+        "/usr/share/fonts/noto-cjk/NotoSansCJK-Regular.ttc",
+        "/usr/share/fonts/google-noto-cjk/NotoSansCJK-Regular.ttc",
+        "/usr/share/fonts/truetype/fonts-japanese-gothic.ttf",
+    ];
+    #[cfg(not(any(target_os = "windows", target_os = "linux")))]
+    let candidate_paths: &[&str] = &[];
+    for p in candidate_paths.iter() {
+        if let Ok(data) = std::fs::read(p) {
+            let mut fonts = egui::FontDefinitions::default();
+            fonts.font_data.insert("japanese".to_string(), egui::FontData::from_owned(data));
+            // 優先度のためにプロポーショナル・等幅フォントファミリーの先頭に挿入
+            if let Some(fam) = fonts.families.get_mut(&egui::FontFamily::Proportional) { fam.insert(0, "japanese".to_string()); }
+            if let Some(fam) = fonts.families.get_mut(&egui::FontFamily::Monospace) { fam.insert(0, "japanese".to_string()); }
+            ctx.set_fonts(fonts);
+            break;
         }
     }
 }
@@ -787,134 +1368,21 @@ fn load_icon() -> egui::IconData {
 }
 
 impl CaptureCardViewer {
-    fn apply_settings(&mut self, initial: bool) {
+    // 設定をデバイスワーカーと UI に反映 (force なら変更がなくてもデバイスを開き直す)
+    fn apply_settings(&mut self, force: bool) {
         if let Ok(settings) = self.settings.lock() {
-            // Video - リトライ機能付き
-            if let Ok(mut video) = self.video_capture.lock() {
-                let need_video_restart =
-                    settings.video.device_name != self.last_video_device ||
-                    settings.video.resolution != self.last_video_res ||
-                    settings.video.format != self.last_video_format ||
-                    settings.video.fps != self.last_video_fps;
-                    
-                if settings.video.device_name.is_some() && (need_video_restart || initial) {
-                    println!("Debug: Starting video device connection: {:?}", settings.video.device_name);
-                    let mut video_success = false;
-                    let max_retries = if initial { 3 } else { 1 };
-                    
-                    for attempt in 0..max_retries {
-                        if attempt > 0 {
-                            println!("Video device connection attempt {} of {}", attempt + 1, max_retries);
-                            std::thread::sleep(std::time::Duration::from_millis(1000));
-                        }
-                        
-                        match video.start_capture(
-                            settings.video.device_name.as_deref(),
-                            settings.video.resolution,
-                            settings.video.format.as_deref(),
-                            settings.video.fps,
-                        ) {
-                            Ok(_) => {
-                                println!("Debug: Video device connected successfully");
-                                video_success = true;
-                                break;
-                            }
-                            Err(e) => {
-                                println!("Video capture failed (attempt {}): {}", attempt + 1, e);
-                                if attempt < max_retries - 1 {
-                                    continue;
-                                }
-                            }
-                        }
-                    }
-                    
-                    if video_success {
-                        self.last_video_device = settings.video.device_name.clone();
-                        self.last_video_res = settings.video.resolution;
-                        self.last_video_format = settings.video.format.clone();
-                        self.last_video_fps = settings.video.fps;
-                    }
-                }
+            // 変更のあったデバイスだけワーカー側で開き直される
+            let changed = self.applied_devices.as_ref()
+                .is_none_or(|(video, audio)| *video != settings.video || *audio != settings.audio);
+            if force || changed {
+                self.devices.apply(&settings.video, &settings.audio, force);
+                self.applied_devices = Some((settings.video.clone(), settings.audio.clone()));
             }
             
-            // Audio - 改良されたリトライとデフォルト設定
-            if let Ok(mut audio) = self.audio_capture.lock() {
-                let need_audio_restart =
-                    settings.audio.input_device_name != self.last_audio_device ||
-                    settings.audio.sample_rate != self.last_audio_rate ||
-                    settings.audio.channels != self.last_audio_channels ||
-                    initial; // 起動時は必ず接続試行
-                    
-                if need_audio_restart {
-                    println!("Debug: Starting audio device connection");
-                    println!("Debug: Input device: {:?}", settings.audio.input_device_name);
-                    println!("Debug: Output device: {:?}", settings.audio.output_device_name);
-                    
-                    // まずは利用可能なデバイスをリスト
-                    let input_devices = audio.list_input_devices();
-                    let output_devices = audio.list_output_devices();
-                    println!("Debug: Available input devices: {:?}", input_devices);
-                    println!("Debug: Available output devices: {:?}", output_devices);
-                    
-                    let mut audio_success = false;
-                    let max_retries = if initial { 5 } else { 2 }; // 起動時により多くリトライ
-                    
-                    for attempt in 0..max_retries {
-                        if attempt > 0 {
-                            println!("Audio device connection attempt {} of {}", attempt + 1, max_retries);
-                            std::thread::sleep(std::time::Duration::from_millis(300));
-                        }
-                        
-                        // 接続試行
-                        match audio.start_passthrough_with_settings(
-                            settings.audio.input_device_name.as_deref(), 
-                            settings.audio.output_device_name.as_deref(), 
-                            settings.audio.sample_rate, 
-                            settings.audio.channels
-                        ) {
-                            Ok(_) => {
-                                println!("Debug: Audio devices connected successfully");
-                                self.audio_last_error = None;
-                                audio_success = true;
-                                break;
-                            }
-                            Err(e) => {
-                                println!("Audio capture failed (attempt {}): {}", attempt + 1, e);
-                                self.audio_last_error = Some(e.clone());
-                                
-                                // 3回目以降のリトライではデフォルトデバイスを試行
-                                if attempt == 2 && initial {
-                                    println!("Debug: Trying with default devices...");
-                                    match audio.start_passthrough_with_settings(None, None, None, None) {
-                                        Ok(_) => {
-                                            println!("Debug: Audio connected with default devices");
-                                            self.audio_last_error = None;
-                                            audio_success = true;
-                                            break;
-                                        }
-                                        Err(e2) => {
-                                            println!("Default audio connection also failed: {}", e2);
-                                        }
-                                    }
-                                }
-                            }
-                        }
-                    }
-                    
-                    if audio_success {
-                        self.last_audio_device = settings.audio.input_device_name.clone();
-                        self.last_audio_rate = settings.audio.sample_rate;
-                        self.last_audio_channels = settings.audio.channels;
-                    } else {
-                        println!("Debug: All audio connection attempts failed");
-                    }
-                }
-                
-                // 音量とパススルー設定を適用
-                self.volume = settings.ui.volume;
-                audio.set_volume(self.volume);
-                audio.set_audio_passthrough_enabled(settings.audio.passthrough_enabled);
-            }
+            // 音量を適用
+            self.volume = settings.ui.volume;
+            self.devices.set_volume(self.volume);
+            self.last_volume_sent = self.volume;
             
             // UI設定
             self.maintain_aspect_ratio = settings.ui.maintain_aspect_ratio;
@@ -930,10 +1398,6 @@ impl CaptureCardViewer {
                 }
             }
         }
-        
-        if !initial { 
-            self.last_settings_applied = Instant::now(); 
-        }
     }
 
     fn update_cached_device_lists(&mut self) {
@@ -943,11 +1407,9 @@ impl CaptureCardViewer {
             .unwrap_or(true);
             
         if should_update {
-            if let Ok(audio) = self.audio_capture.lock() {
-                self.cached_input_devices = audio.list_input_devices();
-                self.cached_output_devices = audio.list_output_devices();
-                self.last_device_list_update = Some(Instant::now());
-            }
+            self.cached_input_devices = AudioCapture::list_input_devices();
+            self.cached_output_devices = AudioCapture::list_output_devices();
+            self.last_device_list_update = Some(Instant::now());
         }
     }
     
@@ -0,0 +1,136 @@
+use std::borrow::Cow;
+use crate::settings::{Rotation, VideoSettings};
+
+// 表示の回転・反転
+// フレーム自体は変換せず、テクスチャを貼る頂点の UV を入れ替えて表示する (GPU 側で処理されるので追加のコピーなし)
+// スクリーンショットは同じ変換を画像に対して行う
+// 変換の順序は「反転してから時計回りに回転」で、座標は 0~1 に正規化したもの
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Orientation {
+    pub rotation: Rotation,
+    pub flip_horizontal: bool,
+    pub flip_vertical: bool,
+}
+
+impl Orientation {
+    pub fn from_settings(video: &VideoSettings) -> Self {
+        Self { rotation: video.rotation, flip_horizontal: video.flip_horizontal, flip_vertical: video.flip_vertical }
+    }
+
+    pub fn is_identity(&self) -> bool {
+        *self == Self::default()
+    }
+
+    // 90度・270度の回転では縦横が入れ替わる
+    pub fn swaps_axes(&self) -> bool {
+        matches!(self.rotation, Rotation::Cw90 | Rotation::Cw270)
+    }
+
+    // 表示上の大きさ
+    pub fn oriented_size(&self, size: egui::Vec2) -> egui::Vec2 {
+        if self.swaps_axes() { egui::vec2(size.y, size.x) } else { size }
+    }
+
+    // 元の映像の点 -> 表示上の点
+    pub fn to_display(self, p: egui::Pos2) -> egui::Pos2 {
+        let x = if self.flip_horizontal { 1.0 - p.x } else { p.x };
+        let y = if self.flip_vertical { 1.0 - p.y } else { p.y };
+        match self.rotation {
+            Rotation::None => egui::pos2(x, y),
+            Rotation::Cw90 => egui::pos2(1.0 - y, x),
+            Rotation::Cw180 => egui::pos2(1.0 - x, 1.0 - y),
+            Rotation::Cw270 => egui::pos2(y, 1.0 - x),
+        }
+    }
+
+    // 表示上の点 -> 元の映像の点
+    pub fn to_source(self, p: egui::Pos2) -> egui::Pos2 {
+        let (x, y) = match self.rotation {
+            Rotation::None => (p.x, p.y),
+            Rotation::Cw90 => (p.y, 1.0 - p.x),
+            Rotation::Cw180 => (1.0 - p.x, 1.0 - p.y),
+            Rotation::Cw270 => (1.0 - p.y, p.x),
+        };
+        egui::pos2(
+            if self.flip_horizontal { 1.0 - x } else { x },
+            if self.flip_vertical { 1.0 - y } else { y },
+        )
+    }
+
+    // テクスチャの uv の範囲を回転・反転して rect に描く
+    pub fn paint(&self, painter: &egui::Painter, texture: egui::TextureId, rect: egui::Rect, uv: egui::Rect) {
+        let mut mesh = egui::Mesh::with_texture(texture);
+        for corner in [rect.left_top(), rect.right_top(), rect.right_bottom(), rect.left_bottom()] {
+            let source = self.to_source(((corner - rect.min) / rect.size()).to_pos2());
+            mesh.vertices.push(egui::epaint::Vertex {
+                pos: corner,
+                uv: uv.min + source.to_vec2() * uv.size(),
+                color: egui::Color32::WHITE,
+            });
+        }
+        mesh.add_triangle(0, 1, 2);
+        mesh.add_triangle(0, 2, 3);
+        painter.add(egui::Shape::mesh(mesh));
+    }
+
+    // width x height の RGBA 画像に同じ変換をかける (変換後の画像と幅・高さ)
+    pub fn apply_to_rgba<'a>(&self, pixels: Cow<'a, [u8]>, width: u32, height: u32) -> (Cow<'a, [u8]>, u32, u32) {
+        if self.is_identity() {
+            return (pixels, width, height);
+        }
+        let Some(mut image) = image::RgbaImage::from_raw(width, height, pixels.to_vec()) else {
+            println!("Warning: Image data size does not match {}x{}, orientation not applied", width, height);
+            return (pixels, width, height);
+        };
+        if self.flip_horizontal {
+            image::imageops::flip_horizontal_in_place(&mut image);
+        }
+        if self.flip_vertical {
+            image::imageops::flip_vertical_in_place(&mut image);
+        }
+        let image = match self.rotation {
+            Rotation::None => image,
+            Rotation::Cw90 => image::imageops::rotate90(&image),
+            Rotation::Cw180 => image::imageops::rotate180(&image),
+            Rotation::Cw270 => image::imageops::rotate270(&image),
+        };
+        let (width, height) = image.dimensions();
+        (Cow::Owned(image.into_raw()), width, height)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn check(orientation: Orientation) {
+        // 画素ごとに値の違う 3x2 の画像
+        let (width, height) = (3u32, 2u32);
+        let pixels: Vec<u8> = (0..width * height).flat_map(|i| [i as u8, 0, 0, 255]).collect();
+        let (out, out_width, out_height) = orientation.apply_to_rgba(Cow::Borrowed(&pixels), width, height);
+        let expected_size = orientation.oriented_size(egui::vec2(width as f32, height as f32));
+        assert_eq!(egui::vec2(out_width as f32, out_height as f32), expected_size, "{:?}", orientation);
+        for y in 0..height {
+            for x in 0..width {
+                let source = egui::pos2((x as f32 + 0.5) / width as f32, (y as f32 + 0.5) / height as f32);
+                let display = orientation.to_display(source);
+                let back = orientation.to_source(display);
+                assert!((back - source).length() <= 1e-5, "{:?}: to_source(to_display({:?})) gave {:?}", orientation, source, back);
+                let (dx, dy) = ((display.x * out_width as f32) as u32, (display.y * out_height as f32) as u32);
+                let value = out[((dy * out_width + dx) * 4) as usize];
+                assert_eq!(value as u32, y * width + x, "{:?}: pixel ({}, {}) is at ({}, {}) in the display but not in the image", orientation, x, y, dx, dy);
+            }
+        }
+    }
+
+    // すべての回転・反転の組み合わせで、表示 (UV) とスクリーンショット (画像の変換) が同じ向きになるか確認する
+    #[test]
+    fn display_matches_image() {
+        for rotation in [Rotation::None, Rotation::Cw90, Rotation::Cw180, Rotation::Cw270] {
+            for (flip_horizontal, flip_vertical) in [(false, false), (true, false), (false, true), (true, true)] {
+                check(Orientation { rotation, flip_horizontal, flip_vertical });
+            }
+        }
+    }
+}
@@ -0,0 +1,141 @@
+use egui::Color32;
+use rayon::prelude::*;
+use crate::settings::PictureAdjustments;
+
+// 画質調整 (明るさ・コントラスト・彩度・色相・ガンマ)
+// MJPEG・RGB 入力にも同じように効くよう、YUV 変換後の RGBA に対して行う
+// 明るさ〜色相は 3x3 行列 + オフセットにまとめて1回で計算し、ガンマは表引きで適用する
+
+// 固定小数点の桁 (1.0 = 1 << SHIFT)
+const SHIFT: i32 = 12;
+// 並列処理の単位 (行数)
+const ROWS_PER_TASK: usize = 16;
+
+// 調整に使う輝度・色差への分解 (BT.601 フルレンジ)
+const KR: f64 = 0.299;
+const KB: f64 = 0.114;
+
+type Matrix = [[f64; 3]; 3];
+
+fn multiply(a: Matrix, b: Matrix) -> Matrix {
+    let mut out = [[0.0; 3]; 3];
+    for (i, row) in out.iter_mut().enumerate() {
+        for (j, v) in row.iter_mut().enumerate() {
+            *v = (0..3).map(|k| a[i][k] * b[k][j]).sum();
+        }
+    }
+    out
+}
+
+// 調整値から計算した変換 (キャプチャスレッドで毎フレーム使う)
+#[derive(Debug, Clone, Copy)]
+pub struct PictureProcessor {
+    matrix: [[i32; 3]; 3],
+    offset: i32,
+    gamma: [u8; 256],
+    identity: bool,
+}
+
+impl PictureProcessor {
+    pub fn new(adjustments: &PictureAdjustments) -> Self {
+        let kg = 1.0 - KR - KB;
+        // RGB -> (Y, Cb, Cr)
+        let to_ycc = [
+            [KR, kg, KB],
+            [-KR / (2.0 * (1.0 - KB)), -kg / (2.0 * (1.0 - KB)), 0.5],
+            [0.5, -kg / (2.0 * (1.0 - KR)), -KB / (2.0 * (1.0 - KR))],
+        ];
+        // (Y, Cb, Cr) -> RGB
+        let to_rgb = [
+            [1.0, 0.0, 2.0 * (1.0 - KR)],
+            [1.0, -2.0 * (1.0 - KB) * KB / kg, -2.0 * (1.0 - KR) * KR / kg],
+            [1.0, 2.0 * (1.0 - KB), 0.0],
+        ];
+        let contrast = (adjustments.contrast as f64 / 100.0).max(0.0);
+        let saturation = (adjustments.saturation as f64 / 100.0).max(0.0);
+        let (sin, cos) = (adjustments.hue as f64).to_radians().sin_cos();
+        // 輝度はコントラスト倍、色差はコントラスト×彩度倍して色相の角度だけ回転
+        let chroma = contrast * saturation;
+        let adjust = [
+            [contrast, 0.0, 0.0],
+            [0.0, chroma * cos, -chroma * sin],
+            [0.0, chroma * sin, chroma * cos],
+        ];
+        let matrix = multiply(to_rgb, multiply(adjust, to_ycc));
+        // コントラストは中間の灰色を中心にかけ、明るさは全体に足す (±100 で ±半分)
+        let offset = (0.5 * (1.0 - contrast) + adjustments.brightness as f64 / 200.0) * 255.0;
+
+        let gamma = adjustments.gamma.clamp(0.1, 3.0) as f64;
+        let mut table = [0u8; 256];
+        for (i, v) in table.iter_mut().enumerate() {
+            *v = (255.0 * (i as f64 / 255.0).powf(1.0 / gamma)).round() as u8;
+        }
+
+        let fixed = |v: f64| (v * (1 << SHIFT) as f64).round() as i32;
+        Self {
+            matrix: matrix.map(|row| row.map(fixed)),
+            offset: fixed(offset),
+            gamma: table,
+            identity: *adjustments == PictureAdjustments::default(),
+        }
+    }
+
+    #[inline(always)]
+    fn apply_pixel(&self, p: Color32) -> Color32 {
+        let (r, g, b) = (p.r() as i32, p.g() as i32, p.b() as i32);
+        let channel = |m: &[i32; 3]| {
+            let v = (m[0] * r + m[1] * g + m[2] * b + self.offset + (1 << (SHIFT - 1))) >> SHIFT;
+            self.gamma[v.clamp(0, 255) as usize]
+        };
+        Color32::from_rgb(channel(&self.matrix[0]), channel(&self.matrix[1]), channel(&self.matrix[2]))
+    }
+
+    // フレーム全体に適用 (無調整なら何もしない)
+    pub fn apply(&self, pixels: &mut [Color32], width: usize) {
+        if self.identity || width == 0 {
+            return;
+        }
+        pixels.par_chunks_mut(width * ROWS_PER_TASK).for_each(|rows| {
+            for p in rows {
+                *p = self.apply_pixel(*p);
+            }
+        });
+    }
+}
+
+impl Default for PictureProcessor {
+    fn default() -> Self {
+        Self::new(&PictureAdjustments::default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 代表的な色が調整後に期待どおりの色になるか確認する
+    #[test]
+    fn adjusts_reference_colors() {
+        let neutral = PictureAdjustments::default();
+        let cases = [
+            ("identity", neutral, Color32::from_rgb(12, 200, 77), Color32::from_rgb(12, 200, 77)),
+            ("brightness", PictureAdjustments { brightness: 100.0, ..neutral }, Color32::BLACK, Color32::from_gray(128)),
+            ("brightness", PictureAdjustments { brightness: -50.0, ..neutral }, Color32::WHITE, Color32::from_gray(191)),
+            ("contrast", PictureAdjustments { contrast: 0.0, ..neutral }, Color32::from_rgb(255, 0, 40), Color32::from_gray(128)),
+            ("contrast", PictureAdjustments { contrast: 200.0, ..neutral }, Color32::from_gray(96), Color32::from_gray(64)),
+            ("saturation", PictureAdjustments { saturation: 0.0, ..neutral }, Color32::from_rgb(255, 0, 0), Color32::from_gray(76)),
+            ("hue", PictureAdjustments { hue: 180.0, ..neutral }, Color32::from_rgb(255, 0, 0), Color32::from_rgb(0, 152, 152)),
+            ("gamma", PictureAdjustments { gamma: 2.0, ..neutral }, Color32::from_gray(64), Color32::from_gray(128)),
+        ];
+        for (name, adjustments, input, expected) in cases {
+            let mut pixels = [input; 4];
+            PictureProcessor::new(&adjustments).apply(&mut pixels, 2);
+            let actual = pixels[0];
+            // 固定小数点の丸め誤差分は許容
+            let close = [(actual.r(), expected.r()), (actual.g(), expected.g()), (actual.b(), expected.b())]
+                .iter()
+                .all(|(a, e)| a.abs_diff(*e) <= 1);
+            assert!(close, "{}: {:?} became {:?}, expected {:?}", name, input, actual, expected);
+        }
+    }
+}
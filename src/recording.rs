@@ -0,0 +1,568 @@
+use rayon::prelude::*;
+use std::borrow::Cow;
+use std::fs::File;
+use std::io::{self, BufWriter, Seek, SeekFrom, Write};
+use std::path::PathBuf;
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender, TryRecvError};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+use crate::audio::{AudioTap, SampleConsumer};
+use crate::letterbox::BarInsets;
+use crate::orientation::Orientation;
+use crate::settings::CropSettings;
+use crate::video::{FrameReader, VideoFrame};
+
+// 録画 (MJPEG 映像 + 16bit PCM 音声の AVI、外部のコーデックは使わない)
+// キャプチャスレッドから届いたフレームを録画スレッドで JPEG にして書き込む
+// 映像は固定フレームレートで、到着時刻に合わせて空のチャンク (直前のフレームの繰り返し) で埋めたり間引いたりして音声とずれないようにする
+
+// 録画スレッドに溜められるフレーム数 (溢れた分は捨てて、繰り返しで埋める)
+const FRAME_QUEUE: usize = 8;
+// まとめて並列に JPEG にするフレーム数
+const MAX_BATCH: usize = 4;
+// 録画用の音声のリングバッファの長さ (秒)
+const AUDIO_BUFFER_SECONDS: u32 = 2;
+const POLL_INTERVAL: Duration = Duration::from_millis(20);
+// AVI (RIFF) の大きさは 32bit なので、上限の手前で録画を止める
+const MAX_FILE_SIZE: u64 = 0xF000_0000;
+
+const AVIF_HASINDEX: u32 = 0x10;
+const AVIF_ISINTERLEAVED: u32 = 0x100;
+const AVIIF_KEYFRAME: u32 = 0x10;
+const VIDEO_CHUNK: [u8; 4] = *b"00dc";
+const AUDIO_CHUNK: [u8; 4] = *b"01wb";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AudioFormat {
+    pub sample_rate: u32,
+    pub channels: u16,
+}
+
+impl AudioFormat {
+    // 1サンプル (全チャンネル) のバイト数
+    fn block_align(&self) -> u32 {
+        self.channels as u32 * 2
+    }
+}
+
+struct IndexEntry {
+    id: [u8; 4],
+    flags: u32,
+    offset: u32,
+    size: u32,
+}
+
+// リトルエンディアンの値を順に並べる
+#[derive(Default)]
+struct Fields(Vec<u8>);
+
+impl Fields {
+    fn u32(mut self, v: u32) -> Self {
+        self.0.extend_from_slice(&v.to_le_bytes());
+        self
+    }
+    fn u16(mut self, v: u16) -> Self {
+        self.0.extend_from_slice(&v.to_le_bytes());
+        self
+    }
+    fn fourcc(mut self, v: &[u8; 4]) -> Self {
+        self.0.extend_from_slice(v);
+        self
+    }
+}
+
+fn chunk(id: &[u8; 4], data: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(data.len() + 9);
+    out.extend_from_slice(id);
+    out.extend_from_slice(&(data.len() as u32).to_le_bytes());
+    out.extend_from_slice(data);
+    if data.len() % 2 == 1 {
+        out.push(0);
+    }
+    out
+}
+
+fn list(kind: &[u8; 4], data: &[u8]) -> Vec<u8> {
+    let mut content = kind.to_vec();
+    content.extend_from_slice(data);
+    chunk(b"LIST", &content)
+}
+
+// AVI ファイルの書き出し
+// ヘッダは固定長なので、最後にフレーム数などを埋めて先頭に書き直す
+pub struct AviWriter<W: Write + Seek> {
+    out: W,
+    width: u32,
+    height: u32,
+    fps: u32,
+    audio: Option<AudioFormat>,
+    // "movi" の位置 (索引のオフセットの基準) と現在の書き込み位置
+    movi: u64,
+    position: u64,
+    index: Vec<IndexEntry>,
+    video_frames: u32,
+    audio_bytes: u64,
+    max_chunk: u32,
+}
+
+impl<W: Write + Seek> AviWriter<W> {
+    pub fn new(out: W, width: u32, height: u32, fps: u32, audio: Option<AudioFormat>) -> io::Result<Self> {
+        let mut writer = Self {
+            out,
+            width,
+            height,
+            fps: fps.max(1),
+            audio,
+            movi: 0,
+            position: 0,
+            index: Vec::new(),
+            video_frames: 0,
+            audio_bytes: 0,
+            max_chunk: 0,
+        };
+        let header = writer.header(0, 0);
+        writer.out.write_all(&header)?;
+        writer.position = header.len() as u64;
+        writer.movi = writer.position - 4;
+        Ok(writer)
+    }
+
+    // ファイルの先頭から "movi" までのヘッダ
+    fn header(&self, riff_size: u32, movi_size: u32) -> Vec<u8> {
+        let streams = if self.audio.is_some() { 2 } else { 1 };
+        let avih = Fields::default()
+            .u32(1_000_000 / self.fps)
+            .u32(0)
+            .u32(0)
+            .u32(AVIF_HASINDEX | AVIF_ISINTERLEAVED)
+            .u32(self.video_frames)
+            .u32(0)
+            .u32(streams)
+            .u32(self.max_chunk)
+            .u32(self.width)
+            .u32(self.height)
+            .u32(0).u32(0).u32(0).u32(0);
+        let video_strh = Fields::default()
+            .fourcc(b"vids")
+            .fourcc(b"MJPG")
+            .u32(0)
+            .u16(0)
+            .u16(0)
+            .u32(0)
+            .u32(1)
+            .u32(self.fps)
+            .u32(0)
+            .u32(self.video_frames)
+            .u32(self.max_chunk)
+            .u32(u32::MAX)
+            .u32(0)
+            .u16(0).u16(0).u16(self.width as u16).u16(self.height as u16);
+        // BITMAPINFOHEADER
+        let video_strf = Fields::default()
+            .u32(40)
+            .u32(self.width)
+            .u32(self.height)
+            .u16(1)
+            .u16(24)
+            .fourcc(b"MJPG")
+            .u32(self.width * self.height * 3)
+            .u32(0).u32(0).u32(0).u32(0);
+        let mut strl = chunk(b"strh", &video_strh.0);
+        strl.extend(chunk(b"strf", &video_strf.0));
+        let mut hdrl = chunk(b"avih", &avih.0);
+        hdrl.extend(list(b"strl", &strl));
+        if let Some(audio) = self.audio {
+            let block_align = audio.block_align();
+            let audio_strh = Fields::default()
+                .fourcc(b"auds")
+                .u32(0)
+                .u32(0)
+                .u16(0)
+                .u16(0)
+                .u32(0)
+                .u32(block_align)
+                .u32(audio.sample_rate * block_align)
+                .u32(0)
+                .u32((self.audio_bytes / block_align as u64) as u32)
+                .u32(self.max_chunk)
+                .u32(u32::MAX)
+                .u32(block_align)
+                .u16(0).u16(0).u16(0).u16(0);
+            // WAVEFORMATEX (16bit PCM)
+            let audio_strf = Fields::default()
+                .u16(1)
+                .u16(audio.channels)
+                .u32(audio.sample_rate)
+                .u32(audio.sample_rate * block_align)
+                .u16(block_align as u16)
+                .u16(16)
+                .u16(0);
+            let mut strl = chunk(b"strh", &audio_strh.0);
+            strl.extend(chunk(b"strf", &audio_strf.0));
+            hdrl.extend(list(b"strl", &strl));
+        }
+        let mut out = Fields::default().fourcc(b"RIFF").u32(riff_size).fourcc(b"AVI ").0;
+        out.extend(list(b"hdrl", &hdrl));
+        out.extend(Fields::default().fourcc(b"LIST").u32(movi_size).fourcc(b"movi").0);
+        out
+    }
+
+    fn write_chunk(&mut self, id: [u8; 4], data: &[u8], flags: u32) -> io::Result<()> {
+        let bytes = chunk(&id, data);
+        self.out.write_all(&bytes)?;
+        self.index.push(IndexEntry { id, flags, offset: (self.position - self.movi) as u32, size: data.len() as u32 });
+        self.position += bytes.len() as u64;
+        self.max_chunk = self.max_chunk.max(data.len() as u32);
+        Ok(())
+    }
+
+    pub fn write_video(&mut self, jpeg: &[u8]) -> io::Result<()> {
+        self.write_chunk(VIDEO_CHUNK, jpeg, AVIIF_KEYFRAME)?;
+        self.video_frames += 1;
+        Ok(())
+    }
+
+    // 直前のフレームをもう1フレーム表示する (中身のないチャンク)
+    pub fn repeat_video(&mut self) -> io::Result<()> {
+        self.write_chunk(VIDEO_CHUNK, &[], 0)?;
+        self.video_frames += 1;
+        Ok(())
+    }
+
+    // インターリーブ済みの 16bit PCM
+    pub fn write_audio(&mut self, samples: &[i16]) -> io::Result<()> {
+        if self.audio.is_none() || samples.is_empty() {
+            return Ok(());
+        }
+        let bytes: Vec<u8> = samples.iter().flat_map(|s| s.to_le_bytes()).collect();
+        self.write_chunk(AUDIO_CHUNK, &bytes, AVIIF_KEYFRAME)?;
+        self.audio_bytes += bytes.len() as u64;
+        Ok(())
+    }
+
+    pub fn file_size(&self) -> u64 {
+        self.position
+    }
+
+    pub fn video_frames(&self) -> u32 {
+        self.video_frames
+    }
+
+    // 索引を書いてヘッダを埋める
+    pub fn finish(mut self) -> io::Result<W> {
+        let movi_end = self.position;
+        let mut idx1 = Fields::default();
+        for entry in &self.index {
+            idx1 = idx1.fourcc(&entry.id).u32(entry.flags).u32(entry.offset).u32(entry.size);
+        }
+        let idx1 = chunk(b"idx1", &idx1.0);
+        self.out.write_all(&idx1)?;
+        let total = movi_end + idx1.len() as u64;
+        let header = self.header((total - 8) as u32, (movi_end - self.movi) as u32);
+        self.out.seek(SeekFrom::Start(0))?;
+        self.out.write_all(&header)?;
+        self.out.flush()?;
+        Ok(self.out)
+    }
+}
+
+// 録画の設定 (開始時の状態で固定)
+#[derive(Debug, Clone, Copy)]
+pub struct RecordOptions {
+    pub fps: u32,
+    pub quality: u8,
+    pub crop: CropSettings,
+    pub bars: BarInsets,
+    pub orientation: Orientation,
+}
+
+// 録画結果
+#[derive(Debug, Clone)]
+pub struct RecordingSummary {
+    pub path: PathBuf,
+    pub frames: u32,
+    // 間に合わなかった・届かなかったために繰り返したフレーム数
+    pub repeated: u32,
+    pub duration: Duration,
+}
+
+// 録画中のファイル (最初のフレームが届いた時点で作る)
+struct Session {
+    writer: AviWriter<BufWriter<File>>,
+    size: (u32, u32),
+    fps: u32,
+    // 最初のフレームの到着時刻 (映像・音声の時刻の基準)
+    start: Instant,
+    repeated: u32,
+    audio: Option<(SampleConsumer, AudioFormat)>,
+}
+
+impl Session {
+    // 到着時刻に当たる位置に書く (遅れていれば繰り返しで埋め、進みすぎていれば捨てる)
+    fn write_frame(&mut self, arrived: Instant, jpeg: &[u8]) -> io::Result<()> {
+        let slot = (arrived.saturating_duration_since(self.start).as_secs_f64() * self.fps as f64) as u32;
+        if slot < self.writer.video_frames() {
+            return Ok(());
+        }
+        self.fill_until(slot)?;
+        self.writer.write_video(jpeg)
+    }
+
+    fn fill_until(&mut self, frames: u32) -> io::Result<()> {
+        while self.writer.video_frames() < frames {
+            self.writer.repeat_video()?;
+            self.repeated += 1;
+        }
+        Ok(())
+    }
+
+    fn write_audio(&mut self) -> io::Result<()> {
+        let Some((consumer, format)) = &mut self.audio else {
+            return Ok(());
+        };
+        let channels = format.channels as usize;
+        let available = consumer.len() - consumer.len() % channels;
+        let samples: Vec<i16> = consumer.pop_iter()
+            .take(available)
+            .map(|s| (s.clamp(-1.0, 1.0) * i16::MAX as f32) as i16)
+            .collect();
+        self.writer.write_audio(&samples)
+    }
+
+    fn finish(mut self, path: PathBuf, stopped: Instant) -> io::Result<RecordingSummary> {
+        let end = (stopped.saturating_duration_since(self.start).as_secs_f64() * self.fps as f64) as u32;
+        self.fill_until(end)?;
+        self.write_audio()?;
+        let frames = self.writer.video_frames();
+        self.writer.finish()?;
+        Ok(RecordingSummary { path, frames, repeated: self.repeated, duration: Duration::from_secs_f64(frames as f64 / self.fps as f64) })
+    }
+}
+
+// 表示と同じ範囲・向きにしたフレームを JPEG にする (size を指定すればその大きさに合わせる)
+fn encode_frame(frame: &VideoFrame, options: &RecordOptions, size: Option<(u32, u32)>) -> Result<(Vec<u8>, u32, u32), String> {
+    let (width, height) = (frame.width as u32, frame.height as u32);
+    let rect = options.bars.crop_rect(options.crop.rect(width, height), width, height);
+    let (pixels, mut width, mut height) = options.orientation.apply_to_rgba(frame.cropped_rgba(rect), rect.2, rect.3);
+    let mut pixels = pixels;
+    if let Some((w, h)) = size.filter(|&s| s != (width, height)) {
+        let image = image::RgbaImage::from_raw(width, height, pixels.into_owned()).ok_or("frame data size does not match")?;
+        pixels = Cow::Owned(image::imageops::resize(&image, w, h, image::imageops::FilterType::Triangle).into_raw());
+        (width, height) = (w, h);
+    }
+    // エンコーダは長さが合わないと panic するので先に確かめる
+    if pixels.len() != width as usize * height as usize * 4 {
+        return Err("frame data size does not match".to_string());
+    }
+    let mut jpeg = Vec::new();
+    image::codecs::jpeg::JpegEncoder::new_with_quality(&mut jpeg, options.quality.clamp(1, 100))
+        .encode(&pixels, width, height, image::ColorType::Rgba8)
+        .map_err(|e| format!("Failed to encode frame: {}", e))?;
+    Ok((jpeg, width, height))
+}
+
+// 録画スレッド本体
+fn record(
+    file: File,
+    path: PathBuf,
+    frames: Receiver<(Instant, VideoFrame)>,
+    stop: Receiver<Instant>,
+    mut audio: Option<(SampleConsumer, AudioFormat)>,
+    options: RecordOptions,
+) -> Result<RecordingSummary, String> {
+    let io_error = |e: io::Error| format!("Failed to write {}: {}", path.display(), e);
+    let mut file = Some(file);
+    let mut session: Option<Session> = None;
+    let mut stopped: Option<Instant> = None;
+    let mut skipped = 0u32;
+    loop {
+        if stopped.is_none() {
+            match stop.try_recv() {
+                Ok(at) => stopped = Some(at),
+                Err(TryRecvError::Disconnected) => stopped = Some(Instant::now()),
+                Err(TryRecvError::Empty) => {}
+            }
+        }
+        // 停止後は溜まっているフレームだけ書く
+        let batch: Vec<(Instant, VideoFrame)> = match stopped {
+            Some(at) => frames.try_iter().take(MAX_BATCH).filter(|(t, _)| *t <= at).collect(),
+            None => match frames.recv_timeout(POLL_INTERVAL) {
+                Ok(first) => std::iter::once(first).chain(frames.try_iter().take(MAX_BATCH - 1)).collect(),
+                Err(RecvTimeoutError::Timeout) => Vec::new(),
+                Err(RecvTimeoutError::Disconnected) => {
+                    stopped = Some(Instant::now());
+                    Vec::new()
+                }
+            },
+        };
+        let size = session.as_ref().map(|s| s.size);
+        let encoded: Vec<_> = batch.par_iter()
+            .map(|(arrived, frame)| encode_frame(frame, &options, size).map(|encoded| (*arrived, encoded)))
+            .collect();
+        for result in encoded {
+            // 1枚の失敗で録画全体を止めず、そのフレームだけ飛ばす (空いた位置は繰り返しで埋まる)
+            let (arrived, (jpeg, width, height)) = match result {
+                Ok(encoded) => encoded,
+                Err(e) => {
+                    if skipped == 0 {
+                        println!("Warning: Recording skipped a frame: {}", e);
+                    }
+                    skipped += 1;
+                    continue;
+                }
+            };
+            if session.is_none() {
+                let Some(file) = file.take() else { break };
+                // 最初のフレームより前の音声は捨てて、映像と頭を揃える
+                if let Some((consumer, _)) = &mut audio {
+                    consumer.clear();
+                }
+                let format = audio.as_ref().map(|(_, format)| *format);
+                let writer = AviWriter::new(BufWriter::new(file), width, height, options.fps, format).map_err(io_error)?;
+                println!("Debug: Recording {}x{} at {} fps{}", width, height, options.fps, if format.is_some() { " with audio" } else { "" });
+                session = Some(Session { writer, size: (width, height), fps: options.fps.max(1), start: arrived, repeated: 0, audio: audio.take() });
+            }
+            if let Some(session) = &mut session {
+                if (width, height) == session.size {
+                    session.write_frame(arrived, &jpeg).map_err(io_error)?;
+                }
+            }
+        }
+        if let Some(session) = &mut session {
+            session.write_audio().map_err(io_error)?;
+            if stopped.is_none() && session.writer.file_size() > MAX_FILE_SIZE {
+                println!("Warning: Recording reached the AVI size limit, stopping");
+                stopped = Some(Instant::now());
+            }
+        }
+        if stopped.is_some() && batch.is_empty() {
+            break;
+        }
+    }
+    let stopped = stopped.unwrap_or_else(Instant::now);
+    if skipped > 0 {
+        println!("Warning: Recording skipped {} frames that failed to encode", skipped);
+    }
+    match session {
+        Some(session) => session.finish(path.clone(), stopped).map_err(io_error),
+        None => {
+            drop(file);
+            let _ = std::fs::remove_file(&path);
+            Err("No video frames were received".to_string())
+        }
+    }
+}
+
+// 録画中の状態 (UI スレッド側)
+pub struct Recorder {
+    started: Instant,
+    frames: FrameReader,
+    audio: Option<AudioTap>,
+    stop: Sender<Instant>,
+    thread: Option<JoinHandle<Result<RecordingSummary, String>>>,
+}
+
+impl Recorder {
+    // frames に届くフレームと audio の入力音声 (None なら映像のみ) の録画を始める
+    pub fn start(path: PathBuf, frames: &FrameReader, audio: Option<&AudioTap>, options: RecordOptions) -> Result<Self, String> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent).map_err(|e| format!("Failed to create {}: {}", parent.display(), e))?;
+        }
+        let file = File::create(&path).map_err(|e| format!("Failed to create {}: {}", path.display(), e))?;
+        let audio = audio.and_then(|tap| {
+            let (sample_rate, channels) = tap.format()?;
+            Some((tap.clone(), tap.open(AUDIO_BUFFER_SECONDS)?, AudioFormat { sample_rate, channels }))
+        });
+        let (tap, consumer) = match audio {
+            Some((tap, consumer, format)) => (Some(tap), Some((consumer, format))),
+            None => (None, None),
+        };
+        let (frame_sender, frame_receiver) = mpsc::sync_channel(FRAME_QUEUE);
+        let (stop, stop_receiver) = mpsc::channel();
+        let thread_path = path.clone();
+        let thread = std::thread::Builder::new()
+            .name("recorder".to_string())
+            .spawn(move || record(file, thread_path, frame_receiver, stop_receiver, consumer, options))
+            .map_err(|e| format!("Failed to start recording thread: {}", e))?;
+        frames.set_recording_tap(Some(frame_sender));
+        println!("Debug: Recording started: {}", path.display());
+        Ok(Self { started: Instant::now(), frames: frames.clone(), audio: tap, stop, thread: Some(thread) })
+    }
+
+    pub fn elapsed(&self) -> Duration {
+        self.started.elapsed()
+    }
+
+    // 録画スレッドが自分で終了したか (書き込みエラー・大きさの上限)
+    pub fn is_finished(&self) -> bool {
+        self.thread.as_ref().is_none_or(|thread| thread.is_finished())
+    }
+
+    pub fn stop(mut self) -> Result<RecordingSummary, String> {
+        self.finish()
+    }
+
+    fn finish(&mut self) -> Result<RecordingSummary, String> {
+        let stopped = Instant::now();
+        self.frames.set_recording_tap(None);
+        if let Some(tap) = &self.audio {
+            tap.close();
+        }
+        let _ = self.stop.send(stopped);
+        let thread = self.thread.take().ok_or("Recording already stopped")?;
+        let result = thread.join().map_err(|_| "Recording thread panicked".to_string())?;
+        match &result {
+            Ok(summary) => println!("Debug: Recording saved: {} ({} frames, {} repeated, {:.1}s)",
+                summary.path.display(), summary.frames, summary.repeated, summary.duration.as_secs_f32()),
+            Err(e) => println!("Warning: Recording failed: {}", e),
+        }
+        result
+    }
+}
+
+impl Drop for Recorder {
+    fn drop(&mut self) {
+        if self.thread.is_some() {
+            let _ = self.finish();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    fn gray_frame(width: usize, height: usize, generation: u64) -> VideoFrame {
+        let image = egui::ColorImage { size: [width, height], pixels: vec![egui::Color32::from_gray(128); width * height] };
+        VideoFrame { width, height, image: Arc::new(image), ungraded: None, generation }
+    }
+
+    // JPEG にできないフレームが混ざっても録画は続き、索引付きで閉じられる
+    #[test]
+    fn skips_frames_that_fail_to_encode() {
+        let path = std::env::temp_dir().join(format!("capturecard_viewer_skip_{}.avi", std::process::id()));
+        let file = File::create(&path).unwrap();
+        let (frame_sender, frame_receiver) = mpsc::sync_channel(FRAME_QUEUE);
+        let (stop, stop_receiver) = mpsc::channel();
+        let options = RecordOptions { fps: 30, quality: 80, crop: CropSettings::default(), bars: BarInsets::default(), orientation: Orientation::default() };
+
+        let start = Instant::now();
+        let at = |frame: u32| start + Duration::from_secs(frame as u64) / 30;
+        frame_sender.send((at(0), gray_frame(16, 16, 1))).unwrap();
+        // 報告サイズと画素数が合わないフレーム
+        let mut broken = gray_frame(16, 16, 2);
+        (broken.width, broken.height) = (32, 32);
+        frame_sender.send((at(1), broken)).unwrap();
+        frame_sender.send((at(2), gray_frame(16, 16, 3))).unwrap();
+        stop.send(at(3)).unwrap();
+
+        let summary = record(file, path.clone(), frame_receiver, stop_receiver, None, options);
+        let bytes = std::fs::read(&path).unwrap();
+        let _ = std::fs::remove_file(&path);
+        let summary = summary.unwrap();
+        assert_eq!(summary.frames, 3);
+        assert_eq!(summary.repeated, 1);
+        assert!(bytes.windows(4).any(|w| w == b"idx1"), "index was not written");
+    }
+}
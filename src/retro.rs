@@ -0,0 +1,507 @@
+use egui::{Color32, ColorImage};
+use rayon::prelude::*;
+use std::sync::mpsc::{self, SyncSender, TrySendError};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use crate::scaling;
+use crate::settings::{CrtEffect, RetroSettings, Upscaler};
+use crate::video::VideoFrame;
+
+// レトロゲーム機向けの後処理 (ドット絵の拡大フィルタ + CRT 風の効果)
+// YUV 変換・画質調整済みのフレームに対してワーカースレッドで行い、キャプチャ・UI スレッドを待たせない
+// 処理が追いつかない場合は途中のフレームを飛ばす
+
+// CRT 効果をかける前に拡大する目安の高さ (低解像度の映像でも走査線・マスクが見える大きさにする)
+const CRT_TARGET_HEIGHT: usize = 1080;
+const MAX_CRT_FACTOR: usize = 4;
+// 拡大フィルタの出力の最大サイズ (これを超える入力には拡大フィルタをかけない)
+const MAX_OUTPUT_SIZE: usize = 4096;
+// 事前拡大の出力バッファの最大保持数 (映像と補正前の映像)
+const MAX_SPARE_BUFFERS: usize = 2;
+
+// Scale2x (EPX): 上下左右の画素が揃っている角だけ隣の色にして、斜めの線を滑らかにする
+pub fn scale2x(src: &ColorImage) -> ColorImage {
+    let [width, height] = src.size;
+    if width == 0 || height == 0 {
+        return ColorImage { size: [width * 2, height * 2], pixels: Vec::new() };
+    }
+    let px = |x: usize, y: usize| src.pixels[y * width + x];
+    let mut pixels = vec![Color32::BLACK; width * height * 4];
+    pixels.par_chunks_mut(width * 4).enumerate().for_each(|(y, rows)| {
+        let (top, bottom) = rows.split_at_mut(width * 2);
+        let (up, down) = (y.saturating_sub(1), (y + 1).min(height - 1));
+        for x in 0..width {
+            let (left, right) = (x.saturating_sub(1), (x + 1).min(width - 1));
+            let (b, d, e, f, h) = (px(x, up), px(left, y), px(x, y), px(right, y), px(x, down));
+            top[2 * x] = if d == b && b != f && d != h { d } else { e };
+            top[2 * x + 1] = if b == f && b != d && f != h { f } else { e };
+            bottom[2 * x] = if d == h && d != b && h != f { d } else { e };
+            bottom[2 * x + 1] = if h == f && d != h && b != f { f } else { e };
+        }
+    });
+    ColorImage { size: [width * 2, height * 2], pixels }
+}
+
+// Scale3x (AdvMAME3x): Scale2x の規則を 3x3 に広げたもの
+pub fn scale3x(src: &ColorImage) -> ColorImage {
+    let [width, height] = src.size;
+    if width == 0 || height == 0 {
+        return ColorImage { size: [width * 3, height * 3], pixels: Vec::new() };
+    }
+    let px = |x: usize, y: usize| src.pixels[y * width + x];
+    let mut pixels = vec![Color32::BLACK; width * height * 9];
+    pixels.par_chunks_mut(width * 9).enumerate().for_each(|(y, rows)| {
+        let (up, down) = (y.saturating_sub(1), (y + 1).min(height - 1));
+        let mut out = [Color32::BLACK; 9];
+        for x in 0..width {
+            let (left, right) = (x.saturating_sub(1), (x + 1).min(width - 1));
+            let (a, b, c) = (px(left, up), px(x, up), px(right, up));
+            let (d, e, f) = (px(left, y), px(x, y), px(right, y));
+            let (g, h, i) = (px(left, down), px(x, down), px(right, down));
+            out.fill(e);
+            if b != h && d != f {
+                if d == b { out[0] = d; }
+                if (d == b && e != c) || (b == f && e != a) { out[1] = b; }
+                if b == f { out[2] = f; }
+                if (d == b && e != g) || (d == h && e != a) { out[3] = d; }
+                if (b == f && e != i) || (h == f && e != c) { out[5] = f; }
+                if d == h { out[6] = d; }
+                if (d == h && e != i) || (h == f && e != g) { out[7] = h; }
+                if h == f { out[8] = f; }
+            }
+            for (row, chunk) in out.chunks_exact(3).enumerate() {
+                rows[row * width * 3 + x * 3..][..3].copy_from_slice(chunk);
+            }
+        }
+    });
+    ColorImage { size: [width * 3, height * 3], pixels }
+}
+
+// xBR の色差 (輝度を重く見る YUV の重み付き距離) と、同じ色とみなす範囲
+fn to_yuv(p: Color32) -> [i32; 3] {
+    let (r, g, b) = (p.r() as i32, p.g() as i32, p.b() as i32);
+    [
+        (299 * r + 587 * g + 114 * b) / 1000,
+        (-169 * r - 331 * g + 500 * b) / 1000,
+        (500 * r - 419 * g - 81 * b) / 1000,
+    ]
+}
+
+fn yuv_distance(a: [i32; 3], b: [i32; 3]) -> i32 {
+    48 * (a[0] - b[0]).abs() + 7 * (a[1] - b[1]).abs() + 6 * (a[2] - b[2]).abs()
+}
+
+fn yuv_equal(a: [i32; 3], b: [i32; 3]) -> bool {
+    (a[0] - b[0]).abs() <= 48 && (a[1] - b[1]).abs() <= 7 && (a[2] - b[2]).abs() <= 6
+}
+
+// dst を src に alpha / 256 だけ近づける
+fn blend(dst: Color32, src: Color32, alpha: i32) -> Color32 {
+    let mix = |d: u8, s: u8| (d as i32 + (s as i32 - d as i32) * alpha / 256) as u8;
+    Color32::from_rgb(mix(dst.r(), src.r()), mix(dst.g(), src.g()), mix(dst.b(), src.b()))
+}
+
+// xBR 2x (Hyllian): 周囲の色差から角を横切る辺の向きを判定し、辺の角度に応じた割合で隣の色と混ぜる
+// 右下の角の規則を 90° ずつ回して 4 つの角に使う
+//
+//     A  B  C
+//     D  E  F  F4
+//     G  H  I  I4
+//        H5 I5
+pub fn xbr2x(src: &ColorImage) -> ColorImage {
+    let [width, height] = src.size;
+    if width == 0 || height == 0 {
+        return ColorImage { size: [width * 2, height * 2], pixels: Vec::new() };
+    }
+    let yuv: Vec<[i32; 3]> = src.pixels.par_iter().map(|&p| to_yuv(p)).collect();
+    let mut pixels = vec![Color32::BLACK; width * height * 4];
+    pixels.par_chunks_mut(width * 4).enumerate().for_each(|(y, rows)| {
+        for x in 0..width {
+            let e = src.pixels[y * width + x];
+            // 出力の 2x2 (左上, 右上, 左下, 右下)
+            let mut out = [e; 4];
+            // (dx, dy) を 90° ずつ回す: 右下 -> 右上 -> 左上 -> 左下
+            for rotation in 0..4 {
+                let turn = |(dx, dy): (isize, isize)| (0..rotation).fold((dx, dy), |(dx, dy), _| (dy, -dx));
+                // 回した位置の画素の番号 (端は繰り返し)
+                let at = |offset: (isize, isize)| {
+                    let (dx, dy) = turn(offset);
+                    let sx = (x as isize + dx).clamp(0, width as isize - 1) as usize;
+                    let sy = (y as isize + dy).clamp(0, height as isize - 1) as usize;
+                    sy * width + sx
+                };
+                let corner = |offset: (isize, isize)| {
+                    let (dx, dy) = turn(offset);
+                    (dy > 0) as usize * 2 + (dx > 0) as usize
+                };
+                let [pb, pc, pd, pe, pf, pg, ph, pi] = [(0, -1), (1, -1), (-1, 0), (0, 0), (1, 0), (-1, 1), (0, 1), (1, 1)].map(at);
+                let [i4, i5, f4, h5] = [(2, 1), (1, 2), (2, 0), (0, 2)].map(at);
+                let color = |i: usize| src.pixels[i];
+                if color(pe) == color(ph) || color(pe) == color(pf) {
+                    continue;
+                }
+                let d = |a: usize, b: usize| yuv_distance(yuv[a], yuv[b]);
+                let eq = |a: usize, b: usize| yuv_equal(yuv[a], yuv[b]);
+                // E-I 方向 (角を横切る辺) と H-F 方向の色の変化の大きさ
+                let across = d(pe, pc) + d(pe, pg) + d(pi, h5) + d(pi, f4) + 4 * d(ph, pf);
+                let along = d(ph, pd) + d(ph, i5) + d(pf, i4) + d(pf, pb) + 4 * d(pe, pi);
+                if across > along {
+                    continue;
+                }
+                let px = if d(pe, pf) <= d(pe, ph) { color(pf) } else { color(ph) };
+                let (n1, n2, n3) = (corner((1, -1)), corner((-1, 1)), corner((1, 1)));
+                let edge = across < along
+                    && ((!eq(pf, pb) && !eq(ph, pd)) || (eq(pe, pi) && !eq(pf, i4) && !eq(ph, i5)) || eq(pe, pg) || eq(pe, pc));
+                if !edge {
+                    out[n3] = blend(out[n3], px, 128);
+                    continue;
+                }
+                // 辺が緩やかなら隣の角にも少し混ぜる
+                let (ke, ki) = (d(pf, pg), d(ph, pc));
+                let left = 2 * ke <= ki && color(pe) != color(pg) && color(pd) != color(pg);
+                let up = ke >= 2 * ki && color(pe) != color(pc) && color(pb) != color(pc);
+                match (left, up) {
+                    (true, true) => {
+                        out[n3] = blend(out[n3], px, 224);
+                        out[n2] = blend(out[n2], px, 64);
+                        out[n1] = out[n2];
+                    }
+                    (true, false) => {
+                        out[n3] = blend(out[n3], px, 192);
+                        out[n2] = blend(out[n2], px, 64);
+                    }
+                    (false, true) => {
+                        out[n3] = blend(out[n3], px, 192);
+                        out[n1] = blend(out[n1], px, 64);
+                    }
+                    (false, false) => out[n3] = blend(out[n3], px, 128),
+                }
+            }
+            rows[2 * x..][..2].copy_from_slice(&out[..2]);
+            rows[width * 2 + 2 * x..][..2].copy_from_slice(&out[2..]);
+        }
+    });
+    ColorImage { size: [width * 2, height * 2], pixels }
+}
+
+fn dim(value: u8, keep: f32) -> u8 {
+    (value as f32 * keep).round() as u8
+}
+
+// 高さ height の映像に CRT 効果をかける時の拡大倍率
+fn crt_factor(height: usize) -> usize {
+    (CRT_TARGET_HEIGHT / height.max(1)).clamp(1, MAX_CRT_FACTOR)
+}
+
+// 各画素を factor 倍に拡大してから CRT 効果をかける
+// strength: 暗くする割合 (0 ~ 100 %)
+pub fn crt(src: &ColorImage, effect: CrtEffect, factor: usize, strength: f32) -> ColorImage {
+    if src.size[0] == 0 || src.size[1] == 0 {
+        return src.clone();
+    }
+    let mut image = if factor > 1 { scaling::prescale(src, factor) } else { src.clone() };
+    let keep = 1.0 - (strength / 100.0).clamp(0.0, 1.0);
+    let width = image.size[0];
+    // 走査線は元の1行ごとに最後の行を暗くする (拡大しない場合は1行おき)
+    let period = factor.max(2);
+    image.pixels.par_chunks_mut(width).enumerate().for_each(|(y, row)| {
+        match effect {
+            CrtEffect::None => {}
+            CrtEffect::Scanlines => {
+                if y % period == period - 1 {
+                    for p in row {
+                        *p = Color32::from_rgb(dim(p.r(), keep), dim(p.g(), keep), dim(p.b(), keep));
+                    }
+                }
+            }
+            CrtEffect::ApertureGrille | CrtEffect::ShadowMask => {
+                // 画素ごとに R・G・B のどれか1色だけを残し、他の色を暗くする
+                let offset = if effect == CrtEffect::ShadowMask { y % 2 } else { 0 };
+                for (x, p) in row.iter_mut().enumerate() {
+                    let lit = (x + offset) % 3;
+                    let channel = |i: usize, v: u8| if i == lit { v } else { dim(v, keep) };
+                    *p = Color32::from_rgb(channel(0, p.r()), channel(1, p.g()), channel(2, p.b()));
+                }
+            }
+        }
+    });
+    image
+}
+
+// 設定に従って後処理した画像 (拡大フィルタ -> CRT 効果)
+pub fn process(src: &ColorImage, settings: &RetroSettings) -> ColorImage {
+    let [width, height] = src.size;
+    let fits = |scale: usize| width * scale <= MAX_OUTPUT_SIZE && height * scale <= MAX_OUTPUT_SIZE;
+    let upscaled = match settings.upscaler {
+        Upscaler::Scale2x if fits(2) => Some(scale2x(src)),
+        Upscaler::Scale3x if fits(3) => Some(scale3x(src)),
+        Upscaler::Xbr2x if fits(2) => Some(xbr2x(src)),
+        _ => None,
+    };
+    if settings.crt == CrtEffect::None {
+        return upscaled.unwrap_or_else(|| src.clone());
+    }
+    let base = upscaled.as_ref().unwrap_or(src);
+    crt(base, settings.crt, crt_factor(base.size[1]), settings.strength)
+}
+
+// ワーカースレッドの処理結果
+#[derive(Clone)]
+pub struct RetroOutput {
+    // 処理に使った設定と事前拡大の倍率 (設定を変えた直後に古い結果を表示しないため)
+    pub settings: RetroSettings,
+    pub prescale: usize,
+    // 処理前のフレームの大きさ
+    pub source_size: [usize; 2],
+    pub frame: VideoFrame,
+}
+
+// 表示用のフレームの後処理を UI スレッドの外で行うワーカー
+// レトロフィルタと、シャープバイリニアの事前拡大 (レトロフィルタを使わないとき) を受け持つ
+pub struct RetroWorker {
+    sender: Option<SyncSender<(VideoFrame, RetroSettings, usize)>>,
+    output: Arc<Mutex<Option<RetroOutput>>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl RetroWorker {
+    pub fn spawn() -> Self {
+        // 処理待ちは1フレームまで (それ以上は submit 側で捨てる)
+        let (sender, receiver) = mpsc::sync_channel::<(VideoFrame, RetroSettings, usize)>(1);
+        let output = Arc::new(Mutex::new(None));
+        let worker_output = output.clone();
+        let thread = std::thread::Builder::new()
+            .name("retro-filter".to_string())
+            .spawn(move || {
+                // 表示し終わった事前拡大の出力バッファ (次のフレームで再利用)
+                let mut spare: Vec<Vec<Color32>> = Vec::new();
+                while let Ok((frame, settings, prescale)) = receiver.recv() {
+                    let processed = if settings.is_active() {
+                        let image = process(&frame.image, &settings);
+                        VideoFrame { width: image.size[0], height: image.size[1], image: Arc::new(image), ungraded: None, generation: frame.generation }
+                    } else {
+                        // 補正前後の比較用の補正前の画像も同じ倍率で拡大する
+                        let mut scale = |image: &ColorImage| Arc::new(scaling::prescale_into(image, prescale, spare.pop().unwrap_or_default()));
+                        let image = scale(&frame.image);
+                        let ungraded = frame.ungraded.as_deref().map(&mut scale);
+                        VideoFrame { width: image.size[0], height: image.size[1], image, ungraded, generation: frame.generation }
+                    };
+                    let previous = worker_output.lock().ok().and_then(|mut output| {
+                        output.replace(RetroOutput { settings, prescale, source_size: [frame.width, frame.height], frame: processed })
+                    });
+                    // UI がもう参照していなければバッファを回収
+                    if let Some(previous) = previous {
+                        for image in std::iter::once(previous.frame.image).chain(previous.frame.ungraded) {
+                            if let Ok(image) = Arc::try_unwrap(image) {
+                                if spare.len() < MAX_SPARE_BUFFERS {
+                                    spare.push(image.pixels);
+                                }
+                            }
+                        }
+                    }
+                }
+            });
+        let thread = match thread {
+            Ok(thread) => Some(thread),
+            Err(e) => {
+                println!("Warning: Failed to start retro filter worker: {}", e);
+                None
+            }
+        };
+        Self { sender: Some(sender), output, thread }
+    }
+
+    // フレームを処理に回す (前のフレームを処理中で受け付けられなければ false)
+    // settings が無効なら prescale 倍の事前拡大だけを行う
+    pub fn submit(&self, frame: &VideoFrame, settings: RetroSettings, prescale: usize) -> bool {
+        match self.sender.as_ref().map(|s| s.try_send((frame.clone(), settings, prescale))) {
+            Some(Ok(())) => true,
+            Some(Err(TrySendError::Full(_))) | None => false,
+            Some(Err(TrySendError::Disconnected(_))) => {
+                println!("Warning: Retro filter worker has stopped");
+                false
+            }
+        }
+    }
+
+    // settings と prescale で処理した最新の結果
+    pub fn latest(&self, settings: &RetroSettings, prescale: usize) -> Option<RetroOutput> {
+        self.output.lock().ok()?.as_ref().filter(|o| o.settings == *settings && o.prescale == prescale).cloned()
+    }
+}
+
+impl Drop for RetroWorker {
+    fn drop(&mut self) {
+        // 送信側を閉じるとワーカーのループが終わる
+        self.sender.take();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 小さな入力画像に各フィルタをかけ、あらかじめ求めた正解画像 (ゴールデンイメージ) と比較する
+
+    // 'X' を黒、'W' を白とした画像
+    fn mono_image(rows: &[&str]) -> ColorImage {
+        let pixels = rows.iter()
+            .flat_map(|row| row.chars().map(|c| if c == 'X' { Color32::BLACK } else { Color32::WHITE }))
+            .collect();
+        ColorImage { size: [rows[0].len(), rows.len()], pixels }
+    }
+
+    fn assert_image_eq(actual: &ColorImage, expected: &ColorImage) {
+        assert_eq!(actual.size, expected.size);
+        if let Some(i) = actual.pixels.iter().zip(&expected.pixels).position(|(a, e)| a != e) {
+            let width = actual.size[0];
+            panic!("pixel ({}, {}) was {:?}, expected {:?}", i % width, i / width, actual.pixels[i], expected.pixels[i]);
+        }
+    }
+
+    // 左上から右下への斜めの線
+    const DIAGONAL: [&str; 3] = ["XWW", "WXW", "WWX"];
+
+    #[test]
+    fn scale2x_golden() {
+        let expected = mono_image(&[
+            "XXWWWW",
+            "XWXWWW",
+            "WXXXWW",
+            "WWXXXW",
+            "WWWXWX",
+            "WWWWXX",
+        ]);
+        assert_image_eq(&scale2x(&mono_image(&DIAGONAL)), &expected);
+    }
+
+    #[test]
+    fn scale3x_golden() {
+        let expected = mono_image(&[
+            "XXXWWWWWW",
+            "XXWXWWWWW",
+            "XWWXWWWWW",
+            "WXXXXXWWW",
+            "WWWXXXWWW",
+            "WWWXXXXXW",
+            "WWWWWXWWX",
+            "WWWWWXWXX",
+            "WWWWWWXXX",
+        ]);
+        assert_image_eq(&scale3x(&mono_image(&DIAGONAL)), &expected);
+    }
+
+    // 明るさの表から作った灰色の画像
+    fn gray_image(rows: &[&[u8]]) -> ColorImage {
+        let pixels = rows.iter().flat_map(|row| row.iter().map(|&v| Color32::from_gray(v))).collect();
+        ColorImage { size: [rows[0].len(), rows.len()], pixels }
+    }
+
+    #[test]
+    fn xbr2x_golden() {
+        // 斜めの線は角だけ混ぜて滑らかにする (上下左右は対称)
+        let expected = gray_image(&[
+            &[0, 0, 192, 255, 255, 255],
+            &[0, 0, 64, 255, 255, 255],
+            &[192, 64, 0, 127, 255, 255],
+            &[255, 255, 127, 0, 64, 192],
+            &[255, 255, 255, 64, 0, 0],
+            &[255, 255, 255, 192, 0, 0],
+        ]);
+        assert_image_eq(&xbr2x(&mono_image(&DIAGONAL)), &expected);
+        // 緩やかな傾きの境界は2画素にわたって混ぜる
+        let slope = mono_image(&["WWWWW", "WWWWW", "WWXXX", "XXXXX", "XXXXX"]);
+        let expected = gray_image(&[
+            &[255; 10],
+            &[255; 10],
+            &[255; 10],
+            &[255; 10],
+            &[255, 255, 255, 255, 191, 63, 0, 0, 0, 0],
+            &[255, 255, 192, 64, 0, 0, 0, 0, 0, 0],
+            &[0; 10],
+            &[0; 10],
+            &[0; 10],
+            &[0; 10],
+        ]);
+        assert_image_eq(&xbr2x(&slope), &expected);
+        // 単色の画像はそのまま
+        let flat = mono_image(&["WWW", "WWW"]);
+        assert_image_eq(&xbr2x(&flat), &mono_image(&["WWWWWW"; 4]));
+    }
+
+    #[test]
+    fn empty_images() {
+        for size in [[0, 0], [0, 4], [4, 0]] {
+            let empty = ColorImage { size, pixels: Vec::new() };
+            for upscaled in [scale2x(&empty), scale3x(&empty), xbr2x(&empty), crt(&empty, CrtEffect::Scanlines, 2, 50.0)] {
+                assert!(upscaled.pixels.is_empty());
+            }
+            let settings = RetroSettings { upscaler: Upscaler::Xbr2x, crt: CrtEffect::ShadowMask, ..RetroSettings::default() };
+            assert!(process(&empty, &settings).pixels.is_empty());
+        }
+    }
+
+    #[test]
+    fn scanlines_golden() {
+        // 2倍に拡大し、元の各行の2行目を半分の明るさにする
+        let src = ColorImage { size: [1, 2], pixels: vec![Color32::from_gray(200), Color32::from_gray(100)] };
+        let expected = ColorImage {
+            size: [2, 4],
+            pixels: [200, 100, 100, 50].iter().flat_map(|&v| [Color32::from_gray(v); 2]).collect(),
+        };
+        assert_image_eq(&crt(&src, CrtEffect::Scanlines, 2, 50.0), &expected);
+    }
+
+    #[test]
+    fn aperture_grille_golden() {
+        // 縦縞: 列ごとに R・G・B だけを残す (他は半分)
+        let src = ColorImage { size: [1, 1], pixels: vec![Color32::WHITE] };
+        let row = [Color32::from_rgb(255, 128, 128), Color32::from_rgb(128, 255, 128), Color32::from_rgb(128, 128, 255)];
+        let expected = ColorImage { size: [3, 3], pixels: row.repeat(3) };
+        assert_image_eq(&crt(&src, CrtEffect::ApertureGrille, 3, 50.0), &expected);
+    }
+
+    #[test]
+    fn shadow_mask_golden() {
+        // 奇数行は1画素ずらす
+        let src = ColorImage { size: [1, 1], pixels: vec![Color32::WHITE] };
+        let (r, g, b) = (Color32::from_rgb(255, 0, 0), Color32::from_rgb(0, 255, 0), Color32::from_rgb(0, 0, 255));
+        let expected = ColorImage { size: [3, 3], pixels: vec![r, g, b, g, b, r, r, g, b] };
+        assert_image_eq(&crt(&src, CrtEffect::ShadowMask, 3, 100.0), &expected);
+    }
+
+    #[test]
+    fn pipeline_output_size() {
+        // 320x240 に Scale2x (640x480) -> CRT (1080 / 480 = 2倍) で 1280x960
+        let src = ColorImage { size: [320, 240], pixels: vec![Color32::from_gray(90); 320 * 240] };
+        let settings = RetroSettings { upscaler: Upscaler::Scale2x, crt: CrtEffect::Scanlines, ..RetroSettings::default() };
+        let out = process(&src, &settings);
+        assert_eq!(out.size, [1280, 960]);
+    }
+
+    #[test]
+    fn worker_prescales_frame_and_ungraded_image() {
+        // レトロフィルタを使わないときは事前拡大だけをワーカーで行い、補正前の画像も同じ倍率にする
+        let worker = RetroWorker::spawn();
+        let image = |gray: u8| Arc::new(ColorImage { size: [3, 2], pixels: vec![Color32::from_gray(gray); 6] });
+        let frame = VideoFrame { width: 3, height: 2, image: image(200), ungraded: Some(image(10)), generation: 7 };
+        let settings = RetroSettings::default();
+        assert!(worker.submit(&frame, settings, 2));
+        let deadline = std::time::Instant::now() + std::time::Duration::from_secs(5);
+        let output = loop {
+            assert!(std::time::Instant::now() < deadline, "worker did not produce a result");
+            if let Some(output) = worker.latest(&settings, 2) {
+                break output;
+            }
+            std::thread::sleep(std::time::Duration::from_millis(5));
+        };
+        assert_eq!((output.source_size, output.frame.generation), ([3, 2], 7));
+        assert_image_eq(&output.frame.image, &scaling::prescale(&frame.image, 2));
+        assert_image_eq(output.frame.ungraded.as_ref().unwrap(), &scaling::prescale(frame.ungraded.as_ref().unwrap(), 2));
+        // 倍率が違う結果は返さない
+        assert!(worker.latest(&settings, 3).is_none());
+    }
+}
@@ -0,0 +1,143 @@
+use egui::{Color32, ColorImage, TextureFilter, TextureOptions, TextureWrapMode};
+use rayon::prelude::*;
+use std::borrow::Cow;
+use crate::settings::ScalingMode;
+
+// 映像の拡大方式 (テクスチャの補間方法・表示サイズ・シャープバイリニア用の事前拡大)
+// 大きさはすべて物理ピクセル単位 (ポイント単位への換算は呼び出し側で行う)
+
+// シャープバイリニアで事前に拡大する最大倍率と、拡大後のテクスチャの最大サイズ
+const MAX_PRESCALE: usize = 8;
+const MAX_PRESCALED_SIZE: usize = 4096;
+
+pub fn texture_options(mode: ScalingMode) -> TextureOptions {
+    let (magnification, minification) = match mode {
+        ScalingMode::Auto | ScalingMode::Integer => (TextureFilter::Nearest, TextureFilter::Linear),
+        ScalingMode::Nearest => (TextureFilter::Nearest, TextureFilter::Nearest),
+        ScalingMode::Bilinear | ScalingMode::SharpBilinear => (TextureFilter::Linear, TextureFilter::Linear),
+    };
+    TextureOptions { magnification, minification, wrap_mode: TextureWrapMode::ClampToEdge }
+}
+
+// 整数倍拡大の表示サイズ (1倍でも収まらない場合は None)
+pub fn integer_size(image_size: egui::Vec2, available: egui::Vec2) -> Option<egui::Vec2> {
+    let scale = (available.x / image_size.x).min(available.y / image_size.y).floor();
+    (scale >= 1.0).then(|| image_size * scale)
+}
+
+// シャープバイリニアで事前に拡大する倍率 (表示倍率の整数部分、テクスチャが大きくなりすぎない範囲)
+pub fn prescale_factor(mode: ScalingMode, image_size: egui::Vec2, display_size: egui::Vec2) -> usize {
+    if mode != ScalingMode::SharpBilinear || image_size.x < 1.0 || image_size.y < 1.0 {
+        return 1;
+    }
+    let scale = (display_size.x / image_size.x).min(display_size.y / image_size.y).floor().max(1.0) as usize;
+    let limit = MAX_PRESCALED_SIZE / (image_size.x.max(image_size.y) as usize).max(1);
+    scale.min(MAX_PRESCALE).min(limit).max(1)
+}
+
+// 各画素を factor x factor に複製した画像
+pub fn prescale(image: &ColorImage, factor: usize) -> ColorImage {
+    prescale_into(image, factor, Vec::new())
+}
+
+// prescale の出力先に使い終わったバッファを再利用する版 (容量が足りていれば確保し直さない)
+pub fn prescale_into(image: &ColorImage, factor: usize, mut pixels: Vec<Color32>) -> ColorImage {
+    let [width, height] = image.size;
+    let out_width = width * factor;
+    pixels.clear();
+    pixels.resize(out_width * height * factor, Color32::BLACK);
+    pixels.par_chunks_mut(out_width).enumerate().for_each(|(y, row)| {
+        let src = &image.pixels[(y / factor) * width..][..width];
+        for (out, &p) in row.chunks_exact_mut(factor).zip(src) {
+            out.fill(p);
+        }
+    });
+    ColorImage { size: [out_width, height * factor], pixels }
+}
+
+// width x height の RGBA 画像を縦横比 aspect (幅 / 高さ) に引き伸ばす (縮めずに足りない方向を伸ばす)
+pub fn resize_to_aspect(pixels: Cow<'_, [u8]>, width: u32, height: u32, aspect: f32) -> (Cow<'_, [u8]>, u32, u32) {
+    if width == 0 || height == 0 || !aspect.is_finite() || aspect <= 0.0 {
+        return (pixels, width, height);
+    }
+    let (out_width, out_height) = if aspect > width as f32 / height as f32 {
+        ((height as f32 * aspect).round() as u32, height)
+    } else {
+        (width, (width as f32 / aspect).round() as u32)
+    };
+    if (out_width, out_height) == (width, height) {
+        return (pixels, width, height);
+    }
+    let Some(image) = image::RgbaImage::from_raw(width, height, pixels.to_vec()) else {
+        println!("Warning: Image data size does not match {}x{}, aspect ratio not applied", width, height);
+        return (pixels, width, height);
+    };
+    let image = image::imageops::resize(&image, out_width, out_height, image::imageops::FilterType::Triangle);
+    (Cow::Owned(image.into_raw()), out_width, out_height)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn integer_size_fits_available_area() {
+        let cases = [
+            // 256x224 を 1920x1080 に: 4倍 (1024x896)
+            (egui::vec2(256.0, 224.0), egui::vec2(1920.0, 1080.0), Some(egui::vec2(1024.0, 896.0))),
+            // ちょうど収まる場合
+            (egui::vec2(640.0, 480.0), egui::vec2(1280.0, 960.0), Some(egui::vec2(1280.0, 960.0))),
+            // 1倍でも収まらない場合
+            (egui::vec2(1920.0, 1080.0), egui::vec2(1280.0, 720.0), None),
+        ];
+        for (image, available, expected) in cases {
+            assert_eq!(integer_size(image, available), expected, "{:?} in {:?}", image, available);
+        }
+    }
+
+    #[test]
+    fn prescale_factor_only_for_sharp_bilinear() {
+        assert_eq!(prescale_factor(ScalingMode::SharpBilinear, egui::vec2(256.0, 224.0), egui::vec2(1234.0, 1080.0)), 4);
+        assert_eq!(prescale_factor(ScalingMode::Bilinear, egui::vec2(256.0, 224.0), egui::vec2(1234.0, 1080.0)), 1);
+    }
+
+    #[test]
+    fn prescale_reuses_buffer() {
+        let image = ColorImage { size: [4, 2], pixels: vec![Color32::RED; 8] };
+        let buffer = Vec::with_capacity(4 * 2 * 9);
+        let ptr = buffer.as_ptr();
+        let out = prescale_into(&image, 3, buffer);
+        assert_eq!(out.pixels.as_ptr(), ptr);
+        assert_eq!(out.pixels, prescale(&image, 3).pixels);
+    }
+
+    #[test]
+    fn prescale_repeats_pixels() {
+        let colors = [Color32::RED, Color32::GREEN, Color32::BLUE, Color32::WHITE];
+        let image = ColorImage { size: [2, 2], pixels: colors.to_vec() };
+        let out = prescale(&image, 3);
+        assert_eq!(out.size, [6, 6]);
+        for (i, &p) in out.pixels.iter().enumerate() {
+            let (x, y) = (i % 6, i / 6);
+            assert_eq!(p, colors[(y / 3) * 2 + x / 3], "pixel ({}, {})", x, y);
+        }
+    }
+
+    #[test]
+    fn resize_to_aspect_keeps_height() {
+        let pixels = vec![255u8; 720 * 480 * 4];
+        let cases = [
+            // 720x480 のアナモフィック映像を 16:9 と 4:3 に
+            (16.0 / 9.0, (853, 480)),
+            (4.0 / 3.0, (720, 540)),
+            // 縦横比が同じならそのまま
+            (1.5, (720, 480)),
+        ];
+        for (aspect, expected) in cases {
+            let (out, width, height) = resize_to_aspect(Cow::Borrowed(&pixels), 720, 480, aspect);
+            assert_eq!((width, height), expected, "aspect {}", aspect);
+            assert_eq!(out.len(), (width * height * 4) as usize);
+            assert!(out.iter().all(|&v| v == 255), "aspect {} changed the pixel values", aspect);
+        }
+    }
+}
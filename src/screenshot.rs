@@ -15,6 +15,12 @@ pub struct ScreenshotManager {
     listener_shutdown: Arc<Mutex<bool>>,
 }
 
+impl Default for ScreenshotManager {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl ScreenshotManager {
     pub fn new() -> Self {
         Self {
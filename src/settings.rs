@@ -1,10 +1,19 @@
 use serde::{Deserialize, Serialize};
-use std::path::PathBuf;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
 
 fn default_passthrough_enabled() -> bool {
     true // デフォルトで音声パススルーは有効
 }
 
+fn default_keep_raw_pixels() -> bool {
+    true // デフォルトでスクリーンショットは入力の画素数のまま保存
+}
+
+fn default_custom_aspect() -> (u32, u32) {
+    (16, 10)
+}
+
 fn default_enable_drag_move() -> bool {
     true // デフォルトで画面ドラッグ移動は有効
 }
@@ -15,20 +24,356 @@ pub struct AppSettings {
     pub audio: AudioSettings,
     pub screenshot: ScreenshotSettings,
     pub ui: UiSettings,
+    #[serde(default)]
+    pub recording: RecordingSettings,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct VideoSettings {
     pub device_name: Option<String>,
+    // 名前だけでは区別できないデバイスを特定するための識別情報
+    #[serde(default)]
+    pub device_id: Option<DeviceId>,
     pub resolution: Option<(u32, u32)>,
     pub format: Option<String>,
     pub fps: Option<u32>,
+    #[serde(default)]
+    pub color_space: ColorSpace,
+    #[serde(default)]
+    pub color_range: ColorRange,
+    #[serde(default)]
+    pub backend: CaptureBackend,
+    #[serde(default)]
+    pub deinterlace: DeinterlaceMode,
+    #[serde(default)]
+    pub field_order: FieldOrder,
+    // 表示の回転 (時計回り) と反転 (反転してから回転する)
+    #[serde(default)]
+    pub rotation: Rotation,
+    #[serde(default)]
+    pub flip_horizontal: bool,
+    #[serde(default)]
+    pub flip_vertical: bool,
+    // 3D LUT による色補正
+    #[serde(default)]
+    pub lut: LutSettings,
+    // デバイスごとの表示設定 (キーは device_key())
+    #[serde(default)]
+    pub profiles: BTreeMap<String, DeviceProfile>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+impl VideoSettings {
+    // デバイスごとの設定のキー (固有パスがあればパス、なければ名前)
+    pub fn device_key(&self) -> String {
+        match &self.device_id {
+            Some(id) if !id.path.is_empty() => id.path.clone(),
+            _ => self.device_name.clone().unwrap_or_default(),
+        }
+    }
+
+    // 選択中のデバイスの表示設定 (名前だけで保存されていた設定も探す)
+    pub fn profile(&self) -> DeviceProfile {
+        let name = self.device_name.clone().unwrap_or_default();
+        self.profiles.get(&self.device_key())
+            .or_else(|| self.profiles.get(&name))
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    pub fn profile_mut(&mut self) -> &mut DeviceProfile {
+        let key = self.device_key();
+        let name = self.device_name.clone().unwrap_or_default();
+        // 識別情報が補完される前に名前で保存した設定はパスのキーに移す
+        if !self.profiles.contains_key(&key) {
+            if let Some(old) = self.profiles.remove(&name) {
+                self.profiles.insert(key.clone(), old);
+            }
+        }
+        self.profiles.entry(key).or_default()
+    }
+}
+
+// デバイスごとに保存する表示設定
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DeviceProfile {
+    pub picture: PictureAdjustments,
+    pub crop: CropSettings,
+    pub retro: RetroSettings,
+}
+
+// レトロゲーム機向けの後処理 (拡大フィルタ + CRT 風の効果)
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RetroSettings {
+    pub upscaler: Upscaler,
+    pub crt: CrtEffect,
+    // CRT 効果の強さ (%、暗くする割合)
+    pub strength: f32,
+    // スクリーンショットにも適用する
+    pub apply_to_screenshots: bool,
+}
+
+impl Default for RetroSettings {
+    fn default() -> Self {
+        Self { upscaler: Upscaler::None, crt: CrtEffect::None, strength: 50.0, apply_to_screenshots: false }
+    }
+}
+
+impl RetroSettings {
+    pub fn is_active(&self) -> bool {
+        self.upscaler != Upscaler::None || self.crt != CrtEffect::None
+    }
+}
+
+// ドット絵向けの拡大フィルタ
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+pub enum Upscaler {
+    #[default]
+    None,
+    Scale2x, // EPX / AdvMAME2x
+    Scale3x, // AdvMAME3x
+    Xbr2x,   // xBR 2x (Hyllian)
+}
+
+// CRT 風の効果
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+pub enum CrtEffect {
+    #[default]
+    None,
+    Scanlines,      // 走査線
+    ApertureGrille, // 縦縞の RGB (トリニトロン)
+    ShadowMask,     // 行ごとにずらした RGB の点
+}
+
+// 3D LUT (.cube) による色補正
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LutSettings {
+    // 未設定なら LUT は使わない
+    pub path: Option<PathBuf>,
+    // 適用の強さ (%、元の色との混合)
+    pub strength: f32,
+    pub interpolation: LutInterpolation,
+    // 画面の左半分に補正前の映像を表示して補正前後を比べる (表示だけ、スクリーンショット・録画は補正後)
+    pub split_view: bool,
+}
+
+impl Default for LutSettings {
+    fn default() -> Self {
+        Self { path: None, strength: 100.0, interpolation: LutInterpolation::Tetrahedral, split_view: false }
+    }
+}
+
+// LUT の格子点の間の補間方法
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+pub enum LutInterpolation {
+    #[default]
+    Tetrahedral, // 四面体補間 (高速で色相のずれが少ない)
+    Trilinear,   // 三線形補間
+}
+
+// 表示範囲の切り抜き (各辺のピクセル数 + 全周のオーバースキャン率)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CropSettings {
+    pub left: u32,
+    pub top: u32,
+    pub right: u32,
+    pub bottom: u32,
+    // 各辺から幅・高さのこの割合 (%) を追加で切り落とす (0 ~ 10)
+    pub overscan: f32,
+}
+
+// 切り抜いた後に最低限残す大きさ (ピクセル)
+const MIN_CROPPED_SIZE: u32 = 16;
+
+// クロップ範囲の辺
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CropEdge {
+    Left,
+    Top,
+    Right,
+    Bottom,
+}
+
+// 両側の切り落とし量 (a, b) を、長さ total のうち最小サイズが残るように縮める
+// 設定ファイルに極端な値が書かれていても溢れないよう和は u64 で求める
+fn fit_crop(a: u32, b: u32, total: u32) -> (u32, u32) {
+    let max = total.saturating_sub(MIN_CROPPED_SIZE);
+    let sum = a as u64 + b as u64;
+    if sum <= max as u64 {
+        return (a, b);
+    }
+    // 切り落としすぎた場合は両側を同じ割合で縮める
+    let a = (a as u64 * max as u64 / sum) as u32;
+    (a, max - a)
+}
+
+impl CropSettings {
+    // オーバースキャンで各辺から切り落とすピクセル数 (横, 縦)
+    fn overscan_pixels(&self, width: u32, height: u32) -> (u32, u32) {
+        let ratio = self.overscan.clamp(0.0, 10.0) / 100.0;
+        ((width as f32 * ratio).round() as u32, (height as f32 * ratio).round() as u32)
+    }
+
+    // width x height のフレームで表示する範囲 (x, y, 幅, 高さ)
+    pub fn rect(&self, width: u32, height: u32) -> (u32, u32, u32, u32) {
+        let (ox, oy) = self.overscan_pixels(width, height);
+        let (left, right) = fit_crop(self.left.saturating_add(ox), self.right.saturating_add(ox), width);
+        let (top, bottom) = fit_crop(self.top.saturating_add(oy), self.bottom.saturating_add(oy), height);
+        (left, top, width - left - right, height - top - bottom)
+    }
+
+    // 辺をフレーム上の位置 (左端・上端からのピクセル) に動かす
+    // 保存するのはオーバースキャン分を除いたピクセル数
+    pub fn set_edge(&mut self, edge: CropEdge, position: u32, width: u32, height: u32) {
+        let (ox, oy) = self.overscan_pixels(width, height);
+        let (x, y, w, h) = self.rect(width, height);
+        match edge {
+            CropEdge::Left => self.left = position.min((x + w).saturating_sub(MIN_CROPPED_SIZE)).saturating_sub(ox),
+            CropEdge::Top => self.top = position.min((y + h).saturating_sub(MIN_CROPPED_SIZE)).saturating_sub(oy),
+            CropEdge::Right => self.right = width.saturating_sub(position.max(x.saturating_add(MIN_CROPPED_SIZE)).min(width)).saturating_sub(ox),
+            CropEdge::Bottom => self.bottom = height.saturating_sub(position.max(y.saturating_add(MIN_CROPPED_SIZE)).min(height)).saturating_sub(oy),
+        }
+    }
+
+    pub fn is_none(&self) -> bool {
+        *self == CropSettings::default()
+    }
+}
+
+// 画質調整 (既定値で無調整)
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PictureAdjustments {
+    pub brightness: f32, // -100 ~ 100
+    pub contrast: f32,   // 0 ~ 200 (%)
+    pub saturation: f32, // 0 ~ 200 (%)
+    pub hue: f32,        // -180 ~ 180 (度)
+    pub gamma: f32,      // 0.1 ~ 3.0
+}
+
+impl Default for PictureAdjustments {
+    fn default() -> Self {
+        Self { brightness: 0.0, contrast: 100.0, saturation: 100.0, hue: 0.0, gamma: 1.0 }
+    }
+}
+
+// キャプチャデバイスの識別情報 (テストパターン・ファイル入力では None)
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct DeviceId {
+    // OS が割り当てる固有の文字列 (Media Foundation: シンボリックリンク / V4L2: バス情報)
+    #[serde(default)]
+    pub path: String,
+    // 列挙順 (接続し直すと変わりうるため同名デバイスの区別にのみ使う)
+    #[serde(default)]
+    pub index: Option<u32>,
+}
+
+// 保存されたデバイスとの照合結果
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DeviceMatch {
+    // 候補一覧での位置
+    Found(usize),
+    // 同名デバイスが複数あり特定できない (台数)
+    Ambiguous(usize),
+    NotFound,
+}
+
+// 保存された名前・識別情報に一致するデバイスを候補 (名前, 識別情報) から選ぶ
+// 1. 固有パスが一致 (ドライバ更新で表示名が変わっても追従する)
+// 2. 名前と列挙順が一致
+// 3. 名前が一致するデバイスが1台だけ
+// 同名が複数あってどれにも当てはまらない場合は推測で選ばない
+pub fn match_device(candidates: &[(String, DeviceId)], name: &str, id: Option<&DeviceId>) -> DeviceMatch {
+    if let Some(id) = id.filter(|id| !id.path.is_empty()) {
+        if let Some(i) = candidates.iter().position(|(_, c)| c.path == id.path) {
+            return DeviceMatch::Found(i);
+        }
+    }
+    let same_name: Vec<usize> = candidates.iter()
+        .enumerate()
+        .filter(|(_, (n, _))| n == name)
+        .map(|(i, _)| i)
+        .collect();
+    if let Some(index) = id.and_then(|id| id.index) {
+        if let Some(&i) = same_name.iter().find(|&&i| candidates[i].1.index == Some(index)) {
+            return DeviceMatch::Found(i);
+        }
+    }
+    match same_name.len() {
+        0 => DeviceMatch::NotFound,
+        1 => DeviceMatch::Found(same_name[0]),
+        n => DeviceMatch::Ambiguous(n),
+    }
+}
+
+// キャプチャデバイスの取得に使う API
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, Default)]
+pub enum CaptureBackend {
+    #[default]
+    Auto, // OS標準 (Windows: Media Foundation / Linux: V4L2)
+    MediaFoundation,
+    V4l2,
+}
+
+// YUV -> RGB 変換に使う色空間 (変換行列)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+pub enum ColorSpace {
+    #[default]
+    Auto, // 解像度から自動判定 (SD: BT.601 / HD以上: BT.709)
+    Bt601,
+    Bt709,
+    Bt2020,
+}
+
+// YUV の信号レンジ
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+pub enum ColorRange {
+    #[default]
+    Limited, // 16-235 (一般的な映像機器)
+    Full,    // 0-255 (一部のゲーム機・PC出力)
+}
+
+// インターレース解除の方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+pub enum DeinterlaceMode {
+    #[default]
+    Weave,          // 解除しない (2フィールドをそのまま表示)
+    Bob,            // フィールドごとに補間して倍速表示
+    Blend,          // 上下の行を混ぜる
+    MotionAdaptive, // 動いている部分だけ補間
+}
+
+// インターレース映像のフィールド順
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+pub enum FieldOrder {
+    #[default]
+    Auto, // 解像度から判定 (480i: BFF / それ以外: TFF)
+    Tff,  // トップフィールドが先
+    Bff,  // ボトムフィールドが先
+}
+
+// 映像の回転 (時計回り)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+pub enum Rotation {
+    #[default]
+    None,
+    Cw90,
+    Cw180,
+    Cw270,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct AudioSettings {
     pub input_device_name: Option<String>,
     pub output_device_name: Option<String>, 
+    // 同名デバイスが複数ある場合の何台目か (0始まり)
+    #[serde(default)]
+    pub input_device_index: Option<usize>,
+    #[serde(default)]
+    pub output_device_index: Option<usize>,
     pub sample_rate: Option<u32>,
     pub channels: Option<u16>,
     #[serde(default = "default_passthrough_enabled")]
@@ -41,6 +386,28 @@ pub struct ScreenshotSettings {
     pub sound_file: Option<PathBuf>,
     pub sound_volume: f32,
     pub hotkey: Option<String>,
+    // 画素の縦横比・表示の縦横比を補正せず、入力の画素数のまま保存する
+    #[serde(default = "default_keep_raw_pixels")]
+    pub keep_raw_pixels: bool,
+}
+
+// 録画 (MJPEG 映像 + PCM 音声の AVI)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RecordingSettings {
+    // 保存先 (未設定ならスクリーンショットと同じフォルダ)
+    pub save_folder: Option<PathBuf>,
+    // JPEG の画質 (1 ~ 100)
+    pub quality: u8,
+    pub record_audio: bool,
+    // 録画の開始・停止キー (ウィンドウがアクティブな時のみ)
+    pub hotkey: String,
+}
+
+impl Default for RecordingSettings {
+    fn default() -> Self {
+        Self { save_folder: None, quality: 85, record_audio: true, hotkey: "F9".to_string() }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -52,6 +419,80 @@ pub struct UiSettings {
     pub always_on_top: bool,
     #[serde(default = "default_enable_drag_move")]
     pub enable_drag_move: bool,
+    // 入力解像度が変わったらウィンドウの縦横比を合わせる
+    #[serde(default)]
+    pub fit_window_to_source: bool,
+    // 黒帯 (レターボックス・ピラーボックス) を検出して自動でクロップする
+    #[serde(default)]
+    pub auto_crop_bars: bool,
+    // 映像の拡大方式と画素の縦横比の補正
+    #[serde(default)]
+    pub scaling: ScalingMode,
+    #[serde(default)]
+    pub pixel_aspect: PixelAspect,
+    // 表示の縦横比の指定 (自動以外では画素の縦横比より優先)
+    #[serde(default)]
+    pub display_aspect: DisplayAspect,
+    #[serde(default = "default_custom_aspect")]
+    pub custom_aspect: (u32, u32),
+}
+
+// 映像の拡大方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+pub enum ScalingMode {
+    #[default]
+    Auto,          // 拡大はニアレスト、縮小はバイリニア
+    Nearest,       // 常にニアレスト
+    Bilinear,      // 常にバイリニア
+    Integer,       // 整数倍に拡大して余白は黒 (ウィンドウより大きい場合は縮小)
+    SharpBilinear, // 整数倍に拡大してから残りをバイリニアで拡大
+}
+
+// 表示の縦横比 (クロップ後の映像全体を、回転前にこの比率に引き伸ばす)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+pub enum DisplayAspect {
+    #[default]
+    Auto,      // 入力の画素数と画素の縦横比から決める
+    Standard,  // 4:3
+    Wide,      // 16:9
+    UltraWide, // 21:9
+    SuperWide, // 32:9
+    Custom,    // UiSettings::custom_aspect
+}
+
+impl DisplayAspect {
+    // 幅 / 高さ (自動なら None)
+    pub fn ratio(self, custom: (u32, u32)) -> Option<f32> {
+        match self {
+            DisplayAspect::Auto => None,
+            DisplayAspect::Standard => Some(4.0 / 3.0),
+            DisplayAspect::Wide => Some(16.0 / 9.0),
+            DisplayAspect::UltraWide => Some(21.0 / 9.0),
+            DisplayAspect::SuperWide => Some(32.0 / 9.0),
+            DisplayAspect::Custom => Some(custom.0.max(1) as f32 / custom.1.max(1) as f32),
+        }
+    }
+}
+
+// 入力映像の画素の縦横比 (幅 / 高さ)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+pub enum PixelAspect {
+    #[default]
+    Square, // 1:1
+    Snes,   // 8:7 (ファミコン・スーパーファミコンなど)
+    Ntsc,   // 10:11 (NTSC SD 4:3)
+    Pal,    // 12:11 (PAL SD 4:3)
+}
+
+impl PixelAspect {
+    pub fn ratio(self) -> f32 {
+        match self {
+            PixelAspect::Square => 1.0,
+            PixelAspect::Snes => 8.0 / 7.0,
+            PixelAspect::Ntsc => 10.0 / 11.0,
+            PixelAspect::Pal => 12.0 / 11.0,
+        }
+    }
 }
 
 
@@ -59,9 +500,20 @@ impl Default for VideoSettings {
     fn default() -> Self {
         Self {
             device_name: None,
+            device_id: None,
             resolution: Some((1280, 720)), // 720pで安定性を優先
             format: Some("YUY2".to_string()), // YUY2フォーマット
             fps: Some(60), // 60fps目標
+            color_space: ColorSpace::Auto,
+            color_range: ColorRange::Limited,
+            backend: CaptureBackend::Auto,
+            deinterlace: DeinterlaceMode::Weave,
+            field_order: FieldOrder::Auto,
+            rotation: Rotation::None,
+            flip_horizontal: false,
+            flip_vertical: false,
+            lut: LutSettings::default(),
+            profiles: BTreeMap::new(),
         }
     }
 }
@@ -71,6 +523,8 @@ impl Default for AudioSettings {
         Self {
             input_device_name: None,
             output_device_name: None,
+            input_device_index: None,
+            output_device_index: None,
             sample_rate: Some(48000),
             channels: Some(2),
             passthrough_enabled: true,
@@ -85,6 +539,7 @@ impl Default for ScreenshotSettings {
             sound_file: Some(PathBuf::from("sound/SS.mp3")),
             sound_volume: 100.0,
             hotkey: Some("F5".to_string()),
+            keep_raw_pixels: true,
         }
     }
 }
@@ -98,6 +553,12 @@ impl Default for UiSettings {
             last_window_pos: None,
             always_on_top: false,
             enable_drag_move: true,
+            fit_window_to_source: false,
+            auto_crop_bars: false,
+            scaling: ScalingMode::Auto,
+            pixel_aspect: PixelAspect::Square,
+            display_aspect: DisplayAspect::Auto,
+            custom_aspect: default_custom_aspect(),
         }
     }
 }
@@ -114,17 +575,110 @@ impl AppSettings {
     }
     
     pub fn get_screenshot_path(&self, timestamp: &str) -> PathBuf {
-        let mut path = self.screenshot.save_folder.clone();
-        path.push(format!("{}.jpg", timestamp));
-        
-        // ファイル名の競合を処理
-        let mut counter = 1;
-        while path.exists() {
-            let stem = format!("{}({})", timestamp, counter);
-            path.set_file_name(format!("{}.jpg", stem));
-            counter += 1;
+        unique_path(&self.screenshot.save_folder, timestamp, "jpg")
+    }
+
+    pub fn get_recording_path(&self, timestamp: &str) -> PathBuf {
+        let folder = self.recording.save_folder.as_ref().unwrap_or(&self.screenshot.save_folder);
+        unique_path(folder, timestamp, "avi")
+    }
+}
+
+// フォルダ内の「タイムスタンプ.拡張子」のパス (同名のファイルがあれば番号を付ける)
+fn unique_path(folder: &Path, timestamp: &str, extension: &str) -> PathBuf {
+    let mut path = folder.to_path_buf();
+    path.push(format!("{}.{}", timestamp, extension));
+    
+    // ファイル名の競合を処理
+    let mut counter = 1;
+    while path.exists() {
+        let stem = format!("{}({})", timestamp, counter);
+        path.set_file_name(format!("{}.{}", stem, extension));
+        counter += 1;
+    }
+    
+    path
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn crop(left: u32, top: u32, right: u32, bottom: u32, overscan: f32) -> CropSettings {
+        CropSettings { left, top, right, bottom, overscan }
+    }
+
+    #[test]
+    fn crop_rect_adds_overscan_to_each_side() {
+        // 1000x500 の 5% は横 50 / 縦 25 ピクセル
+        assert_eq!(crop(10, 5, 20, 0, 5.0).rect(1000, 500), (60, 30, 870, 445));
+        assert_eq!(crop(10, 5, 20, 0, 0.0).rect(1000, 500), (10, 5, 970, 495));
+        // オーバースキャンは 10% まで
+        assert_eq!(crop(0, 0, 0, 0, 50.0).rect(1000, 500), (100, 50, 800, 400));
+    }
+
+    #[test]
+    fn crop_rect_shrinks_proportionally() {
+        // 残り 16 ピクセルになるよう 1:3 のまま縮める (100 - 16 = 84 -> 21 + 63)
+        assert_eq!(crop(100, 0, 300, 0, 0.0).rect(100, 50), (21, 0, 16, 50));
+        assert_eq!(crop(0, 40, 0, 40, 0.0).rect(100, 50), (0, 17, 100, 16));
+        // 設定ファイルの極端な値でも溢れない
+        assert_eq!(crop(u32::MAX, u32::MAX, u32::MAX, u32::MAX, 10.0).rect(1920, 1080), (952, 532, 16, 16));
+    }
+
+    #[test]
+    fn crop_rect_keeps_tiny_frames_whole() {
+        // 16 ピクセル以下のフレームは切り落とさない
+        assert_eq!(crop(4, 4, 4, 4, 10.0).rect(10, 16), (0, 0, 10, 16));
+        assert_eq!(crop(4, 4, 4, 4, 0.0).rect(0, 0), (0, 0, 0, 0));
+    }
+
+    #[test]
+    fn set_edge_round_trips_with_overscan() {
+        let mut settings = crop(0, 0, 0, 0, 5.0);
+        let (width, height) = (1000, 500);
+        settings.set_edge(CropEdge::Left, 200, width, height);
+        settings.set_edge(CropEdge::Top, 100, width, height);
+        settings.set_edge(CropEdge::Right, 800, width, height);
+        settings.set_edge(CropEdge::Bottom, 400, width, height);
+        // 保存されるのはオーバースキャン分を除いた量
+        assert_eq!((settings.left, settings.top, settings.right, settings.bottom), (150, 75, 150, 75));
+        assert_eq!(settings.rect(width, height), (200, 100, 600, 300));
+        // 反対の辺を越えて動かしても最小サイズは残る
+        settings.set_edge(CropEdge::Left, 900, width, height);
+        assert_eq!(settings.rect(width, height), (784, 100, 16, 300));
+        // オーバースキャンより外側には広げられない
+        settings.set_edge(CropEdge::Top, 0, width, height);
+        assert_eq!((settings.top, settings.rect(width, height).1), (0, 25));
+    }
+
+    #[test]
+    fn match_device_fallback_order() {
+        let id = |path: &str, index: u32| DeviceId { path: path.to_string(), index: Some(index) };
+        let candidates = vec![
+            ("USB Video".to_string(), id("usb-1", 0)),
+            ("Capture (new driver)".to_string(), id("pci-2", 0)),
+            ("USB Video".to_string(), id("usb-3", 1)),
+            ("Webcam".to_string(), id("usb-4", 0)),
+        ];
+        let cases = [
+            // 固有パスが一致すれば名前が変わっていても選ぶ
+            ("Capture (old driver)", Some(id("pci-2", 5)), DeviceMatch::Found(1)),
+            ("USB Video", Some(id("usb-3", 0)), DeviceMatch::Found(2)),
+            // パスが見つからなければ名前と列挙順
+            ("USB Video", Some(id("gone", 1)), DeviceMatch::Found(2)),
+            ("USB Video", Some(DeviceId { path: String::new(), index: Some(0) }), DeviceMatch::Found(0)),
+            // 同名が1台だけなら名前だけで選ぶ
+            ("Webcam", Some(id("gone", 3)), DeviceMatch::Found(3)),
+            ("Webcam", None, DeviceMatch::Found(3)),
+            // 同名が複数あって列挙順も合わなければ選ばない
+            ("USB Video", Some(id("gone", 7)), DeviceMatch::Ambiguous(2)),
+            ("USB Video", None, DeviceMatch::Ambiguous(2)),
+            ("Missing", Some(id("gone", 0)), DeviceMatch::NotFound),
+            ("Missing", None, DeviceMatch::NotFound),
+        ];
+        for (name, saved, expected) in cases {
+            assert_eq!(match_device(&candidates, name, saved.as_ref()), expected, "{} {:?}", name, saved);
         }
-        
-        path
     }
-}
\ No newline at end of file
+}
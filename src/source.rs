@@ -0,0 +1,316 @@
+use nokhwa::pixel_format::{RgbAFormat, RgbFormat};
+use nokhwa::utils::{RequestedFormat, RequestedFormatType, CameraFormat, Resolution, ApiBackend, FrameFormat, CameraInfo};
+use nokhwa::{CallbackCamera, NokhwaError};
+use crate::convert::PixelFormat;
+use crate::settings::{self, CaptureBackend, DeviceId, DeviceMatch};
+use crate::video::{self, DeviceCapabilities};
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+// 映像入力ソース (キャプチャデバイス、テストパターン等) の共通インターフェース
+
+// ソースから届く1フレーム分の生データ
+pub struct RawFrame<'a> {
+    pub width: usize,
+    pub height: usize,
+    // 変換器で扱えないフォーマットは None (fallback でデコード)
+    pub format: Option<PixelFormat>,
+    pub data: &'a [u8],
+    pub fallback: Option<FallbackDecode<'a>>,
+}
+
+// 専用変換に失敗した場合の RGBA デコード (width * height * 4 のバッファに書き込む)
+pub type FallbackDecode<'a> = &'a dyn Fn(&mut [u8]) -> bool;
+
+// フレームの受け取り先 (ソースのスレッドから呼ばれる)
+pub type FrameSink = Box<dyn FnMut(RawFrame<'_>) + Send>;
+
+pub trait VideoSource: Send {
+    // フレームの配信を開始
+    fn start(&mut self, sink: FrameSink) -> Result<(), String>;
+    // 配信を停止 (停止後は sink は呼ばれない)
+    fn stop(&mut self);
+    // 開いている物理デバイスの識別情報 (設定に保存して次回の照合に使う)
+    fn device_id(&self) -> Option<DeviceId> {
+        None
+    }
+}
+
+// ソフトウェア生成・ファイル再生ソース用のフレーム間隔管理
+pub struct FramePacer {
+    interval: Duration,
+    next: Instant,
+}
+
+impl FramePacer {
+    pub fn new(fps: f64) -> Self {
+        Self { interval: Duration::from_secs_f64(1.0 / fps.max(1.0)), next: Instant::now() }
+    }
+
+    // 次のフレーム時刻まで待機 (遅れた場合は追いつこうとせず次の周期から再開)
+    pub fn wait(&mut self) {
+        self.next += self.interval;
+        let now = Instant::now();
+        if self.next > now {
+            std::thread::sleep(self.next - now);
+        } else {
+            self.next = now;
+        }
+    }
+}
+
+// nokhwa のフレームフォーマットを変換器のフォーマットに対応付け
+fn pixel_format_of(ff: FrameFormat) -> Option<PixelFormat> {
+    match ff {
+        FrameFormat::YUYV => Some(PixelFormat::Yuy2),
+        FrameFormat::MJPEG => Some(PixelFormat::Mjpeg),
+        FrameFormat::NV12 => Some(PixelFormat::Nv12),
+        FrameFormat::RAWRGB => Some(PixelFormat::Rgb24),
+        _ => None,
+    }
+}
+
+// 設定のバックエンドを nokhwa の API に対応付け (Auto は OS 標準)
+pub fn api_backend(backend: CaptureBackend) -> ApiBackend {
+    match backend {
+        CaptureBackend::MediaFoundation => ApiBackend::MediaFoundation,
+        CaptureBackend::V4l2 => ApiBackend::Video4Linux,
+        CaptureBackend::Auto if cfg!(windows) => ApiBackend::MediaFoundation,
+        CaptureBackend::Auto if cfg!(target_os = "linux") => ApiBackend::Video4Linux,
+        CaptureBackend::Auto => ApiBackend::Auto,
+    }
+}
+
+// このビルドで使えるバックエンド (設定画面の選択肢)
+pub fn available_backends() -> Vec<CaptureBackend> {
+    let mut list = vec![CaptureBackend::Auto];
+    if cfg!(windows) {
+        list.push(CaptureBackend::MediaFoundation);
+    }
+    if cfg!(all(target_os = "linux", feature = "v4l2")) {
+        list.push(CaptureBackend::V4l2);
+    }
+    list
+}
+
+// nokhwa のデバイス情報から識別情報を作る
+pub fn camera_id(info: &CameraInfo) -> DeviceId {
+    DeviceId { path: info.misc(), index: info.index().as_index().ok() }
+}
+
+// 保存された名前・識別情報でデバイスを検索 (未指定なら先頭)
+pub fn find_camera(backend: CaptureBackend, device_name: Option<&str>, device_id: Option<&DeviceId>) -> Result<CameraInfo, String> {
+    let mut devices = nokhwa::query(api_backend(backend))
+        .map_err(|e| format!("Failed to query devices: {}", e))?;
+    let Some(name) = device_name else {
+        return devices.into_iter()
+            .next()
+            .ok_or_else(|| "No video devices found".to_string());
+    };
+    let candidates: Vec<(String, DeviceId)> = devices.iter().map(|d| (d.human_name(), camera_id(d))).collect();
+    match settings::match_device(&candidates, name, device_id) {
+        DeviceMatch::Found(i) => Ok(devices.swap_remove(i)),
+        DeviceMatch::Ambiguous(count) => Err(format!("{} devices are named '{}', select one again in the settings", count, name)),
+        DeviceMatch::NotFound => Err(format!("Device '{}' not found", name)),
+    }
+}
+
+// 設定画面に出すフォーマットの順序 (先頭が既定値になる)
+const CAPABILITY_FORMATS: [FrameFormat; 4] = [FrameFormat::YUYV, FrameFormat::MJPEG, FrameFormat::NV12, FrameFormat::RAWRGB];
+
+// デバイスが報告した対応モードを (フォーマット名, [(幅, 高さ, FPS)]) にまとめる
+// 問い合わせに失敗したフォーマットは推測で埋めずに除外する
+pub fn collect_capabilities(
+    fourccs: Result<Vec<FrameFormat>, NokhwaError>,
+    mut modes_of: impl FnMut(FrameFormat) -> Result<HashMap<Resolution, Vec<u32>>, NokhwaError>,
+) -> Result<DeviceCapabilities, String> {
+    // フォーマット一覧が取れないバックエンドでは既知のフォーマットを個別に問い合わせる
+    let fourccs = fourccs.unwrap_or_else(|e| {
+        println!("Warning: Failed to query device formats ({}), probing known formats", e);
+        CAPABILITY_FORMATS.to_vec()
+    });
+
+    let mut result: DeviceCapabilities = Vec::new();
+    for ff in CAPABILITY_FORMATS.into_iter().filter(|ff| fourccs.contains(ff)) {
+        let Some(format) = pixel_format_of(ff) else { continue };
+        let resolution_map = match modes_of(ff) {
+            Ok(map) => map,
+            Err(e) => {
+                println!("Debug: No {} modes reported: {}", format.name(), e);
+                continue;
+            }
+        };
+
+        let mut modes: Vec<(u32, u32, u32)> = resolution_map.iter()
+            .flat_map(|(res, fps_list)| fps_list.iter().map(move |fps| (res.width_x, res.height_y, *fps)))
+            .filter(|&(w, h, fps)| w > 0 && h > 0 && fps > 0)
+            .collect();
+        modes.sort();
+        modes.dedup();
+        // 解像度の大きい順、同じ解像度なら FPS の大きい順
+        modes.sort_by(|a, b| (b.0 * b.1).cmp(&(a.0 * a.1)).then(b.2.cmp(&a.2)));
+
+        if !modes.is_empty() {
+            result.push((format.name().to_string(), modes));
+        }
+    }
+
+    if result.is_empty() {
+        return Err("Device reported no usable formats".to_string());
+    }
+    Ok(result)
+}
+
+// nokhwa (Media Foundation / V4L2) 経由のキャプチャデバイス
+pub struct CameraSource {
+    backend: CaptureBackend,
+    info: CameraInfo,
+    camera_format: CameraFormat,
+    camera: Option<CallbackCamera>,
+}
+
+impl CameraSource {
+    pub fn new(backend: CaptureBackend, device_name: Option<&str>, device_id: Option<&DeviceId>, resolution: Option<(u32, u32)>, format: Option<&str>, fps: Option<u32>) -> Result<Self, String> {
+        let info = find_camera(backend, device_name, device_id)?;
+
+        // Windows Media Foundationでの問題を回避するフォーマット設定
+        let camera_format = if let Some((w,h)) = resolution {
+            let ff = match format.and_then(PixelFormat::from_name) {
+                Some(PixelFormat::Yuy2) => FrameFormat::YUYV,
+                // MJPEGはコールバック内の専用デコーダで処理
+                Some(PixelFormat::Mjpeg) => FrameFormat::MJPEG,
+                Some(PixelFormat::Nv12) => FrameFormat::NV12,
+                // RGB24はWindows MFで問題があるため、YUYVフォールバック
+                Some(PixelFormat::Rgb24) => FrameFormat::YUYV, // YUYVで代替してRGB変換
+                // I420/P010はnokhwa経由では要求できないためYUYVで代替
+                // (デバイスの対応モードには出てこない、テストパターンから切り替えた時の設定が残っている場合のみ)
+                Some(other @ (PixelFormat::I420 | PixelFormat::P010)) => {
+                    println!("Warning: {} cannot be requested from this backend, using YUY2", other.name());
+                    FrameFormat::YUYV
+                }
+                // 未指定: デフォルトフォーマット
+                None => FrameFormat::YUYV,
+            };
+            let fps_value = fps.unwrap_or(60).clamp(15, 120);
+
+            // フォールバック戦略: 安定したYUYVを使用
+            CameraFormat::new(Resolution::new(w,h), ff, fps_value)
+        } else {
+            // 高解像度優先（安定性のためYUYVを使用）
+            CameraFormat::new(Resolution::new(1280, 720), FrameFormat::YUYV, 60)
+        };
+
+        Ok(Self { backend, info, camera_format, camera: None })
+    }
+}
+
+impl VideoSource for CameraSource {
+    fn start(&mut self, mut sink: FrameSink) -> Result<(), String> {
+        let callback = move |frame: nokhwa::Buffer| {
+            let res = frame.resolution();
+            // nokhwa 標準デコード (専用変換の対象外・失敗時)
+            let decode = |buf: &mut [u8]| frame.decode_image_to_buffer::<RgbAFormat>(buf).is_ok();
+            sink(RawFrame {
+                width: res.width_x as usize,
+                height: res.height_y as usize,
+                format: pixel_format_of(frame.source_frame_format()),
+                data: frame.buffer(),
+                fallback: Some(&decode),
+            });
+        };
+
+        let requested_format = RequestedFormat::new::<RgbFormat>(RequestedFormatType::Closest(self.camera_format));
+        let mut camera = CallbackCamera::with_backend(self.info.index().clone(), requested_format, api_backend(self.backend), callback)
+            .map_err(|e| format!("Failed to create camera: {}", e))?;
+
+        // 開いたついでに対応モードを記録 (設定画面が使用中のデバイスを開き直さずに済む)
+        let device_id = camera_id(&self.info);
+        if !video::has_cached_capabilities(self.backend, &self.info.human_name(), Some(&device_id)) {
+            let caps = collect_capabilities(camera.compatible_fourcc(), |ff| camera.compatible_list_by_resolution(ff));
+            video::store_capabilities(self.backend, &self.info.human_name(), Some(&device_id), caps);
+        }
+
+        camera.open_stream()
+            .map_err(|e| format!("Failed to open camera stream: {}", e))?;
+
+        self.camera = Some(camera);
+        Ok(())
+    }
+
+    fn stop(&mut self) {
+        if let Some(mut camera) = self.camera.take() {
+            let _ = camera.stop_stream();
+        }
+    }
+
+    fn device_id(&self) -> Option<DeviceId> {
+        Some(camera_id(&self.info))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn error() -> NokhwaError {
+        NokhwaError::GeneralError("not supported".to_string())
+    }
+
+    // (幅, 高さ, [FPS]) の一覧を問い合わせ結果の形にする
+    fn modes(list: &[(u32, u32, &[u32])]) -> HashMap<Resolution, Vec<u32>> {
+        list.iter().map(|&(w, h, fps)| (Resolution::new(w, h), fps.to_vec())).collect()
+    }
+
+    #[test]
+    fn probes_known_formats_when_fourcc_query_fails() {
+        let mut queried = Vec::new();
+        let caps = collect_capabilities(Err(error()), |ff| {
+            queried.push(ff);
+            match ff {
+                FrameFormat::MJPEG => Ok(modes(&[(1280, 720, &[30])])),
+                _ => Err(error()),
+            }
+        });
+        assert_eq!(queried, CAPABILITY_FORMATS);
+        assert_eq!(caps, Ok(vec![("MJPEG".to_string(), vec![(1280, 720, 30)])]));
+    }
+
+    #[test]
+    fn drops_formats_whose_mode_query_fails() {
+        // 失敗したフォーマットは推測した値で埋めない (順序はデバイスの報告順ではなく既定の順)
+        let caps = collect_capabilities(Ok(vec![FrameFormat::NV12, FrameFormat::MJPEG, FrameFormat::YUYV]), |ff| match ff {
+            FrameFormat::MJPEG => Err(error()),
+            _ => Ok(modes(&[(640, 480, &[60])])),
+        });
+        assert_eq!(caps, Ok(vec![
+            ("YUY2".to_string(), vec![(640, 480, 60)]),
+            ("NV12".to_string(), vec![(640, 480, 60)]),
+        ]));
+    }
+
+    #[test]
+    fn filters_dedups_and_sorts_modes() {
+        let caps = collect_capabilities(Ok(vec![FrameFormat::YUYV]), |_| Ok(modes(&[
+            (0, 480, &[30]),
+            (640, 0, &[30]),
+            (640, 480, &[30, 0, 60, 30]),
+            (1280, 720, &[60]),
+            (1920, 1080, &[30, 30]),
+        ])));
+        assert_eq!(caps, Ok(vec![("YUY2".to_string(), vec![(1920, 1080, 30), (1280, 720, 60), (640, 480, 60), (640, 480, 30)])]));
+        // 使えるモードが残らなかったフォーマットも除外
+        let caps = collect_capabilities(Ok(vec![FrameFormat::YUYV, FrameFormat::MJPEG]), |ff| match ff {
+            FrameFormat::YUYV => Ok(modes(&[(0, 0, &[30]), (640, 480, &[0])])),
+            _ => Ok(modes(&[(640, 480, &[30])])),
+        });
+        assert_eq!(caps, Ok(vec![("MJPEG".to_string(), vec![(640, 480, 30)])]));
+    }
+
+    #[test]
+    fn unknown_when_every_query_fails() {
+        let unknown = Err("Device reported no usable formats".to_string());
+        assert_eq!(collect_capabilities(Err(error()), |_| Err(error())), unknown);
+        assert_eq!(collect_capabilities(Ok(vec![FrameFormat::YUYV]), |_| Err(error())), unknown);
+        assert_eq!(collect_capabilities(Ok(Vec::new()), |_| Ok(modes(&[(640, 480, &[30])]))), unknown);
+    }
+}
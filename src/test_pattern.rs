@@ -0,0 +1,301 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use crate::convert::PixelFormat;
+use crate::source::{FramePacer, FrameSink, RawFrame, VideoSource};
+use crate::video::DeviceCapabilities;
+
+// 内蔵テストパターン (キャプチャデバイスなしで映像パイプラインを確認するためのソース)
+// 上段: 75% カラーバー、中段: 逆順バー、下段: 流れるランプとフレームカウンタ
+
+// デバイス一覧に表示する名前 (設定ファイルにもこの名前で保存される)
+pub const TEST_PATTERN_DEVICE: &str = "テストパターン";
+
+// 75% カラーバー (白, 黄, シアン, 緑, マゼンタ, 赤, 青)
+pub const BARS: [[u8; 3]; 7] = [
+    [191, 191, 191], [191, 191, 0], [0, 191, 191], [0, 191, 0],
+    [191, 0, 191], [191, 0, 0], [0, 0, 191],
+];
+// 中段の逆順バー (青, 黒, マゼンタ, 黒, シアン, 黒, 白)
+const REVERSE_BARS: [[u8; 3]; 7] = [
+    [0, 0, 191], [0, 0, 0], [191, 0, 191], [0, 0, 0],
+    [0, 191, 191], [0, 0, 0], [191, 191, 191],
+];
+
+// 3x5 ドットの数字フォント (各行の下位3bit)
+const DIGITS: [[u8; 5]; 10] = [
+    [0b111, 0b101, 0b101, 0b101, 0b111], [0b010, 0b110, 0b010, 0b010, 0b111],
+    [0b111, 0b001, 0b111, 0b100, 0b111], [0b111, 0b001, 0b111, 0b001, 0b111],
+    [0b101, 0b101, 0b111, 0b001, 0b001], [0b111, 0b100, 0b111, 0b001, 0b111],
+    [0b111, 0b100, 0b111, 0b101, 0b111], [0b111, 0b001, 0b010, 0b010, 0b010],
+    [0b111, 0b101, 0b111, 0b101, 0b111], [0b111, 0b101, 0b111, 0b001, 0b111],
+];
+
+// 生成できるフォーマット
+pub const FORMATS: [PixelFormat; 4] = [PixelFormat::Yuy2, PixelFormat::Nv12, PixelFormat::I420, PixelFormat::P010];
+
+// デバイス能力として返す解像度・FPS (get_device_capabilities と同じ形式)
+pub fn capabilities() -> DeviceCapabilities {
+    let modes = vec![
+        (3840, 2160, 30), (1920, 1080, 60), (1920, 1080, 30),
+        (1280, 720, 60), (1280, 720, 30), (640, 480, 60), (640, 480, 30),
+    ];
+    FORMATS.iter().map(|f| (f.name().to_string(), modes.clone())).collect()
+}
+
+// 上段バーの i 本目の中心 x 座標
+pub fn bar_center_x(width: usize, i: usize) -> usize {
+    (2 * i + 1) * width / 14
+}
+
+// 1フレーム分の RGB 描画と YUV 変換
+struct PatternGenerator {
+    width: usize,
+    height: usize,
+    format: PixelFormat,
+    // (kr, kb) : 変換側の Auto 判定に合わせた係数 (720p 以上は BT.709)
+    kr: f32,
+    kb: f32,
+    rgb: Vec<u8>,
+    out: Vec<u8>,
+}
+
+impl PatternGenerator {
+    fn new(width: usize, height: usize, format: PixelFormat) -> Self {
+        let (kr, kb) = if width >= 1280 || height >= 720 { (0.2126, 0.0722) } else { (0.299, 0.114) };
+        let mut gen = Self { width, height, format, kr, kb, rgb: vec![0; width * height * 3], out: vec![0; format.frame_len(width, height)] };
+        gen.draw_bars();
+        gen.encode(0);
+        gen
+    }
+
+    // 上段・中段 (フレームごとに変化しない部分) を描画
+    fn draw_bars(&mut self) {
+        let (w, h) = (self.width, self.height);
+        for y in 0..h * 3 / 4 {
+            let bars = if y < h * 2 / 3 { &BARS } else { &REVERSE_BARS };
+            for x in 0..w {
+                let c = bars[(x * 7 / w).min(6)];
+                self.rgb[(y * w + x) * 3..][..3].copy_from_slice(&c);
+            }
+        }
+    }
+
+    // 下段: 左 5/7 に流れるグレーランプ、右 2/7 にフレーム番号
+    fn draw_dynamic(&mut self, frame: u64) {
+        let (w, h) = (self.width, self.height);
+        let top = h * 3 / 4;
+        let ramp_w = w * 5 / 7;
+        let shift = (frame as usize * 4) % ramp_w.max(1);
+        for y in top..h {
+            let row = &mut self.rgb[y * w * 3..][..w * 3];
+            for x in 0..w {
+                let v = if x < ramp_w { (((x + shift) % ramp_w) * 255 / ramp_w.max(1)) as u8 } else { 0 };
+                row[x * 3..][..3].copy_from_slice(&[v, v, v]);
+            }
+        }
+        // 数字は下段の高さの半分程度の大きさで描画
+        let scale = ((h - top) / 10).max(1);
+        let text = format!("{:06}", frame % 1_000_000);
+        let text_w = text.len() * 4 * scale;
+        let x0 = ramp_w + (w - ramp_w).saturating_sub(text_w) / 2;
+        let y0 = top + ((h - top).saturating_sub(5 * scale)) / 2;
+        for (i, ch) in text.bytes().enumerate() {
+            let glyph = DIGITS[(ch - b'0') as usize];
+            for (gy, bits) in glyph.iter().enumerate() {
+                for gx in 0..3 {
+                    if bits & (0b100 >> gx) == 0 { continue; }
+                    for dy in 0..scale {
+                        for dx in 0..scale {
+                            let x = x0 + (i * 4 + gx) * scale + dx;
+                            let y = y0 + gy * scale + dy;
+                            if x < w && y < h {
+                                self.rgb[(y * w + x) * 3..][..3].copy_from_slice(&[235, 235, 235]);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    // RGB -> YUV (リミテッドレンジ、8bit)
+    fn yuv(&self, p: &[u8]) -> (f32, f32, f32) {
+        let (r, g, b) = (p[0] as f32 / 255.0, p[1] as f32 / 255.0, p[2] as f32 / 255.0);
+        let y = self.kr * r + (1.0 - self.kr - self.kb) * g + self.kb * b;
+        let u = (b - y) / (2.0 * (1.0 - self.kb));
+        let v = (r - y) / (2.0 * (1.0 - self.kr));
+        (16.0 + 219.0 * y, 128.0 + 224.0 * u, 128.0 + 224.0 * v)
+    }
+
+    // 指定位置の色差 (2x1 / 2x2 ブロックの平均)
+    fn chroma(&self, x: usize, y: usize, rows: usize) -> (f32, f32) {
+        let (w, h) = (self.width, self.height);
+        let (mut su, mut sv, mut n) = (0.0, 0.0, 0.0);
+        for yy in y..(y + rows).min(h) {
+            for xx in x..(x + 2).min(w) {
+                let (_, u, v) = self.yuv(&self.rgb[(yy * w + xx) * 3..]);
+                su += u;
+                sv += v;
+                n += 1.0;
+            }
+        }
+        (su / n, sv / n)
+    }
+
+    // 下段の先頭行 (4:2:0 の色差行に合わせて偶数)
+    fn dynamic_top(&self) -> usize {
+        (self.height * 3 / 4) & !1
+    }
+
+    // 描画済み RGB の first_row 行目以降を出力フォーマットに変換 (first_row は偶数)
+    fn encode(&mut self, first_row: usize) {
+        let (w, h) = (self.width, self.height);
+        let (cw, ch) = (w.div_ceil(2), h.div_ceil(2));
+        let q = |v: f32| v.round().clamp(0.0, 255.0) as u8;
+        let mut out = std::mem::take(&mut self.out);
+        match self.format {
+            PixelFormat::Yuy2 => {
+                for y in first_row..h {
+                    for cx in 0..w / 2 {
+                        let (y0, _, _) = self.yuv(&self.rgb[(y * w + cx * 2) * 3..]);
+                        let (y1, _, _) = self.yuv(&self.rgb[(y * w + cx * 2 + 1) * 3..]);
+                        let (u, v) = self.chroma(cx * 2, y, 1);
+                        out[(y * w + cx * 2) * 2..][..4].copy_from_slice(&[q(y0), q(u), q(y1), q(v)]);
+                    }
+                }
+            }
+            PixelFormat::Nv12 | PixelFormat::I420 | PixelFormat::P010 => {
+                let p010 = self.format == PixelFormat::P010;
+                // P010 は 10bit を上位詰め (8bit 値 << 8 相当)
+                let put = |out: &mut [u8], i: usize, v: u8| {
+                    if p010 {
+                        out[i * 2..][..2].copy_from_slice(&((v as u16) << 8).to_le_bytes());
+                    } else {
+                        out[i] = v;
+                    }
+                };
+                for y in first_row..h {
+                    for x in 0..w {
+                        let (yv, _, _) = self.yuv(&self.rgb[(y * w + x) * 3..]);
+                        put(&mut out, y * w + x, q(yv));
+                    }
+                }
+                let base = w * h;
+                for cy in first_row / 2..ch {
+                    for cx in 0..cw {
+                        let (u, v) = self.chroma(cx * 2, cy * 2, 2);
+                        if self.format == PixelFormat::I420 {
+                            put(&mut out, base + cy * cw + cx, q(u));
+                            put(&mut out, base + cw * ch + cy * cw + cx, q(v));
+                        } else {
+                            put(&mut out, base + (cy * cw + cx) * 2, q(u));
+                            put(&mut out, base + (cy * cw + cx) * 2 + 1, q(v));
+                        }
+                    }
+                }
+            }
+            _ => {}
+        }
+        self.out = out;
+    }
+}
+
+pub struct TestPatternSource {
+    width: usize,
+    height: usize,
+    format: PixelFormat,
+    fps: u32,
+    // 指定フレーム数の後に配信を止める (信号断のシミュレーション)
+    stall_after: Option<u64>,
+    // 指定フレーム数の後に出力サイズだけ変える (報告するサイズは開始時のまま)
+    switch_after: Option<(u64, (usize, usize))>,
+    running: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl TestPatternSource {
+    pub fn new(resolution: Option<(u32, u32)>, format: Option<&str>, fps: Option<u32>) -> Self {
+        let (w, h) = resolution.unwrap_or((1280, 720));
+        // YUY2 は偶数幅が必要
+        let width = (w.clamp(16, 7680) as usize) & !1;
+        let height = h.clamp(16, 4320) as usize;
+        let format = match format.and_then(PixelFormat::from_name) {
+            Some(f) if FORMATS.contains(&f) => f,
+            Some(other) => {
+                println!("Warning: Test pattern cannot generate {}, using YUY2", other.name());
+                PixelFormat::Yuy2
+            }
+            None => PixelFormat::Yuy2,
+        };
+        let fps = fps.unwrap_or(60).clamp(1, 240);
+        Self { width, height, format, fps, stall_after: None, switch_after: None, running: Arc::new(AtomicBool::new(false)), thread: None }
+    }
+
+    // frames 枚送った後はスレッドを動かしたままフレームを送らない (止まったキャプチャカードの再現)
+    pub fn with_stall_after(mut self, frames: u64) -> Self {
+        self.stall_after = Some(frames);
+        self
+    }
+
+    // frames 枚送った後に入力側で解像度が変わった状態を再現する
+    // (Media Foundation 等と同じく、フレームのサイズはネゴシエーション時のまま報告される)
+    pub fn with_resolution_change(mut self, frames: u64, resolution: (u32, u32)) -> Self {
+        self.switch_after = Some((frames, ((resolution.0 as usize) & !1, resolution.1 as usize)));
+        self
+    }
+}
+
+impl VideoSource for TestPatternSource {
+    fn start(&mut self, mut sink: FrameSink) -> Result<(), String> {
+        self.stop();
+        let (width, height, format, fps, stall_after, switch_after) = (self.width, self.height, self.format, self.fps, self.stall_after, self.switch_after);
+        println!("Debug: Test pattern {}x{} {} @{}fps", width, height, format.name(), fps);
+        let running = self.running.clone();
+        running.store(true, Ordering::SeqCst);
+        let thread = std::thread::Builder::new()
+            .name("test-pattern".to_string())
+            .spawn(move || {
+                let mut gen = PatternGenerator::new(width, height, format);
+                let mut pacer = FramePacer::new(fps as f64);
+                let mut frame = 0u64;
+                let mut stalled = false;
+                while running.load(Ordering::SeqCst) {
+                    if stall_after.is_some_and(|n| frame >= n) {
+                        if !stalled {
+                            println!("Debug: Test pattern stalled after {} frames", frame);
+                            stalled = true;
+                        }
+                        pacer.wait();
+                        continue;
+                    }
+                    if let Some((_, (w, h))) = switch_after.filter(|(n, _)| frame == *n) {
+                        println!("Debug: Test pattern switched to {}x{} after {} frames", w, h, frame);
+                        gen = PatternGenerator::new(w, h, format);
+                    }
+                    // 変化するのは下段のみ
+                    gen.draw_dynamic(frame);
+                    gen.encode(gen.dynamic_top());
+                    sink(RawFrame { width, height, format: Some(format), data: &gen.out, fallback: None });
+                    frame += 1;
+                    pacer.wait();
+                }
+            })
+            .map_err(|e| format!("Failed to start test pattern thread: {}", e))?;
+        self.thread = Some(thread);
+        Ok(())
+    }
+
+    fn stop(&mut self) {
+        self.running.store(false, Ordering::SeqCst);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+impl Drop for TestPatternSource {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
@@ -1,13 +1,13 @@
 use eframe::egui;
 use std::sync::{Arc, Mutex};
-use crate::settings::AppSettings;
+use crate::settings::{match_device, AppSettings, CaptureBackend, ColorRange, ColorSpace, CropSettings, CrtEffect, DeinterlaceMode, DeviceId, DeviceMatch, DisplayAspect, FieldOrder, LutInterpolation, PictureAdjustments, PixelAspect, Rotation, ScalingMode, Upscaler};
+use crate::video::{Capabilities, DeviceCapabilities, VideoCapture};
 use std::sync::atomic::{AtomicBool, Ordering};
-use std::collections::HashMap;
 
 static TEST_SOUND_FLAG: AtomicBool = AtomicBool::new(false);
 
-// デバイス能力のキャッシュ
-static DEVICE_CAPABILITIES_CACHE: std::sync::OnceLock<Mutex<HashMap<String, Vec<(String, Vec<(u32, u32, u32)>)>>>> = std::sync::OnceLock::new();
+// 対応モードが不明なデバイスで選べるフォーマット (キャプチャ側で要求できるもの)
+const REQUESTABLE_FORMATS: [&str; 4] = ["YUY2", "MJPEG", "NV12", "RGB24"];
 
 // 一時保存用の設定
 static TEMP_SETTINGS: std::sync::OnceLock<Mutex<Option<AppSettings>>> = std::sync::OnceLock::new();
@@ -24,7 +24,8 @@ pub fn show_settings_dialog(
     settings: &Arc<Mutex<AppSettings>>, 
     show_hotkey_dialog: &mut bool,
     input_devices: &[String],
-    output_devices: &[String]
+    output_devices: &[String],
+    lut_error: Option<&str>
 ) -> bool {
     use std::sync::OnceLock;
     static SELECTED_TAB: OnceLock<Mutex<i32>> = OnceLock::new();
@@ -64,7 +65,7 @@ pub fn show_settings_dialog(
                 egui::ScrollArea::vertical().show(ui, |ui| {
                     if let Ok(tab) = selected_tab.lock() {
                         match *tab {
-                            0 => show_device_settings_tab(ui, &mut settings, input_devices, output_devices),
+                            0 => show_device_settings_tab(ui, &mut settings, input_devices, output_devices, lut_error),
                             1 => show_screenshot_settings_tab(ui, &mut settings, show_hotkey_dialog),
                             _ => {}
                         }
@@ -126,67 +127,122 @@ pub fn show_settings_dialog(
     false
 }
 
-fn show_device_settings_tab(ui: &mut egui::Ui, settings: &mut AppSettings, input_devices: &[String], output_devices: &[String]) {
+fn show_device_settings_tab(ui: &mut egui::Ui, settings: &mut AppSettings, input_devices: &[String], output_devices: &[String], lut_error: Option<&str>) {
     ui.heading("デバイス設定");
     ui.add_space(10.0);
     
-    // キャッシュの初期化
-    let capabilities_cache = DEVICE_CAPABILITIES_CACHE.get_or_init(|| Mutex::new(HashMap::new()));
-    
     // ビデオ設定
     ui.group(|ui| {
     ui.strong("ビデオ設定");
         ui.add_space(5.0);
         
+        // キャプチャ API 選択 (変更するとデバイス一覧が切り替わる)
+        ui.horizontal(|ui| {
+            ui.label("キャプチャAPI:");
+            egui::ComboBox::from_id_source("backend_combo")
+                .selected_text(backend_label(settings.video.backend))
+                .show_ui(ui, |ui| {
+                    for backend in crate::source::available_backends() {
+                        ui.selectable_value(&mut settings.video.backend, backend, backend_label(backend));
+                    }
+                });
+        });
+
         // ビデオデバイス選択
-        let video_devices = crate::video::VideoCapture::list_devices();
+        let video_devices = VideoCapture::list_devices(settings.video.backend);
         let selected_device = settings.video.device_name.clone().unwrap_or_default();
+        let is_file = crate::file_source::file_path_of(&selected_device).is_some();
         
+        // 保存されたデバイスを一覧と照合 (同名デバイスは識別情報で区別)
+        let candidates: Vec<(String, DeviceId)> = video_devices.iter()
+            .map(|d| (d.name.clone(), d.id.clone().unwrap_or_default()))
+            .collect();
+        let device_names: Vec<String> = candidates.iter().map(|(name, _)| name.clone()).collect();
+        let device_match = match_device(&candidates, &selected_device, settings.video.device_id.as_ref());
+        
+        // ファイル入力はファイル名のみ表示
+        let selected_text = match crate::file_source::file_path_of(&selected_device) {
+            Some(path) => format!("ファイル: {}", std::path::Path::new(path).file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default()),
+            None if selected_device.is_empty() => "デバイスを選択...".to_string(),
+            None => match device_match {
+                DeviceMatch::Found(i) => device_label(&device_names, i),
+                _ => selected_device.clone(),
+            },
+        };
         let mut device_changed = false;
-        egui::ComboBox::from_label("ビデオデバイス")
-            .selected_text(if selected_device.is_empty() { "デバイスを選択..." } else { &selected_device })
-            .show_ui(ui, |ui| {
-                for (name, description) in &video_devices {
-                    let display_text = if description.is_empty() { 
-                        name.clone() 
-                    } else { 
-                        format!("{} ({})", name, description) 
-                    };
-                    if ui.selectable_label(settings.video.device_name.as_ref() == Some(name), display_text).clicked()
-                        && settings.video.device_name.as_ref() != Some(name) {
-                        settings.video.device_name = Some(name.clone());
-                        device_changed = true;
+        ui.horizontal(|ui| {
+            egui::ComboBox::from_label("ビデオデバイス")
+                .selected_text(selected_text)
+                .show_ui(ui, |ui| {
+                    for (i, device) in video_devices.iter().enumerate() {
+                        let label = device_label(&device_names, i);
+                        let display_text = if device.description.is_empty() { 
+                            label 
+                        } else { 
+                            format!("{} ({})", label, device.description) 
+                        };
+                        let selected = !is_file && device_match == DeviceMatch::Found(i);
+                        if ui.selectable_label(selected, display_text).clicked() && !selected {
+                            settings.video.device_name = Some(device.name.clone());
+                            settings.video.device_id = device.id.clone();
+                            device_changed = true;
+                        }
                     }
+                });
+
+            // 録画ファイル (.y4m / 生の YUYV・NV12) を入力にする
+            if ui.button("ファイルを開く...").clicked() {
+                if let Some(file) = rfd::FileDialog::new()
+                    .add_filter("映像ファイル", &crate::file_source::FILE_EXTENSIONS)
+                    .pick_file()
+                {
+                    settings.video.device_name = Some(crate::file_source::file_device_name(&file));
+                    settings.video.device_id = None;
+                    device_changed = true;
                 }
-            });
+            }
+        });
+        if !is_file && !device_changed && !selected_device.is_empty() {
+            show_device_match_warning(ui, &selected_device, device_match);
+        }
 
         // デバイス変更時の処理
         if device_changed {
-            // 新しく選択されたデバイス名を取得
+            // 新しく選択されたデバイスの対応モードから最初のものを選択
             let new_device = settings.video.device_name.clone().unwrap_or_default();
-            
-            // デバイス能力を取得（キャッシュ確認）
-            if let Ok(mut cache) = capabilities_cache.lock() {
-                if !cache.contains_key(&new_device) && !new_device.is_empty() {
-                    // キャッシュにない場合は取得
-                    ui.spinner(); // 読み込み中表示
-                    if let Ok(caps) = crate::video::VideoCapture::get_device_capabilities(Some(&new_device)) {
-                        cache.insert(new_device.clone(), caps);
-                    }
-                }
-            }
-            
-            // デフォルトのフォーマットを設定
-            if let Ok(cache) = capabilities_cache.lock() {
-                if let Some(caps) = cache.get(&new_device) {
-                    // 最初のフォーマットを選択
-                    if let Some((format, _)) = caps.first() {
+            if !new_device.is_empty() {
+                if let Capabilities::Known(caps) = VideoCapture::device_capabilities(settings.video.backend, &new_device, settings.video.device_id.as_ref()) {
+                    if let Some((format, modes)) = caps.first() {
                         settings.video.format = Some(format.clone());
+                        if let Some((w, h, fps)) = modes.first() {
+                            settings.video.resolution = Some((*w, *h));
+                            settings.video.fps = Some(*fps);
+                        }
                     }
                 }
             }
         }
         
+        // 選択中デバイスの対応モード (デバイスへの問い合わせは初回のみ、以降はキャッシュ)
+        let selected_device = settings.video.device_name.clone().unwrap_or_default();
+        let capabilities = (!selected_device.is_empty())
+            .then(|| VideoCapture::device_capabilities(settings.video.backend, &selected_device, settings.video.device_id.as_ref()));
+        let known_caps = match &capabilities {
+            Some(Capabilities::Known(caps)) => Some(caps),
+            _ => None,
+        };
+        
+        // 取得できなかった場合は推測せず、その旨を表示して手入力させる
+        if let Some(Capabilities::Unknown(reason)) = &capabilities {
+            ui.horizontal(|ui| {
+                ui.colored_label(egui::Color32::YELLOW, "対応モード: 不明").on_hover_text(reason);
+                if ui.button("再取得").clicked() {
+                    VideoCapture::forget_capabilities(settings.video.backend, &selected_device, settings.video.device_id.as_ref());
+                }
+            });
+            ui.label("デバイスから対応モードを取得できませんでした。解像度・フレームレートを直接指定してください。");
+        }
+        
         // フォーマット選択（フォーマットが起点）
         let mut format_changed = false;
         ui.horizontal(|ui| {
@@ -196,41 +252,26 @@ fn show_device_settings_tab(ui: &mut egui::Ui, settings: &mut AppSettings, input
             egui::ComboBox::from_id_source("format_combo")
                 .selected_text(&current_format)
                 .show_ui(ui, |ui| {
-                    // キャッシュからフォーマット一覧を取得
-                    if let Ok(cache) = capabilities_cache.lock() {
-                        if let Some(caps) = cache.get(&selected_device) {
-                            for (format, _) in caps {
-                                if ui.selectable_value(&mut settings.video.format, Some(format.clone()), format).clicked() {
-                                    format_changed = true;
-                                }
-                            }
-                        } else {
-                            // キャッシュがない場合はデフォルト
-                            ui.selectable_value(&mut settings.video.format, Some("YUY2".to_string()), "YUY2");
-                            ui.selectable_value(&mut settings.video.format, Some("MJPEG".to_string()), "MJPEG");
-                            ui.selectable_value(&mut settings.video.format, Some("RGB24".to_string()), "RGB24");
+                    // 対応モード不明の場合は要求可能なフォーマットをすべて表示
+                    let formats: Vec<&str> = match known_caps {
+                        Some(caps) => caps.iter().map(|(format, _)| format.as_str()).collect(),
+                        None => REQUESTABLE_FORMATS.to_vec(),
+                    };
+                    for format in formats {
+                        if ui.selectable_value(&mut settings.video.format, Some(format.to_string()), format).clicked() {
+                            format_changed = true;
                         }
                     }
                 });
         });
         
+        let current_format = settings.video.format.clone().unwrap_or_default();
+        
         // フォーマット変更時に解像度をリセット
         if format_changed {
-            if let Ok(cache) = capabilities_cache.lock() {
-                if let Some(caps) = cache.get(&selected_device) {
-                    if let Some(current_format) = &settings.video.format {
-                        // 現在のフォーマットに対応する最初の解像度を選択
-                        for (format, resolutions) in caps {
-                            if format == current_format {
-                                if let Some((w, h, fps)) = resolutions.first() {
-                                    settings.video.resolution = Some((*w, *h));
-                                    settings.video.fps = Some(*fps);
-                                }
-                                break;
-                            }
-                        }
-                    }
-                }
+            if let Some((w, h, fps)) = known_caps.and_then(|caps| modes_of(caps, &current_format).first()) {
+                settings.video.resolution = Some((*w, *h));
+                settings.video.fps = Some(*fps);
             }
         }
         
@@ -240,66 +281,43 @@ fn show_device_settings_tab(ui: &mut egui::Ui, settings: &mut AppSettings, input
             ui.label("解像度:");
             let current_resolution = settings.video.resolution.unwrap_or((1280, 720));
             
-            egui::ComboBox::from_id_source("resolution_combo")
-                .selected_text(format!("{}x{}", current_resolution.0, current_resolution.1))
-                .show_ui(ui, |ui| {
-                    if let Ok(cache) = capabilities_cache.lock() {
-                        if let Some(caps) = cache.get(&selected_device) {
-                            if let Some(current_format) = &settings.video.format {
-                                // 現在のフォーマットに対応する解像度一覧
-                                let mut unique_resolutions = std::collections::HashSet::<(u32, u32)>::new();
-                                for (format, resolutions) in caps {
-                                    if format == current_format {
-                                        for (w, h, _) in resolutions {
-                                            unique_resolutions.insert((*w, *h));
-                                        }
-                                    }
+            match known_caps {
+                Some(caps) => {
+                    egui::ComboBox::from_id_source("resolution_combo")
+                        .selected_text(format!("{}x{}", current_resolution.0, current_resolution.1))
+                        .show_ui(ui, |ui| {
+                            // 対応モードは解像度の大きい順に並んでいる
+                            let mut resolutions: Vec<(u32, u32)> = Vec::new();
+                            for &(w, h, _) in modes_of(caps, &current_format) {
+                                if !resolutions.contains(&(w, h)) {
+                                    resolutions.push((w, h));
                                 }
-                                
-                                // ソートして表示
-                                let mut sorted_resolutions: Vec<_> = unique_resolutions.into_iter().collect();
-                                sorted_resolutions.sort_by(|a, b| {
-                                    let size_a = a.0 * a.1;
-                                    let size_b = b.0 * b.1;
-                                    size_b.cmp(&size_a)
-                                });
-                                
-                                for (w, h) in sorted_resolutions {
-                                    if ui.selectable_value(&mut settings.video.resolution, Some((w, h)), format!("{}x{}", w, h)).clicked() {
-                                        resolution_changed = true;
-                                    }
+                            }
+                            for (w, h) in resolutions {
+                                if ui.selectable_value(&mut settings.video.resolution, Some((w, h)), format!("{}x{}", w, h)).clicked() {
+                                    resolution_changed = true;
                                 }
                             }
-                        } else {
-                            // デフォルトの解像度
-                            ui.selectable_value(&mut settings.video.resolution, Some((1920, 1080)), "1920x1080");
-                            ui.selectable_value(&mut settings.video.resolution, Some((1280, 720)), "1280x720");
-                            ui.selectable_value(&mut settings.video.resolution, Some((640, 480)), "640x480");
-                        }
+                        });
+                }
+                None => {
+                    // 手入力
+                    let (mut w, mut h) = current_resolution;
+                    let mut changed = ui.add(egui::DragValue::new(&mut w).clamp_range(160..=7680)).changed();
+                    ui.label("x");
+                    changed |= ui.add(egui::DragValue::new(&mut h).clamp_range(120..=4320)).changed();
+                    if changed {
+                        settings.video.resolution = Some((w, h));
                     }
-                });
+                }
+            }
         });
         
         // 解像度変更時にFPSをリセット
         if resolution_changed {
-            if let Ok(cache) = capabilities_cache.lock() {
-                if let Some(caps) = cache.get(&selected_device) {
-                    if let Some(current_format) = &settings.video.format {
-                        if let Some((w, h)) = settings.video.resolution {
-                            // 現在のフォーマットと解像度に対応する最初のFPSを選択
-                            for (format, resolutions) in caps {
-                                if format == current_format {
-                                    for (res_w, res_h, fps) in resolutions {
-                                        if *res_w == w && *res_h == h {
-                                            settings.video.fps = Some(*fps);
-                                            break;
-                                        }
-                                    }
-                                    break;
-                                }
-                            }
-                        }
-                    }
+            if let (Some(caps), Some((w, h))) = (known_caps, settings.video.resolution) {
+                if let Some((_, _, fps)) = modes_of(caps, &current_format).iter().find(|(res_w, res_h, _)| *res_w == w && *res_h == h) {
+                    settings.video.fps = Some(*fps);
                 }
             }
         }
@@ -309,43 +327,210 @@ fn show_device_settings_tab(ui: &mut egui::Ui, settings: &mut AppSettings, input
             ui.label("フレームレート:");
             let current_fps = settings.video.fps.unwrap_or(30);
             
-            egui::ComboBox::from_id_source("fps_combo")
-                .selected_text(format!("{} fps", current_fps))
-                .show_ui(ui, |ui| {
-                    if let Ok(cache) = capabilities_cache.lock() {
-                        if let Some(caps) = cache.get(&selected_device) {
-                            if let Some(current_format) = &settings.video.format {
-                                if let Some((w, h)) = settings.video.resolution {
-                                    // 現在のフォーマットと解像度に対応するFPS一覧
-                                    let mut available_fps = Vec::new();
-                                    for (format, resolutions) in caps {
-                                        if format == current_format {
-                                            for (res_w, res_h, fps) in resolutions {
-                                                if *res_w == w && *res_h == h {
-                                                    available_fps.push(*fps);
-                                                }
-                                            }
-                                        }
-                                    }
-                                    
-                                    // 重複を削除してソート
-                                    available_fps.sort();
-                                    available_fps.dedup();
-                                    available_fps.reverse(); // 大きい順
-                                    
-                                    for fps in available_fps {
-                                        ui.selectable_value(&mut settings.video.fps, Some(fps), format!("{} fps", fps));
-                                    }
+            match known_caps {
+                Some(caps) => {
+                    egui::ComboBox::from_id_source("fps_combo")
+                        .selected_text(format!("{} fps", current_fps))
+                        .show_ui(ui, |ui| {
+                            if let Some((w, h)) = settings.video.resolution {
+                                // 現在のフォーマットと解像度に対応するFPS一覧 (大きい順)
+                                let mut available_fps: Vec<u32> = modes_of(caps, &current_format).iter()
+                                    .filter(|(res_w, res_h, _)| *res_w == w && *res_h == h)
+                                    .map(|(_, _, fps)| *fps)
+                                    .collect();
+                                available_fps.dedup();
+                                
+                                for fps in available_fps {
+                                    ui.selectable_value(&mut settings.video.fps, Some(fps), format!("{} fps", fps));
                                 }
                             }
-                        } else {
-                            // デフォルトのFPS
-                            ui.selectable_value(&mut settings.video.fps, Some(30), "30 fps");
-                            ui.selectable_value(&mut settings.video.fps, Some(60), "60 fps");
-                        }
+                        });
+                }
+                None => {
+                    // 手入力 (キャプチャ側で 15-120 に制限される)
+                    let mut fps = current_fps;
+                    if ui.add(egui::DragValue::new(&mut fps).clamp_range(15..=120).suffix(" fps")).changed() {
+                        settings.video.fps = Some(fps);
+                    }
+                }
+            }
+        });
+        
+        // 色空間 (YUV -> RGB 変換行列)
+        ui.horizontal(|ui| {
+            ui.label("色空間:");
+            egui::ComboBox::from_id_source("color_space_combo")
+                .selected_text(color_space_label(settings.video.color_space))
+                .show_ui(ui, |ui| {
+                    for space in [ColorSpace::Auto, ColorSpace::Bt601, ColorSpace::Bt709, ColorSpace::Bt2020] {
+                        ui.selectable_value(&mut settings.video.color_space, space, color_space_label(space));
+                    }
+                });
+        });
+        
+        // 信号レンジ
+        ui.horizontal(|ui| {
+            ui.label("レンジ:");
+            ui.selectable_value(&mut settings.video.color_range, ColorRange::Limited, "リミテッド (16-235)");
+            ui.selectable_value(&mut settings.video.color_range, ColorRange::Full, "フル (0-255)");
+        });
+        
+        // インターレース解除 (480i/1080i の櫛状のノイズ対策)
+        ui.horizontal(|ui| {
+            ui.label("インターレース解除:");
+            egui::ComboBox::from_id_source("deinterlace_combo")
+                .selected_text(deinterlace_label(settings.video.deinterlace))
+                .show_ui(ui, |ui| {
+                    for mode in [DeinterlaceMode::Weave, DeinterlaceMode::Bob, DeinterlaceMode::Blend, DeinterlaceMode::MotionAdaptive] {
+                        ui.selectable_value(&mut settings.video.deinterlace, mode, deinterlace_label(mode));
                     }
                 });
         });
+        if settings.video.deinterlace != DeinterlaceMode::Weave {
+            ui.horizontal(|ui| {
+                ui.label("フィールド順:");
+                ui.selectable_value(&mut settings.video.field_order, FieldOrder::Auto, "自動");
+                ui.selectable_value(&mut settings.video.field_order, FieldOrder::Tff, "トップが先 (TFF)");
+                ui.selectable_value(&mut settings.video.field_order, FieldOrder::Bff, "ボトムが先 (BFF)");
+            });
+        }
+
+        // 回転・反転 (表示とスクリーンショットに適用)
+        ui.horizontal(|ui| {
+            ui.label("回転:");
+            for (rotation, label) in [(Rotation::None, "なし"), (Rotation::Cw90, "90°"), (Rotation::Cw180, "180°"), (Rotation::Cw270, "270°")] {
+                ui.selectable_value(&mut settings.video.rotation, rotation, label);
+            }
+            ui.separator();
+            ui.checkbox(&mut settings.video.flip_horizontal, "左右反転");
+            ui.checkbox(&mut settings.video.flip_vertical, "上下反転");
+        });
+        
+        // 画質調整 (デバイスごとに保存、スライダーの操作は即座に反映)
+        ui.add_space(5.0);
+        ui.horizontal(|ui| {
+            ui.label("画質調整 (このデバイスのみ):");
+            if ui.button("リセット").clicked() {
+                settings.video.profile_mut().picture = PictureAdjustments::default();
+            }
+        });
+        let mut picture = settings.video.profile().picture;
+        egui::Grid::new("picture_grid").num_columns(2).show(ui, |ui| {
+            ui.label("明るさ:");
+            ui.add(egui::Slider::new(&mut picture.brightness, -100.0..=100.0));
+            ui.end_row();
+            ui.label("コントラスト:");
+            ui.add(egui::Slider::new(&mut picture.contrast, 0.0..=200.0).suffix("%"));
+            ui.end_row();
+            ui.label("彩度:");
+            ui.add(egui::Slider::new(&mut picture.saturation, 0.0..=200.0).suffix("%"));
+            ui.end_row();
+            ui.label("色相:");
+            ui.add(egui::Slider::new(&mut picture.hue, -180.0..=180.0).suffix("°"));
+            ui.end_row();
+            ui.label("ガンマ:");
+            ui.add(egui::Slider::new(&mut picture.gamma, 0.1..=3.0));
+            ui.end_row();
+        });
+        // 変更があった時だけ書き込む (無調整のデバイスの項目を増やさない)
+        if picture != settings.video.profile().picture {
+            settings.video.profile_mut().picture = picture;
+        }
+
+        // 3D LUT (.cube) による色補正 (画質調整の後に適用、スライダーの操作は即座に反映)
+        ui.add_space(5.0);
+        ui.label("3D LUT:");
+        let lut = &mut settings.video.lut;
+        egui::Grid::new("lut_grid").num_columns(2).show(ui, |ui| {
+            ui.label("ファイル:");
+            ui.horizontal(|ui| {
+                let name = lut.path.as_ref()
+                    .and_then(|p| p.file_name())
+                    .map(|n| n.to_string_lossy().into_owned())
+                    .unwrap_or_else(|| "なし".to_string());
+                ui.label(name);
+                if ui.button("LUT ファイルを選択...").clicked() {
+                    if let Some(file) = rfd::FileDialog::new().add_filter("3D LUT", &["cube"]).pick_file() {
+                        lut.path = Some(file);
+                    }
+                }
+                if lut.path.is_some() && ui.button("解除").clicked() {
+                    lut.path = None;
+                }
+            });
+            ui.end_row();
+            ui.label("強さ:");
+            ui.add_enabled(lut.path.is_some(), egui::Slider::new(&mut lut.strength, 0.0..=100.0).suffix("%"));
+            ui.end_row();
+            ui.label("補間:");
+            ui.horizontal(|ui| {
+                ui.selectable_value(&mut lut.interpolation, LutInterpolation::Tetrahedral, "四面体");
+                ui.selectable_value(&mut lut.interpolation, LutInterpolation::Trilinear, "三線形");
+            });
+            ui.end_row();
+        });
+        ui.checkbox(&mut lut.split_view, "補正前後を比較 (画面の左半分に補正前を表示)");
+        if let (Some(error), true) = (lut_error, lut.path.is_some()) {
+            ui.colored_label(egui::Color32::RED, format!("LUT を読み込めません: {}", error));
+        }
+
+        // 表示範囲の切り抜き (デバイスごとに保存、右クリックメニューの「クロップ調整」でも変更可能)
+        ui.add_space(5.0);
+        ui.horizontal(|ui| {
+            ui.label("クロップ (このデバイスのみ):");
+            if ui.button("リセット").clicked() {
+                settings.video.profile_mut().crop = CropSettings::default();
+            }
+        });
+        let mut crop = settings.video.profile().crop;
+        egui::Grid::new("crop_grid").num_columns(2).show(ui, |ui| {
+            ui.label("オーバースキャン除去:");
+            ui.add(egui::Slider::new(&mut crop.overscan, 0.0..=10.0).suffix("%"));
+            ui.end_row();
+            ui.label("左 / 右:");
+            ui.horizontal(|ui| {
+                ui.add(egui::DragValue::new(&mut crop.left).clamp_range(0..=4096).suffix("px"));
+                ui.add(egui::DragValue::new(&mut crop.right).clamp_range(0..=4096).suffix("px"));
+            });
+            ui.end_row();
+            ui.label("上 / 下:");
+            ui.horizontal(|ui| {
+                ui.add(egui::DragValue::new(&mut crop.top).clamp_range(0..=4096).suffix("px"));
+                ui.add(egui::DragValue::new(&mut crop.bottom).clamp_range(0..=4096).suffix("px"));
+            });
+            ui.end_row();
+        });
+        if crop != settings.video.profile().crop {
+            settings.video.profile_mut().crop = crop;
+        }
+
+        // レトロゲーム機向けの後処理 (デバイスごとに保存)
+        ui.add_space(5.0);
+        ui.label("レトロフィルタ (このデバイスのみ):");
+        let mut retro = settings.video.profile().retro;
+        egui::Grid::new("retro_grid").num_columns(2).show(ui, |ui| {
+            ui.label("拡大フィルタ:");
+            ui.horizontal(|ui| {
+                for upscaler in [Upscaler::None, Upscaler::Scale2x, Upscaler::Scale3x, Upscaler::Xbr2x] {
+                    ui.selectable_value(&mut retro.upscaler, upscaler, upscaler_label(upscaler));
+                }
+            });
+            ui.end_row();
+            ui.label("CRT 効果:");
+            ui.horizontal(|ui| {
+                for effect in [CrtEffect::None, CrtEffect::Scanlines, CrtEffect::ApertureGrille, CrtEffect::ShadowMask] {
+                    ui.selectable_value(&mut retro.crt, effect, crt_effect_label(effect));
+                }
+            });
+            ui.end_row();
+            ui.label("効果の強さ:");
+            ui.add_enabled(retro.crt != CrtEffect::None, egui::Slider::new(&mut retro.strength, 0.0..=100.0).suffix("%"));
+            ui.end_row();
+        });
+        ui.checkbox(&mut retro.apply_to_screenshots, "スクリーンショットにも適用");
+        if retro != settings.video.profile().retro {
+            settings.video.profile_mut().retro = retro;
+        }
     });
     
     ui.add_space(15.0);
@@ -356,27 +541,50 @@ fn show_device_settings_tab(ui: &mut egui::Ui, settings: &mut AppSettings, input
         ui.add_space(5.0);
         
         // オーディオ入力デバイス選択 - キャッシュリストを使用
-        let current_input_device = settings.audio.input_device_name.clone().unwrap_or_default();
+        let input_match = audio_device_match(input_devices, settings.audio.input_device_name.as_deref(), settings.audio.input_device_index);
+        let input_text = match input_match {
+            Some(DeviceMatch::Found(i)) => device_label(input_devices, i),
+            _ => settings.audio.input_device_name.clone().unwrap_or_else(|| "デバイスを選択...".to_string()),
+        };
         
         egui::ComboBox::from_label("オーディオ入力デバイス")
-            .selected_text(if current_input_device.is_empty() { "デバイスを選択..." } else { &current_input_device })
+            .selected_text(input_text)
             .show_ui(ui, |ui| {
-                for device_name in input_devices {
-                    ui.selectable_value(&mut settings.audio.input_device_name, Some(device_name.clone()), device_name);
+                for (i, (name, id)) in crate::audio::device_candidates(input_devices).into_iter().enumerate() {
+                    if ui.selectable_label(input_match == Some(DeviceMatch::Found(i)), device_label(input_devices, i)).clicked() {
+                        settings.audio.input_device_name = Some(name);
+                        settings.audio.input_device_index = id.index.map(|n| n as usize);
+                    }
                 }
             });
+        if let (Some(name), Some(m)) = (&settings.audio.input_device_name, input_match) {
+            show_device_match_warning(ui, name, m);
+        }
         
         // オーディオ出力デバイス選択 - キャッシュリストを使用
-        let current_output_device = settings.audio.output_device_name.clone().unwrap_or_default();
+        let output_match = audio_device_match(output_devices, settings.audio.output_device_name.as_deref(), settings.audio.output_device_index);
+        let output_text = match output_match {
+            Some(DeviceMatch::Found(i)) => device_label(output_devices, i),
+            _ => settings.audio.output_device_name.clone().unwrap_or_else(|| "デフォルト".to_string()),
+        };
         
         egui::ComboBox::from_label("オーディオ出力デバイス")
-            .selected_text(if current_output_device.is_empty() { "デフォルト" } else { &current_output_device })
+            .selected_text(output_text)
             .show_ui(ui, |ui| {
-                ui.selectable_value(&mut settings.audio.output_device_name, None, "デフォルト");
-                for device_name in output_devices {
-                    ui.selectable_value(&mut settings.audio.output_device_name, Some(device_name.clone()), device_name);
+                if ui.selectable_label(settings.audio.output_device_name.is_none(), "デフォルト").clicked() {
+                    settings.audio.output_device_name = None;
+                    settings.audio.output_device_index = None;
+                }
+                for (i, (name, id)) in crate::audio::device_candidates(output_devices).into_iter().enumerate() {
+                    if ui.selectable_label(output_match == Some(DeviceMatch::Found(i)), device_label(output_devices, i)).clicked() {
+                        settings.audio.output_device_name = Some(name);
+                        settings.audio.output_device_index = id.index.map(|n| n as usize);
+                    }
                 }
             });
+        if let (Some(name), Some(m)) = (&settings.audio.output_device_name, output_match) {
+            show_device_match_warning(ui, name, m);
+        }
         
         // サンプルレート
         ui.horizontal(|ui| {
@@ -427,6 +635,44 @@ fn show_device_settings_tab(ui: &mut egui::Ui, settings: &mut AppSettings, input
         ui.add_space(5.0);
         
     ui.checkbox(&mut settings.ui.maintain_aspect_ratio, "アスペクト比を維持");
+        ui.checkbox(&mut settings.ui.fit_window_to_source, "入力解像度が変わったらウィンドウの縦横比を合わせる");
+        
+        ui.horizontal(|ui| {
+            ui.label("拡大方式:");
+            egui::ComboBox::from_id_source("scaling_combo")
+                .selected_text(scaling_label(settings.ui.scaling))
+                .show_ui(ui, |ui| {
+                    for mode in SCALING_MODES {
+                        ui.selectable_value(&mut settings.ui.scaling, mode, scaling_label(mode));
+                    }
+                });
+        });
+        ui.horizontal(|ui| {
+            ui.label("画素の縦横比:");
+            egui::ComboBox::from_id_source("pixel_aspect_combo")
+                .selected_text(pixel_aspect_label(settings.ui.pixel_aspect))
+                .show_ui(ui, |ui| {
+                    for aspect in PIXEL_ASPECTS {
+                        ui.selectable_value(&mut settings.ui.pixel_aspect, aspect, pixel_aspect_label(aspect));
+                    }
+                });
+        });
+        ui.horizontal(|ui| {
+            ui.label("表示の縦横比:");
+            let custom = settings.ui.custom_aspect;
+            egui::ComboBox::from_id_source("display_aspect_combo")
+                .selected_text(display_aspect_label(settings.ui.display_aspect, custom))
+                .show_ui(ui, |ui| {
+                    for aspect in DISPLAY_ASPECTS {
+                        ui.selectable_value(&mut settings.ui.display_aspect, aspect, display_aspect_label(aspect, custom));
+                    }
+                });
+            if settings.ui.display_aspect == DisplayAspect::Custom {
+                ui.add(egui::DragValue::new(&mut settings.ui.custom_aspect.0).clamp_range(1..=100));
+                ui.label(":");
+                ui.add(egui::DragValue::new(&mut settings.ui.custom_aspect.1).clamp_range(1..=100));
+            }
+        });
         
         ui.horizontal(|ui| {
             ui.label("初期音量:");
@@ -435,6 +681,123 @@ fn show_device_settings_tab(ui: &mut egui::Ui, settings: &mut AppSettings, input
     });
 }
 
+// 右クリックメニューで切り替える順番
+pub const SCALING_MODES: [ScalingMode; 5] = [ScalingMode::Auto, ScalingMode::Nearest, ScalingMode::Bilinear, ScalingMode::Integer, ScalingMode::SharpBilinear];
+pub const PIXEL_ASPECTS: [PixelAspect; 4] = [PixelAspect::Square, PixelAspect::Snes, PixelAspect::Ntsc, PixelAspect::Pal];
+pub const DISPLAY_ASPECTS: [DisplayAspect; 6] = [DisplayAspect::Auto, DisplayAspect::Standard, DisplayAspect::Wide, DisplayAspect::UltraWide, DisplayAspect::SuperWide, DisplayAspect::Custom];
+
+pub fn scaling_label(mode: ScalingMode) -> &'static str {
+    match mode {
+        ScalingMode::Auto => "自動",
+        ScalingMode::Nearest => "ニアレストネイバー",
+        ScalingMode::Bilinear => "バイリニア",
+        ScalingMode::Integer => "整数倍",
+        ScalingMode::SharpBilinear => "シャープバイリニア",
+    }
+}
+
+pub fn pixel_aspect_label(aspect: PixelAspect) -> &'static str {
+    match aspect {
+        PixelAspect::Square => "1:1 (補正なし)",
+        PixelAspect::Snes => "8:7 (レトロゲーム機)",
+        PixelAspect::Ntsc => "10:11 (NTSC SD)",
+        PixelAspect::Pal => "12:11 (PAL SD)",
+    }
+}
+
+pub fn display_aspect_label(aspect: DisplayAspect, custom: (u32, u32)) -> String {
+    match aspect {
+        DisplayAspect::Auto => "自動".to_string(),
+        DisplayAspect::Standard => "4:3".to_string(),
+        DisplayAspect::Wide => "16:9".to_string(),
+        DisplayAspect::UltraWide => "21:9".to_string(),
+        DisplayAspect::SuperWide => "32:9".to_string(),
+        DisplayAspect::Custom => format!("カスタム ({}:{})", custom.0, custom.1),
+    }
+}
+
+fn upscaler_label(upscaler: Upscaler) -> &'static str {
+    match upscaler {
+        Upscaler::None => "なし",
+        Upscaler::Scale2x => "Scale2x",
+        Upscaler::Scale3x => "Scale3x",
+        Upscaler::Xbr2x => "xBR 2x",
+    }
+}
+
+fn crt_effect_label(effect: CrtEffect) -> &'static str {
+    match effect {
+        CrtEffect::None => "なし",
+        CrtEffect::Scanlines => "走査線",
+        CrtEffect::ApertureGrille => "アパーチャグリル",
+        CrtEffect::ShadowMask => "シャドウマスク",
+    }
+}
+
+fn color_space_label(space: ColorSpace) -> &'static str {
+    match space {
+        ColorSpace::Auto => "自動 (解像度で判定)",
+        ColorSpace::Bt601 => "BT.601",
+        ColorSpace::Bt709 => "BT.709",
+        ColorSpace::Bt2020 => "BT.2020",
+    }
+}
+
+fn deinterlace_label(mode: DeinterlaceMode) -> &'static str {
+    match mode {
+        DeinterlaceMode::Weave => "オフ (そのまま表示)",
+        DeinterlaceMode::Bob => "Bob (倍速)",
+        DeinterlaceMode::Blend => "ブレンド",
+        DeinterlaceMode::MotionAdaptive => "動き適応",
+    }
+}
+
+fn backend_label(backend: CaptureBackend) -> &'static str {
+    match backend {
+        CaptureBackend::Auto => "自動 (OS標準)",
+        CaptureBackend::MediaFoundation => "Media Foundation",
+        CaptureBackend::V4l2 => "V4L2",
+    }
+}
+
+// 同名デバイスが複数ある場合は何台目かを付けて表示
+fn device_label(names: &[String], i: usize) -> String {
+    let name = &names[i];
+    if names.iter().filter(|n| *n == name).count() > 1 {
+        let ordinal = names[..i].iter().filter(|n| *n == name).count();
+        format!("{} #{}", name, ordinal + 1)
+    } else {
+        name.clone()
+    }
+}
+
+// 保存されたオーディオデバイスを一覧と照合 (未選択なら None)
+fn audio_device_match(names: &[String], name: Option<&str>, index: Option<usize>) -> Option<DeviceMatch> {
+    let id = DeviceId { path: String::new(), index: index.map(|i| i as u32) };
+    name.map(|name| match_device(&crate::audio::device_candidates(names), name, Some(&id)))
+}
+
+// 保存されたデバイスを特定できない場合に理由を表示
+fn show_device_match_warning(ui: &mut egui::Ui, name: &str, device_match: DeviceMatch) {
+    match device_match {
+        DeviceMatch::Found(_) => {}
+        DeviceMatch::Ambiguous(count) => {
+            ui.colored_label(egui::Color32::YELLOW, format!("「{}」という名前のデバイスが{}台あり、どれを使うか特定できません。一覧から選び直してください。", name, count));
+        }
+        DeviceMatch::NotFound => {
+            ui.colored_label(egui::Color32::YELLOW, format!("保存されたデバイス「{}」が見つかりません。接続を確認するか、一覧から選び直してください。", name));
+        }
+    }
+}
+
+// フォーマットの対応モード一覧 (デバイスが報告していなければ空)
+fn modes_of<'a>(caps: &'a DeviceCapabilities, format: &str) -> &'a [(u32, u32, u32)] {
+    caps.iter()
+        .find(|(name, _)| name == format)
+        .map(|(_, modes)| modes.as_slice())
+        .unwrap_or(&[])
+}
+
 fn show_screenshot_settings_tab(ui: &mut egui::Ui, settings: &mut AppSettings, show_hotkey_dialog: &mut bool) {
     ui.heading("スクリーンショット設定");
     ui.add_space(10.0);
@@ -456,6 +819,7 @@ fn show_screenshot_settings_tab(ui: &mut egui::Ui, settings: &mut AppSettings, s
                 }
             }
         });
+        ui.checkbox(&mut settings.screenshot.keep_raw_pixels, "入力の画素数のまま保存 (オフにすると画素・表示の縦横比の補正を反映)");
     });
     
     ui.add_space(15.0);
@@ -529,6 +893,37 @@ fn show_screenshot_settings_tab(ui: &mut egui::Ui, settings: &mut AppSettings, s
         ui.add_space(5.0);
     ui.small("『ホットキー設定...』を押して希望のキーコンビネーションを入力してください。");
     });
+
+    ui.add_space(15.0);
+
+    // 録画 (MJPEG + PCM の AVI)
+    ui.group(|ui| {
+        ui.strong("録画");
+        ui.add_space(5.0);
+
+        ui.horizontal(|ui| {
+            ui.label("保存フォルダ:");
+            let mut folder_str = settings.recording.save_folder.as_ref().map(|p| p.to_string_lossy().to_string()).unwrap_or_default();
+            ui.add(egui::TextEdit::singleline(&mut folder_str).hint_text("スクリーンショットと同じ"));
+            settings.recording.save_folder = (!folder_str.is_empty()).then(|| std::path::PathBuf::from(folder_str));
+
+            if ui.button("参照...").clicked() {
+                if let Some(folder) = rfd::FileDialog::new().pick_folder() {
+                    settings.recording.save_folder = Some(folder);
+                }
+            }
+        });
+        ui.horizontal(|ui| {
+            ui.label("画質 (JPEG):");
+            ui.add(egui::Slider::new(&mut settings.recording.quality, 1..=100));
+        });
+        ui.checkbox(&mut settings.recording.record_audio, "音声も録画する (入力音声)");
+        ui.horizontal(|ui| {
+            ui.label("開始・停止キー:");
+            ui.add(egui::TextEdit::singleline(&mut settings.recording.hotkey).desired_width(120.0).hint_text("F9"));
+        });
+        ui.small("キーはウィンドウがアクティブな時のみ有効です (例: F9, Ctrl+R)。AVI の制限のため約 3.7GB で自動的に停止します。");
+    });
 }
 
 #[allow(static_mut_refs)]
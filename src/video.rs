@@ -1,53 +1,161 @@
 use nokhwa::pixel_format::RgbFormat;
-use nokhwa::utils::{RequestedFormat, RequestedFormatType, CameraFormat, Resolution, ApiBackend, FrameFormat};
-use nokhwa::CallbackCamera;
-use std::sync::{Arc, Mutex};
-use std::time::Instant;
+use nokhwa::utils::{RequestedFormat, RequestedFormatType, CameraFormat, Resolution, FrameFormat};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::sync::mpsc::{SyncSender, TrySendError};
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
 use std::collections::VecDeque;
-// YUY2 -> RGB24 高速変換 (最適化版)
+use std::borrow::Cow;
+use crate::settings::{CaptureBackend, ColorRange, ColorSpace, DeinterlaceMode, DeviceId, FieldOrder, PictureAdjustments};
+use crate::deinterlace::Deinterlacer;
+use crate::picture::PictureProcessor;
+use crate::lut::LutProcessor;
+use crate::convert::{self, PixelFormat, YuvMatrix};
+use crate::source::{self, CameraSource, FrameSink, RawFrame, VideoSource};
+use crate::test_pattern::{self, TestPatternSource, TEST_PATTERN_DEVICE};
+use crate::file_source::{self, FileSource};
+use egui::{Color32, ColorImage};
 
-fn yuy2_to_rgb_naive(width: usize, height: usize, src: &[u8]) -> Vec<u8> {
-    let mut out = vec![0u8; width * height * 3];
-    
-    // 安全確保: 偶数幅前提 (YUYV ペア)
-    let src_chunks = src.chunks_exact(4);
-    let out_chunks = out.chunks_exact_mut(6);
-    
-    for (src_chunk, out_chunk) in src_chunks.zip(out_chunks) {
-        let y0 = src_chunk[0] as i32;
-        let u  = src_chunk[1] as i32;
-        let y1 = src_chunk[2] as i32;
-        let v  = src_chunk[3] as i32;
-        
-        // BT.601 変換 (整数演算で高速化)
-        let c0 = y0 - 16;
-        let c1 = y1 - 16;
-        let d = u - 128;
-        let e = v - 128;
-        
-        // 係数を1024倍して整数演算に変換 (1.164 ≈ 1192/1024)
-        let r0 = (1192 * c0 + 1634 * e) >> 10;
-        let g0 = (1192 * c0 - 401 * d - 833 * e) >> 10;
-        let b0 = (1192 * c0 + 2066 * d) >> 10;
-        let r1 = (1192 * c1 + 1634 * e) >> 10;
-        let g1 = (1192 * c1 - 401 * d - 833 * e) >> 10;
-        let b1 = (1192 * c1 + 2066 * d) >> 10;
-        
-        out_chunk[0] = r0.clamp(0, 255) as u8;
-        out_chunk[1] = g0.clamp(0, 255) as u8;
-        out_chunk[2] = b0.clamp(0, 255) as u8;
-        out_chunk[3] = r1.clamp(0, 255) as u8;
-        out_chunk[4] = g1.clamp(0, 255) as u8;
-        out_chunk[5] = b1.clamp(0, 255) as u8;
+// Color32 配列をバイト列として扱う (不透明なので乗算済み RGBA と同じ並び)
+fn pixel_bytes_mut(pixels: &mut [Color32]) -> &mut [u8] {
+    bytemuck::cast_slice_mut(pixels)
+}
+
+// 生フレームを RGBA に変換して out に書き込む (サイズ不足・デコード失敗時は None)
+// out はプールから渡された再利用バッファ、戻り値の幅・高さはデコード後の実サイズ
+// MJPEG は JPEG 規格どおりフルレンジ BT.601 でデコードされるため m は使わない
+fn decode_raw_frame(format: PixelFormat, width: usize, height: usize, src: &[u8], m: &YuvMatrix, out: &mut Vec<Color32>) -> Option<(usize, usize, DecodePath)> {
+    if format == PixelFormat::Mjpeg {
+        let (w, h) = mjpeg_to_rgba(src, out)?;
+        return Some((w, h, DecodePath::Mjpeg));
     }
-    
-    out
+    // RGB24等はnokhwa標準デコードに任せる
+    if !format.is_yuv() {
+        return None;
+    }
+    out.resize(width * height, Color32::BLACK);
+    if !convert::yuv_to_rgba_into(format, width, height, src, pixel_bytes_mut(out), m, convert::detect_simd()) {
+        return None;
+    }
+    Some((width, height, DecodePath::Fast))
+}
+
+// MJPEG ペイロードの前後にあるゴミ (先頭パディング、EOI 後のゼロ埋め) を取り除く
+fn trim_mjpeg_payload(src: &[u8]) -> &[u8] {
+    let start = src.windows(2).position(|w| w == [0xFF, 0xD8]).unwrap_or(0);
+    let end = src.windows(2).rposition(|w| w == [0xFF, 0xD9]).map(|p| p + 2).unwrap_or(src.len());
+    if start < end { &src[start..end] } else { src }
+}
+
+// MJPEG -> RGBA デコード (Huffmanテーブル省略のMJPEGにも対応)
+// out (再利用バッファ) に RGB / グレーでデコードしてから、その場で RGBA に広げる
+// 戻り値はデコード後の実サイズ (ネゴシエーション結果と異なる場合がある)
+fn mjpeg_to_rgba(src: &[u8], out: &mut Vec<Color32>) -> Option<(usize, usize)> {
+    use image::ImageDecoder;
+    let payload = trim_mjpeg_payload(src);
+    let decoder = image::codecs::jpeg::JpegDecoder::new(std::io::Cursor::new(payload)).ok()?;
+    let channels = match decoder.color_type() {
+        image::ColorType::Rgb8 => 3,
+        image::ColorType::L8 => 1,
+        _ => return None,
+    };
+    let (w, h) = decoder.dimensions();
+    let (w, h) = (w as usize, h as usize);
+    if w == 0 || h == 0 { return None; }
+    out.resize(w * h, Color32::BLACK);
+    let bytes = pixel_bytes_mut(out);
+    decoder.read_image(&mut bytes[..w * h * channels]).ok()?;
+    // 後ろから広げれば、書き込み先 (4バイト/画素) がまだ読んでいない入力を上書きしない
+    for i in (0..w * h).rev() {
+        let s = i * channels;
+        let (r, g, b) = if channels == 3 { (bytes[s], bytes[s + 1], bytes[s + 2]) } else { (bytes[s], bytes[s], bytes[s]) };
+        bytes[i * 4..i * 4 + 4].copy_from_slice(&[r, g, b, 255]);
+    }
+    Some((w, h))
 }
 
+// バッファ長から実際の解像度を推定するときの候補 (一般的な映像・PC 出力の解像度)
+const STANDARD_RESOLUTIONS: [(usize, usize); 17] = [
+    (640, 480), (720, 480), (720, 576), (800, 600), (1024, 768), (1280, 720), (1280, 800),
+    (1280, 1024), (1360, 768), (1366, 768), (1440, 900), (1600, 900), (1680, 1050),
+    (1920, 1080), (1920, 1200), (2560, 1440), (3840, 2160),
+];
+
+// 入力側の映像モードの変化 (フレームのバッファ長がネゴシエーションしたサイズと合わない)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ModeChange {
+    pub format: PixelFormat,
+    // ソースが報告しているサイズ
+    pub reported: (usize, usize),
+    // バッファ長から推定した実際のサイズ (推定できなければ None)
+    pub detected: Option<(usize, usize)>,
+}
+
+// 非圧縮フレームのバッファ長が報告サイズと合うか確認する
+// 足りない場合と、別の標準解像度にちょうど一致する場合はモード変更とみなす (行末パディング程度の超過は許容)
+fn detect_mode_change(format: PixelFormat, width: usize, height: usize, len: usize) -> Option<ModeChange> {
+    if format == PixelFormat::Mjpeg {
+        return None;
+    }
+    let expected = format.frame_len(width, height);
+    if len == expected {
+        return None;
+    }
+    let detected = STANDARD_RESOLUTIONS.iter().copied().find(|&(w, h)| format.frame_len(w, h) == len);
+    if len > expected && detected.is_none() {
+        return None;
+    }
+    Some(ModeChange { format, reported: (width, height), detected })
+}
+
+// フレームがどの経路でデコードされたか (統計用)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum DecodePath {
+    Fast,     // YUY2/NV12/I420/P010 高速パス (SIMD・並列変換)
+    Mjpeg,    // MJPEG 専用デコーダ
+    Fallback, // nokhwa 標準デコード
+}
+
+// デコード経路ごとのフレーム数 (キャプチャ開始から、倍速 bob の2枚目は数えない)
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct DecodeStats {
+    pub fast: u64,
+    pub mjpeg: u64,
+    pub fallback: u64,
+    // MJPEG 専用デコーダが失敗した回数 (フォールバックでデコードできたものも含む)
+    pub mjpeg_errors: u64,
+    // 直近のフレームの変換にかかった時間
+    pub last_decode_ms: f32,
+}
+
+// 画素データは参照カウントで共有 (UI・スクリーンショット間でコピーしない)
+// キャプチャスレッドでテクスチャ用の RGBA (不透明・乗算済み) まで変換済み
+#[derive(Clone)]
 pub struct VideoFrame {
     pub width: usize,
     pub height: usize,
-    pub data: Vec<u8>,
+    pub image: Arc<ColorImage>,
+    // 3D LUT の補正前後を比較表示するときの補正前の画像 (表示専用、比較しないときは None)
+    pub ungraded: Option<Arc<ColorImage>>,
+    // フレーム到着ごとに増加する通し番号 (同じフレームの再アップロード判定用)
+    pub generation: u64,
+}
+
+impl VideoFrame {
+    // (x, y, 幅, 高さ) の範囲の RGBA バイト列 (フレーム全体なら共有バッファをそのまま返す)
+    pub fn cropped_rgba(&self, (x, y, w, h): (u32, u32, u32, u32)) -> Cow<'_, [u8]> {
+        let raw = self.image.as_raw();
+        let (x, y, w, h) = (x as usize, y as usize, w as usize, h as usize);
+        if (x, y, w, h) == (0, 0, self.width, self.height) {
+            return Cow::Borrowed(raw);
+        }
+        let stride = self.width * 4;
+        let mut out = Vec::with_capacity(w * h * 4);
+        for row in raw.chunks_exact(stride).skip(y).take(h) {
+            out.extend_from_slice(&row[x * 4..(x + w) * 4]);
+        }
+        Cow::Owned(out)
+    }
 }
 
 struct FrameBuffer {
@@ -56,20 +164,96 @@ struct FrameBuffer {
     dirty: bool,
     last_frame_instant: Option<Instant>,
     frame_intervals: VecDeque<f32>, // ミリ秒
-    last_decode_ms: f32,
-    fast_count: u64,
-    fallback_count: u64,
+    stats: DecodeStats,
+    // MJPEG のデコード失敗を最後にログに出した時刻
+    last_mjpeg_error_log: Option<Instant>,
+    // 表示されずに破棄されたフレームのバッファ (次フレームで再利用)
+    spare_buffers: Vec<Vec<Color32>>,
+    generation: u64,
+    // キャプチャを開始した時刻 (停止中は None)
+    started_at: Option<Instant>,
+    // 検出した映像モード変更と、ワーカーが未処理か (同じ変更は開始ごとに1回だけ通知する)
+    mode_change: Option<ModeChange>,
+    mode_change_pending: bool,
+    // 録画中はフレームを到着時刻と一緒に録画スレッドへ渡す (録画が追いつかなければ捨てる)
+    recording_tap: Option<SyncSender<(Instant, VideoFrame)>>,
+    // 表示予定時刻を待っている倍速 bob の2枚目のフィールド
+    pending_field: Option<PendingField>,
+}
+
+// 倍速 bob の2枚目のフィールド (due を過ぎたら読み出し側か次のフレームの到着時に表示へ回す)
+struct PendingField {
+    due: Instant,
+    width: usize,
+    height: usize,
+    pixels: Vec<Color32>,
+    ungraded: Option<Vec<Color32>>,
 }
 
+// 再利用バッファの最大保持数
+const MAX_SPARE_BUFFERS: usize = 3;
+// MJPEG のデコード失敗のログを出す最短間隔
+const MJPEG_ERROR_LOG_INTERVAL: Duration = Duration::from_secs(5);
+
 impl FrameBuffer {
     fn new() -> Self {
-        Self { front: None, back: None, dirty: false, last_frame_instant: None, frame_intervals: VecDeque::with_capacity(120), last_decode_ms: 0.0, fast_count: 0, fallback_count: 0 }
+        Self { front: None, back: None, dirty: false, last_frame_instant: None, frame_intervals: VecDeque::with_capacity(120), stats: DecodeStats::default(), last_mjpeg_error_log: None, spare_buffers: Vec::new(), generation: 0, started_at: None, mode_change: None, mode_change_pending: false, recording_tap: None, pending_field: None }
     }
-    fn push_back(&mut self, frame: VideoFrame, decode_ms: f32, fast: bool) {
-        self.back = Some(frame);
+    // 変換先バッファをプールから取得 (なければ新規確保)
+    fn take_buffer(&mut self) -> Vec<Color32> {
+        self.spare_buffers.pop().unwrap_or_default()
+    }
+    // UI等がまだ参照しているバッファは回収せず、参照が外れた時点で解放される
+    fn recycle(&mut self, frame: Option<VideoFrame>) {
+        if let Some(frame) = frame {
+            if self.spare_buffers.len() < MAX_SPARE_BUFFERS {
+                if let Ok(image) = Arc::try_unwrap(frame.image) {
+                    self.spare_buffers.push(image.pixels);
+                }
+            }
+        }
+    }
+    fn push_back(&mut self, width: usize, height: usize, pixels: Vec<Color32>, ungraded: Option<Vec<Color32>>, decode_ms: f32, path: DecodePath) {
+        // 待っている2枚目のフィールドは新しいフレームより前に出す
+        self.flush_pending_field();
+        self.stats.last_decode_ms = decode_ms;
+        match path {
+            DecodePath::Fast => self.stats.fast += 1,
+            DecodePath::Mjpeg => self.stats.mjpeg += 1,
+            DecodePath::Fallback => self.stats.fallback += 1,
+        }
+        self.present(width, height, pixels, ungraded);
+    }
+    // 2枚目のフィールドを預ける (キャプチャのコールバックを表示予定時刻まで待たせない)
+    fn push_delayed(&mut self, field: PendingField) {
+        self.flush_pending_field();
+        self.pending_field = Some(field);
+    }
+    // 表示予定時刻を過ぎていれば2枚目のフィールドを表示に回す
+    fn present_due(&mut self, now: Instant) {
+        if self.pending_field.as_ref().is_some_and(|field| now >= field.due) {
+            self.flush_pending_field();
+        }
+    }
+    fn flush_pending_field(&mut self) {
+        if let Some(field) = self.pending_field.take() {
+            self.present(field.width, field.height, field.pixels, field.ungraded);
+        }
+    }
+    fn present(&mut self, width: usize, height: usize, pixels: Vec<Color32>, ungraded: Option<Vec<Color32>>) {
+        self.generation += 1;
+        let image = Arc::new(ColorImage { size: [width, height], pixels });
+        let ungraded = ungraded.map(|pixels| Arc::new(ColorImage { size: [width, height], pixels }));
+        let frame = VideoFrame { width, height, image, ungraded, generation: self.generation };
+        if let Some(tap) = &self.recording_tap {
+            if let Err(TrySendError::Disconnected(_)) = tap.try_send((Instant::now(), frame.clone())) {
+                self.recording_tap = None;
+            }
+        }
+        // 表示前に上書きされるフレームのバッファは回収
+        let dropped = self.back.replace(frame);
+        self.recycle(dropped);
         self.dirty = true;
-        self.last_decode_ms = decode_ms;
-        if fast { self.fast_count += 1; } else { self.fallback_count += 1; }
         let now = Instant::now();
         if let Some(prev) = self.last_frame_instant.replace(now) {
             let dt = now.duration_since(prev).as_secs_f32() * 1000.0;
@@ -78,164 +262,400 @@ impl FrameBuffer {
         }
     }
     fn take_front(&mut self) -> Option<VideoFrame> {
+        self.present_due(Instant::now());
         if self.dirty {
             std::mem::swap(&mut self.front, &mut self.back);
             self.dirty = false;
         }
-        // メモリリーク修正: cloneの代わりに参照を返すように変更
-        self.front.as_ref().map(|frame| VideoFrame {
-            width: frame.width,
-            height: frame.height,
-            data: frame.data.clone()
-        })
+        // 画素データは共有 (参照カウントの増加のみ)
+        self.front.clone()
     }
     
+    // MJPEG 専用デコーダが失敗した回数を記録 (フォールバック成否に関わらず)
+    // 壊れたフレームが続いてもログは MJPEG_ERROR_LOG_INTERVAL に1回だけ出す
+    fn record_mjpeg_error(&mut self) {
+        self.stats.mjpeg_errors += 1;
+        let now = Instant::now();
+        if self.last_mjpeg_error_log.is_none_or(|t| now.duration_since(t) >= MJPEG_ERROR_LOG_INTERVAL) {
+            self.last_mjpeg_error_log = Some(now);
+            println!("Debug: MJPEG decode failed {} times (fallback decoded: {})",
+                self.stats.mjpeg_errors, self.stats.fallback);
+        }
+    }
+
+    // 映像モードの変化を記録 (同じ変化は1回だけ通知する)
+    fn record_mode_change(&mut self, change: ModeChange) {
+        if self.mode_change != Some(change) {
+            match change.detected {
+                Some((w, h)) => println!("Warning: {} frame does not fit {}x{}, source switched to {}x{}",
+                    change.format.name(), change.reported.0, change.reported.1, w, h),
+                None => println!("Warning: {} frame does not fit {}x{}, source mode changed",
+                    change.format.name(), change.reported.0, change.reported.1),
+            }
+            self.mode_change = Some(change);
+            self.mode_change_pending = true;
+        }
+    }
+
     // メモリリーク防止: 古いフレームをクリア
     fn clear_old_frames(&mut self) {
         // 前回のフレームを破棄
         if self.back.is_some() && !self.dirty {
-            self.back = None;
+            let old = self.back.take();
+            self.recycle(old);
         }
     }
 
 }
 
+// フォーマット名ごとの (幅, 高さ, FPS) 一覧
+pub type DeviceCapabilities = Vec<(String, Vec<(u32, u32, u32)>)>;
+
+// 対応モードの中から指定サイズに最も近いものを選ぶ (同じサイズなら指定 FPS に近いもの)
+// 指定フォーマットのモードがなければ先頭のフォーマットから選ぶ
+pub fn closest_mode(caps: &DeviceCapabilities, format: &str, width: u32, height: u32, fps: u32) -> Option<(u32, u32, u32)> {
+    let (_, modes) = caps.iter().find(|(name, _)| name == format).or_else(|| caps.first())?;
+    modes.iter()
+        .copied()
+        .min_by_key(|&(w, h, f)| (w.abs_diff(width) + h.abs_diff(height), f.abs_diff(fps)))
+}
+
+// デバイスの対応モード (取得できなかった場合は推測せず不明として理由を持つ)
+#[derive(Debug, Clone)]
+pub enum Capabilities {
+    Known(DeviceCapabilities),
+    Unknown(String),
+}
+
+// (バックエンド, デバイス) ごとの対応モード
+// 失敗も記録して、設定画面の再描画のたびにデバイスを開き直さないようにする
+type CapabilitiesCache = HashMap<(CaptureBackend, String), Capabilities>;
+static CAPABILITIES_CACHE: OnceLock<Mutex<CapabilitiesCache>> = OnceLock::new();
+
+fn capabilities_cache() -> &'static Mutex<CapabilitiesCache> {
+    CAPABILITIES_CACHE.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+// キャッシュのキー (固有パスがあれば同名デバイスも区別する)
+fn capabilities_key(backend: CaptureBackend, device_name: &str, device_id: Option<&DeviceId>) -> (CaptureBackend, String) {
+    match device_id {
+        Some(id) if !id.path.is_empty() => (backend, id.path.clone()),
+        _ => (backend, device_name.to_string()),
+    }
+}
+
+pub fn has_cached_capabilities(backend: CaptureBackend, device_name: &str, device_id: Option<&DeviceId>) -> bool {
+    capabilities_cache().lock().is_ok_and(|cache| cache.contains_key(&capabilities_key(backend, device_name, device_id)))
+}
+
+pub fn store_capabilities(backend: CaptureBackend, device_name: &str, device_id: Option<&DeviceId>, caps: Result<DeviceCapabilities, String>) -> Capabilities {
+    if let Err(reason) = &caps {
+        println!("Warning: Capabilities of '{}' unknown: {}", device_name, reason);
+    }
+    let caps = match caps {
+        Ok(list) => Capabilities::Known(list),
+        Err(reason) => Capabilities::Unknown(reason),
+    };
+    if let Ok(mut cache) = capabilities_cache().lock() {
+        cache.insert(capabilities_key(backend, device_name, device_id), caps.clone());
+    }
+    caps
+}
+
+// デバイス一覧の1項目
+#[derive(Debug, Clone)]
+pub struct VideoDevice {
+    pub name: String,
+    pub description: String,
+    // 内蔵テストパターンは None
+    pub id: Option<DeviceId>,
+}
+
+// 映像信号の状態 (ウォッチドッグの判定用)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SignalState {
+    // キャプチャ停止中 (接続失敗を含む)
+    Stopped,
+    // 開始後まだフレームが届いていない (開始からの経過時間)
+    Waiting(Duration),
+    // フレーム受信中 (最後のフレームからの経過時間)
+    Receiving(Duration),
+}
+
+// フレームと信号状態の読み出し専用ハンドル
+// デバイスの開始・停止 (ワーカースレッド) とは独立しているため、接続中も UI を止めない
+#[derive(Clone)]
+pub struct FrameReader {
+    frames: Arc<Mutex<FrameBuffer>>,
+}
+
+impl FrameReader {
+    pub fn get_latest_frame(&self) -> Option<VideoFrame> {
+        self.frames.lock().ok().and_then(|mut fb| {
+            let frame = fb.take_front();
+            // メモリリーク防止: 定期的に古いフレームをクリア
+            fb.clear_old_frames();
+            frame
+        })
+    }
+
+    // 録画スレッドへの送り口をつなぐ (None で外す)
+    pub fn set_recording_tap(&self, tap: Option<SyncSender<(Instant, VideoFrame)>>) {
+        if let Ok(mut fb) = self.frames.lock() {
+            fb.recording_tap = tap;
+        }
+    }
+
+    // デコード経路ごとのフレーム数 (フォールバックや MJPEG の失敗が続いていないかの確認用)
+    pub fn decode_stats(&self) -> DecodeStats {
+        self.frames.lock().map(|fb| fb.stats).unwrap_or_default()
+    }
+
+    // 直近のフレーム間隔から求めた実際のフレームレート (受信し始めたばかりなら None)
+    pub fn measured_fps(&self) -> Option<f32> {
+        let fb = self.frames.lock().ok()?;
+        if fb.frame_intervals.len() < 10 {
+            return None;
+        }
+        let average = fb.frame_intervals.iter().sum::<f32>() / fb.frame_intervals.len() as f32;
+        (average > 0.0).then(|| 1000.0 / average)
+    }
+
+    pub fn signal_state(&self) -> SignalState {
+        let Ok(fb) = self.frames.lock() else {
+            return SignalState::Stopped;
+        };
+        match (fb.last_frame_instant, fb.started_at) {
+            (Some(last), _) => SignalState::Receiving(last.elapsed()),
+            (None, Some(started_at)) => SignalState::Waiting(started_at.elapsed()),
+            (None, None) => SignalState::Stopped,
+        }
+    }
+}
+
 pub struct VideoCapture {
-    camera: Option<CallbackCamera>,
+    source: Option<Box<dyn VideoSource>>,
     frames: Arc<Mutex<FrameBuffer>>,
     is_active: bool,
+    // 色空間・レンジ (コールバックと共有、再接続なしで変更可能)
+    color: Arc<Mutex<(ColorSpace, ColorRange)>>,
+    // インターレース解除の方式とフィールド順 (コールバックと共有、再接続なしで変更可能)
+    deinterlace: Arc<Mutex<(DeinterlaceMode, FieldOrder)>>,
+    // 画質調整 (コールバックと共有、再接続なしで変更可能)
+    picture: Arc<Mutex<PictureProcessor>>,
+    // 3D LUT による色補正と、補正前の画像も残すか (コールバックと共有、未設定なら None)
+    lut: Arc<Mutex<Option<(LutProcessor, bool)>>>,
+    // キャプチャデバイスの API (次回の start_capture から反映)
+    backend: CaptureBackend,
+}
+
+impl Default for VideoCapture {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl VideoCapture {
     pub fn new() -> Self {
-    Self { camera: None, frames: Arc::new(Mutex::new(FrameBuffer::new())), is_active: false }
+    Self {
+        source: None,
+        frames: Arc::new(Mutex::new(FrameBuffer::new())),
+        is_active: false,
+        color: Arc::new(Mutex::new((ColorSpace::Auto, ColorRange::Limited))),
+        deinterlace: Arc::new(Mutex::new((DeinterlaceMode::Weave, FieldOrder::Auto))),
+        picture: Arc::new(Mutex::new(PictureProcessor::default())),
+        lut: Arc::new(Mutex::new(None)),
+        backend: CaptureBackend::Auto,
+    }
+    }
+
+    pub fn set_color_format(&mut self, space: ColorSpace, range: ColorRange) {
+        if let Ok(mut color) = self.color.lock() { *color = (space, range); }
+    }
+
+    pub fn set_deinterlace(&mut self, mode: DeinterlaceMode, order: FieldOrder) {
+        if let Ok(mut deinterlace) = self.deinterlace.lock() { *deinterlace = (mode, order); }
+    }
+
+    pub fn set_picture(&mut self, adjustments: &PictureAdjustments) {
+        if let Ok(mut picture) = self.picture.lock() { *picture = PictureProcessor::new(adjustments); }
+    }
+
+    // keep_ungraded: 補正前後の比較表示用に補正前の画像もフレームに付ける
+    pub fn set_lut(&mut self, processor: Option<LutProcessor>, keep_ungraded: bool) {
+        if let Ok(mut lut) = self.lut.lock() { *lut = processor.map(|p| (p, keep_ungraded)); }
+    }
+    
+    pub fn set_backend(&mut self, backend: CaptureBackend) {
+        self.backend = backend;
     }
     
-    pub fn list_devices() -> Vec<(String, String)> {
-        match nokhwa::query(ApiBackend::MediaFoundation) {
+    pub fn list_devices(backend: CaptureBackend) -> Vec<VideoDevice> {
+        let mut devices: Vec<VideoDevice> = match nokhwa::query(source::api_backend(backend)) {
             Ok(devices) => {
                 devices.into_iter()
-                    .map(|info| (info.human_name().to_string(), info.description().to_string()))
+                    .map(|info| VideoDevice {
+                        name: info.human_name(),
+                        description: info.description(),
+                        id: Some(source::camera_id(&info)),
+                    })
                     .collect()
             }
             Err(_) => Vec::new(),
-        }
+        };
+        // 内蔵テストパターンは常に末尾に追加
+        devices.push(VideoDevice {
+            name: TEST_PATTERN_DEVICE.to_string(),
+            description: "内蔵ジェネレーター".to_string(),
+            id: None,
+        });
+        devices
     }
 
-    pub fn start_capture(&mut self, device_name: Option<&str>, resolution: Option<(u32, u32)>, format: Option<&str>, fps: Option<u32>) -> Result<(), String> {
+    pub fn start_capture(&mut self, device_name: Option<&str>, device_id: Option<&DeviceId>, resolution: Option<(u32, u32)>, format: Option<&str>, fps: Option<u32>) -> Result<(), String> {
         self.stop_capture();
-        
-        let devices = nokhwa::query(ApiBackend::MediaFoundation)
-            .map_err(|e| format!("Failed to query devices: {}", e))?;
-            
-        let device_info = if let Some(name) = device_name {
-            devices.into_iter()
-                .find(|d| d.human_name() == name)
-                .ok_or_else(|| format!("Device '{}' not found", name))?
-        } else {
-            devices.into_iter()
-                .next()
-                .ok_or("No video devices found")?
-        };
-        
-        // Windows Media Foundationでの問題を回避するフォーマット設定
-        let requested_format = if let Some((w,h)) = resolution {
-            let ff = match format.unwrap_or("") {
-                "YUY2" => FrameFormat::YUYV,
-                // MJPEGとRGB24はWindows MFで問題があるため、YUYVフォールバック
-                "MJPEG" => FrameFormat::YUYV, // YUYVで代替してMJPEGシミュレート
-                "RGB24" => FrameFormat::YUYV, // YUYVで代替してRGB変換
-                // 未指定: デフォルトフォーマット
-                "" => FrameFormat::YUYV,
-                _ => FrameFormat::YUYV,
-            };
-            let fps_value = fps.unwrap_or(60).clamp(15, 120);
-            
-            // フォールバック戦略: 安定したYUYVを使用
-            RequestedFormat::new::<RgbFormat>(RequestedFormatType::Closest(CameraFormat::new(
-                Resolution::new(w,h),
-                ff,
-                fps_value,
-            )))
+
+        let source: Box<dyn VideoSource> = if device_name == Some(TEST_PATTERN_DEVICE) {
+            Box::new(TestPatternSource::new(resolution, format, fps))
+        } else if let Some(path) = device_name.and_then(file_source::file_path_of) {
+            Box::new(FileSource::new(path, resolution, format, fps)?)
         } else {
-            // 高解像度優先（安定性のためYUYVを使用）
-            RequestedFormat::new::<RgbFormat>(RequestedFormatType::Closest(CameraFormat::new(
-                Resolution::new(1280, 720),
-                FrameFormat::YUYV,
-                60,
-            )))
+            Box::new(CameraSource::new(self.backend, device_name, device_id, resolution, format, fps)?)
         };
+        self.start_source(source)
+    }
+
+    // 生成済みのソースでキャプチャを開始
+    pub fn start_source(&mut self, mut source: Box<dyn VideoSource>) -> Result<(), String> {
+        self.stop_capture();
+        source.start(self.frame_sink())?;
+
+        self.source = Some(source);
+        self.is_active = true;
+        if let Ok(mut fb) = self.frames.lock() {
+            fb.started_at = Some(Instant::now());
+        }
         
-        let frame_callback = {
-            let fb = self.frames.clone();
-            move |frame: nokhwa::Buffer| {
-                let start = Instant::now();
-                let res = frame.resolution();
-                let width = res.width_x as usize;
-                let height = res.height_y as usize;
-                // YUY2 高速パス (naive) 試行
-                let mut used_fast = false;
-                #[allow(unused_mut)]
-                let mut rgb_vec: Option<Vec<u8>> = None;
-                // フレームフォーマットを取得して適切な処理を行う
-                let source_format = frame.source_frame_format();
-                
-                match source_format {
-                    FrameFormat::YUYV if width % 2 == 0 => {
-                        // YUY2の高速パス
-                        let raw_data = frame.buffer_bytes();
-                        if raw_data.len() >= width * height * 2 {
-                            let rgb = yuy2_to_rgb_naive(width, height, &raw_data);
-                            rgb_vec = Some(rgb);
-                            used_fast = true;
-                        }
-                    },
-
-                    _ => {
-                        // その他のフォーマットも標準デコード
-                        if let Ok(rgb_data) = frame.decode_image::<RgbFormat>() {
-                            rgb_vec = Some(rgb_data.into_raw());
-                        }
+        Ok(())
+    }
+
+    // UI スレッドに渡すフレーム読み出し用のハンドル
+    pub fn frame_reader(&self) -> FrameReader {
+        FrameReader { frames: self.frames.clone() }
+    }
+
+    pub fn signal_state(&self) -> SignalState {
+        self.frame_reader().signal_state()
+    }
+
+    // 入力側の映像モード変更を取り出す (なければ None)
+    pub fn take_mode_change(&self) -> Option<ModeChange> {
+        self.frames.lock().ok().and_then(|mut fb| {
+            std::mem::take(&mut fb.mode_change_pending).then_some(fb.mode_change).flatten()
+        })
+    }
+
+    // 現在開いている物理デバイスの識別情報
+    pub fn device_id(&self) -> Option<DeviceId> {
+        self.source.as_ref().and_then(|source| source.device_id())
+    }
+
+    // ソースから届いた生フレームを RGBA に変換して FrameBuffer に渡すコールバック
+    fn frame_sink(&self) -> FrameSink {
+        let fb = self.frames.clone();
+        let color = self.color.clone();
+        let deinterlace = self.deinterlace.clone();
+        let picture = self.picture.clone();
+        let lut = self.lut.clone();
+        let mut deinterlacer = Deinterlacer::new();
+        Box::new(move |frame: RawFrame<'_>| {
+            let start = Instant::now();
+            let mut width = frame.width;
+            let mut height = frame.height;
+            let (space, range) = color.lock().map(|c| *c).unwrap_or((ColorSpace::Auto, ColorRange::Limited));
+            let matrix = YuvMatrix::for_frame(space, range, width, height);
+            let mut path = DecodePath::Fallback;
+            let mut decoded = false;
+            // 入力側で解像度が変わったフレームは崩れた画になるため表示せず、開き直しを要求する
+            if let Some(change) = frame.format.and_then(|fmt| detect_mode_change(fmt, width, height, frame.data.len())) {
+                if let Ok(mut guard) = fb.lock() { guard.record_mode_change(change); }
+                return;
+            }
+            let mut out = fb.lock().map(|mut g| g.take_buffer()).unwrap_or_default();
+            // フレームフォーマットに応じて専用変換を試行
+            if let Some(fmt) = frame.format {
+                if let Some((w, h, p)) = decode_raw_frame(fmt, width, height, frame.data, &matrix, &mut out) {
+                    width = w;
+                    height = h;
+                    decoded = true;
+                    path = p;
+                }
+            }
+            // MJPEG専用デコーダの失敗はフォールバック前に記録
+            let mjpeg_failed = frame.format == Some(PixelFormat::Mjpeg) && !decoded;
+            if !decoded {
+                if let Some(fallback) = frame.fallback {
+                    // その他のフォーマットも標準デコード (RGBA で直接バッファへ)
+                    out.resize(width * height, Color32::BLACK);
+                    decoded = fallback(pixel_bytes_mut(&mut out));
+                }
+            }
+            if mjpeg_failed {
+                if let Ok(mut guard) = fb.lock() { guard.record_mjpeg_error(); }
+            }
+            if decoded {
+                // 画質調整 (無調整なら何もしない)
+                if let Ok(processor) = picture.lock().map(|p| *p) {
+                    processor.apply(&mut out, width);
+                }
+                // インターレース解除 (倍速 bob では2枚目のフィールドに表示予定時刻を付けて渡す)
+                let (mode, order) = deinterlace.lock().map(|d| *d).unwrap_or((DeinterlaceMode::Weave, FieldOrder::Auto));
+                let mut second = match mode {
+                    DeinterlaceMode::Bob => fb.lock().map(|mut g| g.take_buffer()).unwrap_or_default(),
+                    _ => Vec::new(),
+                };
+                let has_second = deinterlacer.process(mode, order, width, height, &mut out, &mut second);
+                // 3D LUT はフレーム全体に適用 (格子点は共有なのでロックはすぐに離す)
+                // 補正前後の比較中は補正前の画像も残し、表示側で左半分に描く (スクリーンショット・録画は補正後のみ)
+                let (mut ungraded, mut second_ungraded) = (None, None);
+                if let Some((processor, keep_ungraded)) = lut.lock().ok().and_then(|l| l.clone()) {
+                    if keep_ungraded {
+                        ungraded = Some(out.clone());
+                        second_ungraded = has_second.then(|| second.clone());
+                    }
+                    processor.apply(&mut out, width);
+                    if has_second {
+                        processor.apply(&mut second, width);
                     }
                 }
-                if let Some(data) = rgb_vec {
-                    let decode_ms = start.elapsed().as_secs_f32() * 1000.0;
-                    let vf = VideoFrame { width, height, data };
-                    if let Ok(mut guard) = fb.lock() { 
-                        guard.push_back(vf, decode_ms, used_fast); 
+                let decode_ms = start.elapsed().as_secs_f32() * 1000.0;
+                if let Ok(mut guard) = fb.lock() { 
+                    guard.push_back(width, height, out, ungraded, decode_ms, path); 
+                    if has_second {
+                        let due = Instant::now() + deinterlacer.field_delay();
+                        guard.push_delayed(PendingField { due, width, height, pixels: second, ungraded: second_ungraded });
                     }
                 }
             }
-        };
-        
-        let mut camera = CallbackCamera::new(device_info.index().clone(), requested_format, frame_callback)
-            .map_err(|e| format!("Failed to create camera: {}", e))?;
-            
-        camera.open_stream()
-            .map_err(|e| format!("Failed to open camera stream: {}", e))?;
-            
-        self.camera = Some(camera);
-        self.is_active = true;
-        
-        Ok(())
+        })
     }
     
     pub fn stop_capture(&mut self) {
-        if let Some(mut camera) = self.camera.take() {
-            let _ = camera.stop_stream();
+        if let Some(mut source) = self.source.take() {
+            source.stop();
         }
         self.is_active = false;
         
-    if let Ok(mut buf) = self.frames.lock() { *buf = FrameBuffer::new(); }
+    // 通し番号は再接続後も継続 (UI側の重複判定がずれないように)
+    if let Ok(mut buf) = self.frames.lock() {
+        let generation = buf.generation;
+        *buf = FrameBuffer::new();
+        buf.generation = generation;
+    }
     }
     
     pub fn get_latest_frame(&self) -> Option<VideoFrame> {
-        self.frames.lock().ok().and_then(|mut fb| {
-            let frame = fb.take_front();
-            // メモリリーク防止: 定期的に古いフレームをクリア
-            fb.clear_old_frames();
-            frame
-        })
+        self.frame_reader().get_latest_frame()
     }
 
 
@@ -249,42 +669,36 @@ impl VideoCapture {
         self.is_active
     }
     
-    #[allow(dead_code)]
-    pub fn get_supported_formats(&self) -> Vec<(String, Vec<(u32, u32)>)> {
-        // 簡略化された実装 - 実際のフォーマットには、より複雑なロジックが必要
-        vec![
-            ("MJPEG".to_string(), vec![(1920, 1080), (1280, 720), (640, 480)]),
-            ("YUY2".to_string(), vec![(1280, 720), (640, 480)]),
-        ]
+    // デバイスの対応モードを取得 (キャッシュ済みならデバイスを開かない)
+    pub fn device_capabilities(backend: CaptureBackend, device_name: &str, device_id: Option<&DeviceId>) -> Capabilities {
+        let key = capabilities_key(backend, device_name, device_id);
+        if let Some(caps) = capabilities_cache().lock().ok().and_then(|cache| cache.get(&key).cloned()) {
+            return caps;
+        }
+        store_capabilities(backend, device_name, device_id, Self::query_capabilities(backend, device_name, device_id))
     }
 
-    #[allow(dead_code)]
-    pub fn get_supported_formats_for(_device: &str) -> Vec<(String, Vec<(u32, u32)>)> {
-        // デバイス毎のプレースホルダー; 実際の実装ではデバイス機能を照会
-        vec![
-            ("MJPEG".to_string(), vec![(1920,1080),(1280,720),(640,480)]),
-            ("YUY2".to_string(), vec![(1280,720),(640,480)]),
-            ("RGB24".to_string(), vec![(1280,720),(640,480)]),
-        ]
+    // キャッシュを破棄して次回に問い合わせ直す (設定画面の「再取得」)
+    pub fn forget_capabilities(backend: CaptureBackend, device_name: &str, device_id: Option<&DeviceId>) {
+        if let Ok(mut cache) = capabilities_cache().lock() {
+            cache.remove(&capabilities_key(backend, device_name, device_id));
+        }
     }
-    
-    // デバイスの能力を取得するメソッド
-    pub fn get_device_capabilities(device_name: Option<&str>) -> Result<Vec<(String, Vec<(u32, u32, u32)>)>, String> {
+
+    fn query_capabilities(backend: CaptureBackend, device_name: &str, device_id: Option<&DeviceId>) -> Result<DeviceCapabilities, String> {
         use nokhwa::Camera;
+
+        // テストパターンは固定の一覧
+        if device_name == TEST_PATTERN_DEVICE {
+            return Ok(test_pattern::capabilities());
+        }
+        // ファイルはヘッダ・ファイル名から判定
+        if let Some(path) = file_source::file_path_of(device_name) {
+            return file_source::capabilities(path);
+        }
         
         // デバイス情報を取得
-        let devices = nokhwa::query(ApiBackend::MediaFoundation)
-            .map_err(|e| format!("Failed to query devices: {}", e))?;
-            
-        let device_info = if let Some(name) = device_name {
-            devices.into_iter()
-                .find(|d| d.human_name() == name)
-                .ok_or_else(|| format!("Device '{}' not found", name))?
-        } else {
-            devices.into_iter()
-                .next()
-                .ok_or("No video devices found")?
-        };
+        let device_info = source::find_camera(backend, Some(device_name), device_id)?;
         
         // カメラを一時的に開いて能力を取得
         let requested_format = RequestedFormat::new::<RgbFormat>(RequestedFormatType::Closest(CameraFormat::new(
@@ -293,72 +707,10 @@ impl VideoCapture {
             30,
         )));
         
-        let mut camera = Camera::new(device_info.index().clone(), requested_format)
-            .map_err(|e| format!("Failed to create camera for capability query: {}", e))?;
-        
-        let mut result: Vec<(String, Vec<(u32, u32, u32)>)> = Vec::new();
-        
-        // 各フォーマットで対応解像度・FPSを取得
-        let formats = vec![
-            ("YUY2", FrameFormat::YUYV),
-            ("MJPEG", FrameFormat::MJPEG),
-            ("RGB24", FrameFormat::RAWRGB),
-        ];
-        
-        for (format_name, frame_format) in formats {
-            match camera.compatible_list_by_resolution(frame_format) {
-                Ok(resolution_map) => {
-                    let mut resolutions_with_fps: Vec<(u32, u32, u32)> = Vec::new();
-                    
-                    for (resolution, fps_list) in resolution_map.iter() {
-                        // 各解像度に対して利用可能な全FPSを記録
-                        for fps in fps_list.iter() {
-                            resolutions_with_fps.push((resolution.width_x, resolution.height_y, *fps));
-                        }
-                    }
-                    
-                    // 重複を削除してユニークな組み合わせのみ保持
-                    resolutions_with_fps.sort();
-                    resolutions_with_fps.dedup();
-                    
-                    // 解像度でソート（大きい順）、同じ解像度ならFPSでソート（大きい順）
-                    resolutions_with_fps.sort_by(|a, b| {
-                        let size_a = a.0 * a.1;
-                        let size_b = b.0 * b.1;
-                        match size_b.cmp(&size_a) {
-                            std::cmp::Ordering::Equal => b.2.cmp(&a.2),
-                            other => other,
-                        }
-                    });
-                    
-                    if !resolutions_with_fps.is_empty() {
-                        result.push((format_name.to_string(), resolutions_with_fps));
-                    }
-                }
-                Err(_) => {
-                    // エラーの場合、デフォルト値を設定
-                    let default_resolutions = match format_name {
-                        "YUY2" => vec![(1280, 720, 60), (640, 480, 30)],
-                        "MJPEG" => vec![(1920, 1080, 30), (1280, 720, 60), (640, 480, 30)],
-                        "RGB24" => vec![(1280, 720, 30), (640, 480, 30)],
-                        _ => vec![],
-                    };
-                    if !default_resolutions.is_empty() {
-                        result.push((format_name.to_string(), default_resolutions));
-                    }
-                }
-            }
-        }
-        
-        // 結果が空の場合はデフォルト値を返す
-        if result.is_empty() {
-            result = vec![
-                ("YUY2".to_string(), vec![(1280, 720, 60), (640, 480, 30)]),
-                ("MJPEG".to_string(), vec![(1920, 1080, 30), (1280, 720, 60), (640, 480, 30)]),
-            ];
-        }
+        let mut camera = Camera::with_backend(device_info.index().clone(), requested_format, source::api_backend(backend))
+            .map_err(|e| format!("Failed to open device for capability query: {}", e))?;
         
-        Ok(result)
+        source::collect_capabilities(camera.compatible_fourcc(), |ff| camera.compatible_list_by_resolution(ff))
     }
 }
 
@@ -368,12 +720,234 @@ impl Drop for VideoCapture {
     }
 }
 
-impl Clone for VideoFrame {
-    fn clone(&self) -> Self {
-        Self {
-            width: self.width,
-            height: self.height,
-            data: self.data.clone(),
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 左半分が赤、右半分が青の JPEG (ブロックの境界で色が分かれるので中央付近は圧縮後もほぼ元の色)
+    fn fixture_jpeg(width: u32, height: u32) -> Vec<u8> {
+        let image = image::RgbImage::from_fn(width, height, |x, _| {
+            if x < width / 2 { image::Rgb([255, 0, 0]) } else { image::Rgb([0, 0, 255]) }
+        });
+        let mut jpeg = Vec::new();
+        image::codecs::jpeg::JpegEncoder::new_with_quality(&mut jpeg, 90)
+            .encode_image(&image)
+            .unwrap();
+        jpeg
+    }
+
+    // UVC カメラの MJPEG と同じ形にする (Huffman テーブル (DHT) を省き、APP0 を AVI1 にする)
+    fn strip_huffman_tables(jpeg: &[u8]) -> Vec<u8> {
+        let mut out = jpeg[..2].to_vec();
+        let mut at = 2;
+        while jpeg[at + 1] != 0xDA {
+            let len = u16::from_be_bytes([jpeg[at + 2], jpeg[at + 3]]) as usize;
+            match jpeg[at + 1] {
+                0xC4 => {}
+                0xE0 => out.extend_from_slice(&[0xFF, 0xE0, 0x00, 0x10, b'A', b'V', b'I', b'1', 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]),
+                _ => out.extend_from_slice(&jpeg[at..at + 2 + len]),
+            }
+            at += 2 + len;
         }
+        out.extend_from_slice(&jpeg[at..]);
+        out
+    }
+
+    fn decode(src: &[u8]) -> Option<(usize, usize, Vec<Color32>)> {
+        let mut out = Vec::new();
+        let matrix = YuvMatrix::new(ColorSpace::Bt601, ColorRange::Limited);
+        // MJPEG のサイズはネゴシエーション結果ではなく JPEG のヘッダから決まる
+        let (w, h, path) = decode_raw_frame(PixelFormat::Mjpeg, 640, 480, src, &matrix, &mut out)?;
+        assert_eq!(path, DecodePath::Mjpeg);
+        Some((w, h, out))
+    }
+
+    fn assert_close(actual: Color32, expected: [u8; 3]) {
+        let diff = actual.to_array()[..3].iter().zip(expected).map(|(&a, e)| a.abs_diff(e)).max().unwrap();
+        assert!(diff <= 24 && actual.a() == 255, "{:?} is not close to {:?}", actual, expected);
+    }
+
+    fn assert_fixture(width: usize, height: usize, pixels: &[Color32]) {
+        assert_eq!((width, height), (32, 16));
+        assert_eq!(pixels.len(), 32 * 16);
+        assert_close(pixels[8 * 32 + 4], [255, 0, 0]);
+        assert_close(pixels[8 * 32 + 27], [0, 0, 255]);
+    }
+
+    #[test]
+    fn decodes_jpeg() {
+        let (w, h, pixels) = decode(&fixture_jpeg(32, 16)).expect("fixture should decode");
+        assert_fixture(w, h, &pixels);
+    }
+
+    #[test]
+    fn decodes_jpeg_with_padding() {
+        // 先頭のゴミと EOI 後のゼロ埋め (固定長の転送バッファ)
+        let mut padded = vec![0x00, 0x12, 0x34];
+        padded.extend_from_slice(&fixture_jpeg(32, 16));
+        padded.resize(padded.len() + 4096, 0);
+        let (w, h, pixels) = decode(&padded).expect("padded payload should decode");
+        assert_fixture(w, h, &pixels);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn decodes_jpeg_without_huffman_tables() {
+        let jpeg = fixture_jpeg(32, 16);
+        let stripped = strip_huffman_tables(&jpeg);
+        assert!(stripped.len() < jpeg.len() && !stripped.windows(2).any(|w| w == [0xFF, 0xC4]));
+        let (w, h, pixels) = decode(&stripped).expect("MJPEG without DHT should decode");
+        assert_fixture(w, h, &pixels);
+    }
+
+    #[test]
+    fn decodes_grayscale_jpeg() {
+        let image = image::GrayImage::from_pixel(16, 8, image::Luma([200]));
+        let mut jpeg = Vec::new();
+        image::codecs::jpeg::JpegEncoder::new(&mut jpeg).encode_image(&image).unwrap();
+        let (w, h, pixels) = decode(&jpeg).expect("grayscale JPEG should decode");
+        assert_eq!((w, h), (16, 8));
+        assert!(pixels.iter().all(|&p| p.r().abs_diff(200) <= 2 && p.r() == p.g() && p.g() == p.b() && p.a() == 255));
+    }
+
+    #[test]
+    fn rejects_broken_jpeg() {
+        let jpeg = fixture_jpeg(32, 16);
+        assert!(decode(&jpeg[..jpeg.len() / 3]).is_none());
+        assert!(decode(&[0u8; 64]).is_none());
+    }
+
+    #[test]
+    fn counts_frames_per_decode_path() {
+        // MJPEG の成功・失敗 (フォールバックの成否) と YUY2 の高速パスを経路ごとに数える
+        let video = VideoCapture::new();
+        let mut sink = video.frame_sink();
+        let jpeg = fixture_jpeg(32, 16);
+        let broken = &jpeg[..jpeg.len() / 3];
+        let gray = |out: &mut [u8]| {
+            out.fill(128);
+            true
+        };
+        sink(RawFrame { width: 32, height: 16, format: Some(PixelFormat::Mjpeg), data: &jpeg, fallback: None });
+        // フォールバックがなければ表示されない
+        sink(RawFrame { width: 32, height: 16, format: Some(PixelFormat::Mjpeg), data: broken, fallback: None });
+        sink(RawFrame { width: 32, height: 16, format: Some(PixelFormat::Mjpeg), data: broken, fallback: Some(&gray) });
+        let yuy2 = [16u8, 128].repeat(32 * 16);
+        sink(RawFrame { width: 32, height: 16, format: Some(PixelFormat::Yuy2), data: &yuy2, fallback: None });
+
+        let stats = video.frame_reader().decode_stats();
+        assert_eq!((stats.fast, stats.mjpeg, stats.fallback, stats.mjpeg_errors), (1, 1, 1, 2));
+        assert_eq!(video.get_latest_frame().unwrap().generation, 3);
+    }
+
+    fn frame_pixels(width: usize, height: usize) -> Vec<Color32> {
+        (0..width * height).map(|i| Color32::from_gray(i as u8)).collect()
+    }
+
+    #[test]
+    fn frame_handoff_shares_pixels() {
+        // UI・スクリーンショット・録画が同じフレームを受け取っても画素データはコピーされない (参照カウントだけ増える)
+        let reader = FrameReader { frames: Arc::new(Mutex::new(FrameBuffer::new())) };
+        let (tap, recorded) = std::sync::mpsc::sync_channel(1);
+        reader.set_recording_tap(Some(tap));
+        let pixels = frame_pixels(64, 32);
+        let source = pixels.as_ptr();
+        reader.frames.lock().unwrap().push_back(64, 32, pixels, None, 0.0, DecodePath::Fast);
+
+        let first = reader.get_latest_frame().unwrap();
+        let again = reader.get_latest_frame().unwrap();
+        let (_, for_recording) = recorded.try_recv().unwrap();
+        assert_eq!(first.image.pixels.as_ptr(), source, "the decoded buffer was copied into the frame");
+        assert!(Arc::ptr_eq(&first.image, &again.image) && Arc::ptr_eq(&first.image, &for_recording.image));
+        assert_eq!(first.generation, again.generation);
+        // 全体の範囲ならスクリーンショットも共有バッファをそのまま使う
+        let whole = first.cropped_rgba((0, 0, 64, 32));
+        assert!(matches!(whole, Cow::Borrowed(_)) && whole.as_ptr() == source as *const u8);
+    }
+
+    #[test]
+    fn dropped_frame_buffers_are_recycled() {
+        // 表示される前に上書きされたフレームのバッファは、次のフレームの変換先として再利用される
+        let mut fb = FrameBuffer::new();
+        let pixels = frame_pixels(64, 32);
+        let dropped = pixels.as_ptr();
+        fb.push_back(64, 32, pixels, None, 0.0, DecodePath::Fast);
+        fb.push_back(64, 32, frame_pixels(64, 32), None, 0.0, DecodePath::Fast);
+        let recycled = fb.take_buffer();
+        assert_eq!(recycled.as_ptr(), dropped);
+        // UI がまだ参照しているフレームのバッファは回収しない (入れ替わりで back に戻ってから上書きされても)
+        let shown = fb.take_front().unwrap();
+        fb.push_back(64, 32, frame_pixels(64, 32), None, 0.0, DecodePath::Fast);
+        fb.take_front();
+        fb.push_back(64, 32, frame_pixels(64, 32), None, 0.0, DecodePath::Fast);
+        assert!(fb.spare_buffers.iter().all(|b| b.as_ptr() != shown.image.pixels.as_ptr()));
+    }
+
+    #[test]
+    fn second_field_waits_for_its_time() {
+        // 倍速 bob の2枚目は表示予定時刻まで出さず、過ぎたら読み出し時に表示へ回す
+        let mut fb = FrameBuffer::new();
+        let due = Instant::now() + Duration::from_secs(60);
+        fb.push_back(4, 2, frame_pixels(4, 2), None, 0.0, DecodePath::Fast);
+        let second = frame_pixels(4, 2);
+        let second_ptr = second.as_ptr();
+        fb.push_delayed(PendingField { due, width: 4, height: 2, pixels: second, ungraded: None });
+        let first = fb.take_front().unwrap();
+        assert_ne!(first.image.pixels.as_ptr(), second_ptr);
+        assert_eq!(fb.take_front().unwrap().generation, first.generation);
+        fb.present_due(due);
+        let shown = fb.take_front().unwrap();
+        assert_eq!(shown.image.pixels.as_ptr(), second_ptr);
+        assert_eq!(shown.generation, first.generation + 1);
+    }
+
+    #[test]
+    fn second_field_is_flushed_before_next_frame() {
+        // 読み出されなくても、次のフレームが届いた時点で2枚目を先に渡す (録画にも両方のフィールドが届く)
+        let mut fb = FrameBuffer::new();
+        let (tap, recorded) = std::sync::mpsc::sync_channel(4);
+        fb.recording_tap = Some(tap);
+        let due = Instant::now() + Duration::from_secs(60);
+        fb.push_back(4, 2, frame_pixels(4, 2), None, 0.0, DecodePath::Fast);
+        fb.push_delayed(PendingField { due, width: 4, height: 2, pixels: frame_pixels(4, 2), ungraded: None });
+        fb.push_back(4, 2, frame_pixels(4, 2), None, 0.0, DecodePath::Fast);
+        let generations: Vec<u64> = recorded.try_iter().map(|(_, frame)| frame.generation).collect();
+        assert_eq!(generations, [1, 2, 3]);
+        assert!(fb.pending_field.is_none());
+    }
+
+    #[test]
+    fn lut_grades_whole_frame() {
+        // 補正前後の比較中もフレーム全体を補正し (スクリーンショット・録画はこちら)、補正前の画像は表示用に別に付ける
+        let mut video = VideoCapture::new();
+        let white = crate::lut::parse_cube(&("LUT_3D_SIZE 2\n".to_string() + &"1 1 1\n".repeat(8))).unwrap();
+        let processor = LutProcessor::new(&white, crate::settings::LutInterpolation::Tetrahedral);
+        let mut sink = video.frame_sink();
+        // 黒の YUY2
+        let data = [16u8, 128].repeat(8 * 4);
+        let mut send = || sink(RawFrame { width: 8, height: 4, format: Some(PixelFormat::Yuy2), data: &data, fallback: None });
+
+        video.set_lut(Some(processor.clone()), true);
+        send();
+        let frame = video.get_latest_frame().unwrap();
+        assert!(frame.image.pixels.iter().all(|&p| p == Color32::WHITE));
+        let ungraded = frame.ungraded.expect("ungraded image should be kept while comparing");
+        assert_eq!(ungraded.size, [8, 4]);
+        assert!(ungraded.pixels.iter().all(|&p| p.r() <= 2 && p.g() <= 2 && p.b() <= 2));
+
+        video.set_lut(Some(processor), false);
+        send();
+        let frame = video.get_latest_frame().unwrap();
+        assert!(frame.image.pixels.iter().all(|&p| p == Color32::WHITE) && frame.ungraded.is_none());
+    }
+
+    #[test]
+    fn decodes_into_recycled_buffer() {
+        // プールから戻ったバッファに十分な容量があれば確保し直さない
+        let mut out = Vec::with_capacity(64 * 64);
+        let ptr = out.as_ptr();
+        let matrix = YuvMatrix::new(ColorSpace::Bt601, ColorRange::Limited);
+        let (w, h, _) = decode_raw_frame(PixelFormat::Mjpeg, 32, 16, &fixture_jpeg(32, 16), &matrix, &mut out).unwrap();
+        assert_fixture(w, h, &out);
+        assert_eq!(out.as_ptr(), ptr);
+    }
+}
@@ -0,0 +1,117 @@
+use std::time::{Duration, Instant};
+use crate::video::SignalState;
+
+// 映像が途絶えたキャプチャを検知して再接続を要求するウォッチドッグ
+// 再接続に失敗するたびに次の試行までの間隔を倍にする (2秒, 4秒, 8秒 ... 最大30秒)
+
+// 最後のフレームからこれ以上経過したら信号断とみなす
+pub const SIGNAL_TIMEOUT: Duration = Duration::from_secs(3);
+const FIRST_RETRY_DELAY: Duration = Duration::from_secs(2);
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(30);
+
+// n 回目の再接続の後、次の試行までの待ち時間
+pub fn retry_delay(attempt: u32) -> Duration {
+    FIRST_RETRY_DELAY
+        .saturating_mul(1 << attempt.saturating_sub(1).min(5))
+        .min(MAX_RETRY_DELAY)
+}
+
+pub struct SignalWatchdog {
+    timeout: Duration,
+    // 信号断を検知した時刻 (受信中は None)
+    lost_since: Option<Instant>,
+    // 今回の信号断での再接続回数
+    attempts: u32,
+    next_attempt: Option<Instant>,
+}
+
+impl SignalWatchdog {
+    pub fn new(timeout: Duration) -> Self {
+        Self { timeout, lost_since: None, attempts: 0, next_attempt: None }
+    }
+
+    // 信号断中か (オーバーレイ表示用)
+    pub fn is_lost(&self) -> bool {
+        self.lost_since.is_some()
+    }
+
+    pub fn attempts(&self) -> u32 {
+        self.attempts
+    }
+
+    // 次の再接続の予定時刻
+    pub fn next_attempt(&self) -> Option<Instant> {
+        self.next_attempt
+    }
+
+    // 監視状態を初期化 (デバイス未選択時など)
+    pub fn reset(&mut self) {
+        self.lost_since = None;
+        self.attempts = 0;
+        self.next_attempt = None;
+    }
+
+    // 定期的に呼び出し、再接続すべきタイミングなら true を返す
+    pub fn poll(&mut self, state: SignalState, now: Instant) -> bool {
+        match state {
+            SignalState::Receiving(age) if age < self.timeout => {
+                if let Some(since) = self.lost_since {
+                    println!("Debug: Video signal restored after {:.1}s ({} reconnect attempts)",
+                        now.duration_since(since).as_secs_f32(), self.attempts);
+                    self.reset();
+                }
+                return false;
+            }
+            // 開始直後はフレームが届くまで待つ (再接続直後も同様)
+            SignalState::Waiting(elapsed) if elapsed < self.timeout => return false,
+            _ => {}
+        }
+
+        if self.lost_since.is_none() {
+            println!("Warning: No video signal ({:?}), reconnecting", state);
+            self.lost_since = Some(now);
+            self.next_attempt = Some(now);
+        }
+        if self.next_attempt.is_some_and(|t| now >= t) {
+            self.attempts += 1;
+            let delay = retry_delay(self.attempts);
+            self.next_attempt = Some(now + delay);
+            println!("Debug: Reconnect attempt {} (next in {:.0}s if the signal does not return)", self.attempts, delay.as_secs_f32());
+            return true;
+        }
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 再接続が続けて失敗した場合に間隔が伸びること
+    #[test]
+    fn backoff() {
+        let mut watchdog = SignalWatchdog::new(SIGNAL_TIMEOUT);
+        let start = Instant::now();
+        let mut retries = Vec::new();
+        // 接続できない状態を 2 分間、100ms 刻みで監視
+        for step in 0..1200u64 {
+            let now = start + Duration::from_millis(step * 100);
+            if watchdog.poll(SignalState::Stopped, now) {
+                retries.push(now.duration_since(start).as_secs());
+            }
+        }
+        assert_eq!(retries, [0, 2, 6, 14, 30, 60, 90]);
+    }
+
+    #[test]
+    fn signal_restored_resets_backoff() {
+        let mut watchdog = SignalWatchdog::new(SIGNAL_TIMEOUT);
+        let start = Instant::now();
+        assert!(watchdog.poll(SignalState::Stopped, start));
+        assert!(watchdog.poll(SignalState::Stopped, start + Duration::from_secs(2)));
+        assert_eq!(watchdog.attempts(), 2);
+        assert!(!watchdog.poll(SignalState::Receiving(Duration::ZERO), start + Duration::from_secs(3)));
+        assert!(!watchdog.is_lost());
+        assert_eq!(watchdog.attempts(), 0);
+    }
+}
@@ -0,0 +1,156 @@
+use crate::orientation::Orientation;
+
+// 映像の拡大表示 (ルーペ)
+// 表示範囲は回転・反転後の表示上の座標 (クロップ後の範囲を 0~1 に正規化したもの) で持つ
+// 描画時は元の映像の UV に変換するので、テクスチャやクロップの処理はそのまま使える
+
+pub const MAX_ZOOM: f32 = 8.0;
+// ミニマップの大きさ (映像の幅に対する割合) と画面端からの余白
+const MINIMAP_SCALE: f32 = 0.2;
+const MINIMAP_MARGIN: f32 = 8.0;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ZoomView {
+    zoom: f32,
+    // 表示範囲の中心
+    center: egui::Pos2,
+}
+
+impl Default for ZoomView {
+    fn default() -> Self {
+        Self { zoom: 1.0, center: egui::pos2(0.5, 0.5) }
+    }
+}
+
+impl ZoomView {
+    pub fn zoom(&self) -> f32 {
+        self.zoom
+    }
+
+    pub fn is_zoomed(&self) -> bool {
+        self.zoom > 1.0
+    }
+
+    pub fn reset(&mut self) {
+        *self = Self::default();
+    }
+
+    // 表示している範囲 (表示上の座標)
+    pub fn view(&self) -> egui::Rect {
+        egui::Rect::from_center_size(self.center, egui::Vec2::splat(1.0 / self.zoom))
+    }
+
+    // anchor (映像の表示領域内の位置、0~1) の下にある点を動かさずに factor 倍する
+    pub fn zoom_at(&mut self, factor: f32, anchor: egui::Pos2) {
+        let view = self.view();
+        let point = view.min + anchor.to_vec2() * view.size();
+        self.zoom = (self.zoom * factor).clamp(1.0, MAX_ZOOM);
+        let size = 1.0 / self.zoom;
+        self.center = point + (egui::Vec2::splat(0.5) - anchor.to_vec2()) * size;
+        self.clamp();
+    }
+
+    // 表示範囲を動かす (delta は映像の表示領域に対する割合、ドラッグと同じ向き)
+    pub fn pan(&mut self, delta: egui::Vec2) {
+        self.center -= delta / self.zoom;
+        self.clamp();
+    }
+
+    // 表示範囲が映像の外に出ないようにする
+    fn clamp(&mut self) {
+        let half = 0.5 / self.zoom;
+        self.center.x = self.center.x.clamp(half, 1.0 - half);
+        self.center.y = self.center.y.clamp(half, 1.0 - half);
+    }
+
+    // 表示する範囲の UV (uv はクロップ後の範囲)
+    // 回転・反転は 90 度単位なので、表示上の矩形は元の映像でも軸に沿った矩形になる
+    pub fn apply_to_uv(&self, orientation: Orientation, uv: egui::Rect) -> egui::Rect {
+        if !self.is_zoomed() {
+            return uv;
+        }
+        let view = self.view();
+        let source = egui::Rect::from_two_pos(orientation.to_source(view.min), orientation.to_source(view.max));
+        egui::Rect::from_min_max(uv.min + source.min.to_vec2() * uv.size(), uv.min + source.max.to_vec2() * uv.size())
+    }
+
+    // 拡大中は右下に映像全体の縮小表示と、今表示している範囲の枠を描く
+    pub fn paint_minimap(&self, painter: &egui::Painter, texture: egui::TextureId, orientation: Orientation, rect: egui::Rect, uv: egui::Rect) {
+        if !self.is_zoomed() {
+            return;
+        }
+        let size = rect.size() * MINIMAP_SCALE;
+        let map = egui::Rect::from_min_size(rect.max - size - egui::Vec2::splat(MINIMAP_MARGIN), size);
+        painter.rect_filled(map.expand(2.0), 2.0, egui::Color32::from_black_alpha(160));
+        orientation.paint(painter, texture, map, uv);
+        let view = self.view();
+        let frame = egui::Rect::from_min_max(map.min + view.min.to_vec2() * size, map.min + view.max.to_vec2() * size);
+        painter.rect_stroke(frame, 0.0, egui::Stroke::new(1.5, egui::Color32::YELLOW));
+        painter.text(
+            map.left_top() + egui::vec2(2.0, -4.0),
+            egui::Align2::LEFT_BOTTOM,
+            format!("x{:.1}", self.zoom),
+            egui::FontId::proportional(12.0),
+            egui::Color32::WHITE,
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::settings::Rotation;
+
+    fn close(a: egui::Pos2, b: egui::Pos2) -> bool {
+        (a - b).length() < 1e-4
+    }
+
+    // カーソルの下の点は拡大しても動かない
+    #[test]
+    fn anchor_stays_under_cursor() {
+        let mut zoom = ZoomView::default();
+        let anchor = egui::pos2(0.25, 0.75);
+        let point = |z: &ZoomView| z.view().min + anchor.to_vec2() * z.view().size();
+        for factor in [2.0, 1.5] {
+            let before = point(&zoom);
+            zoom.zoom_at(factor, anchor);
+            assert!(close(point(&zoom), before), "point under cursor moved from {:?} to {:?}", before, point(&zoom));
+        }
+    }
+
+    // 表示範囲は映像の外に出ず、倍率は 1 ~ MAX_ZOOM に収まる
+    #[test]
+    fn clamps_view_and_zoom() {
+        let mut zoom = ZoomView::default();
+        zoom.zoom_at(4.0, egui::pos2(0.5, 0.5));
+        zoom.pan(egui::vec2(10.0, -10.0));
+        let view = zoom.view();
+        assert!(close(view.min, egui::pos2(0.0, 0.75)), "view after panning past the edge was {:?}", view);
+        zoom.zoom_at(100.0, egui::pos2(0.0, 0.0));
+        assert_eq!(zoom.zoom(), MAX_ZOOM);
+        zoom.zoom_at(0.001, egui::pos2(0.3, 0.3));
+        assert!(!zoom.is_zoomed());
+        assert_eq!(zoom.view(), ZoomView::default().view());
+    }
+
+    // 回転・反転していても、表示上の左上を拡大すると表示上の左上に当たる元の映像の範囲が選ばれる
+    #[test]
+    fn follows_orientation() {
+        let uv = egui::Rect::from_min_max(egui::pos2(0.1, 0.2), egui::pos2(0.9, 0.8));
+        let mut zoom = ZoomView::default();
+        zoom.zoom_at(2.0, egui::pos2(0.0, 0.0));
+        for rotation in [Rotation::None, Rotation::Cw90, Rotation::Cw180, Rotation::Cw270] {
+            for flip_horizontal in [false, true] {
+                let orientation = Orientation { rotation, flip_horizontal, flip_vertical: false };
+                let zoomed = zoom.apply_to_uv(orientation, uv);
+                let corner = orientation.to_source(egui::pos2(0.0, 0.0));
+                let expected = uv.min + corner.to_vec2() * uv.size();
+                let has_corner = [zoomed.left_top(), zoomed.right_top(), zoomed.left_bottom(), zoomed.right_bottom()]
+                    .into_iter()
+                    .any(|p| close(p, expected));
+                assert!(has_corner && (zoomed.area() - uv.area() / 4.0).abs() <= 1e-4,
+                    "{:?}: zoomed uv {:?} does not start at {:?}", orientation, zoomed, expected);
+            }
+        }
+    }
+}
@@ -0,0 +1,110 @@
+// テストパターンのソースと合成した音声を1秒録画し、書き出した AVI のフレーム数と長さを確認する
+use std::time::{Duration, Instant};
+use capturecard_viewer::audio::AudioTap;
+use capturecard_viewer::letterbox::BarInsets;
+use capturecard_viewer::orientation::Orientation;
+use capturecard_viewer::recording::{RecordOptions, Recorder};
+use capturecard_viewer::settings::CropSettings;
+use capturecard_viewer::test_pattern::TestPatternSource;
+use capturecard_viewer::video::VideoCapture;
+
+const FPS: u32 = 30;
+const SAMPLE_RATE: u32 = 48000;
+
+// 書き出した AVI から読み取った値
+struct AviInfo {
+    total_frames: u32,
+    fps: u32,
+    audio_seconds: f64,
+    video_chunks: usize,
+    first_frame: Vec<u8>,
+}
+
+fn read_u32(bytes: &[u8], at: usize) -> u32 {
+    let b = &bytes[at..at + 4];
+    u32::from_le_bytes([b[0], b[1], b[2], b[3]])
+}
+
+fn read_avi(bytes: &[u8]) -> AviInfo {
+    assert!(bytes.get(0..4) == Some(b"RIFF") && bytes.get(8..12) == Some(b"AVI "), "not an AVI file");
+    assert_eq!(read_u32(bytes, 4) as usize + 8, bytes.len(), "RIFF size does not match the file size");
+    // 最上位のチャンク (hdrl, movi, idx1) を順に読む
+    let (mut at, mut hdrl, mut movi_at, mut idx1) = (12, None, None, None);
+    while at + 8 <= bytes.len() {
+        let size = read_u32(bytes, at + 4) as usize;
+        let data = bytes.get(at + 8..at + 8 + size).expect("chunk runs past the end of the file");
+        match (&bytes[at..at + 4], data.get(0..4)) {
+            (b"LIST", Some(b"hdrl")) => hdrl = Some(data),
+            (b"LIST", Some(b"movi")) => movi_at = Some(at + 8),
+            (b"idx1", _) => idx1 = Some(data),
+            _ => {}
+        }
+        at += 8 + size + size % 2;
+    }
+    let hdrl = hdrl.expect("missing hdrl");
+    let movi_at = movi_at.expect("missing movi");
+    let idx1 = idx1.expect("missing idx1");
+    // avih は hdrl の先頭、ストリームヘッダ (strh) は映像・音声の順
+    let total_frames = read_u32(hdrl, 4 + 8 + 16);
+    let strh: Vec<usize> = hdrl.windows(4).enumerate().filter(|(_, w)| *w == b"strh").map(|(i, _)| i + 8).collect();
+    let [video, audio] = strh[..] else {
+        panic!("expected 2 streams, found {}", strh.len());
+    };
+    let fps = read_u32(hdrl, video + 24) / read_u32(hdrl, video + 20).max(1);
+    let audio_seconds = read_u32(hdrl, audio + 32) as f64 * read_u32(hdrl, audio + 20) as f64 / read_u32(hdrl, audio + 24).max(1) as f64;
+    // 索引の映像チャンクを数え、最初の中身のあるフレームを取り出す
+    let mut video_chunks = 0;
+    let mut first_frame = Vec::new();
+    for entry in idx1.chunks_exact(16) {
+        if &entry[0..4] != b"00dc" {
+            continue;
+        }
+        video_chunks += 1;
+        let (offset, size) = (read_u32(entry, 8) as usize, read_u32(entry, 12) as usize);
+        if first_frame.is_empty() && size > 0 {
+            // オフセットは "movi" の位置から
+            let start = movi_at + offset + 8;
+            first_frame = bytes.get(start..start + size).expect("index points past the end of the file").to_vec();
+        }
+    }
+    AviInfo { total_frames, fps, audio_seconds, video_chunks, first_frame }
+}
+
+#[test]
+fn records_test_pattern_with_audio() {
+    let mut video = VideoCapture::new();
+    video.start_source(Box::new(TestPatternSource::new(Some((320, 240)), None, Some(FPS)))).unwrap();
+    // 入力音声の代わりに一定の音を録画用の分岐に流す
+    let tap = AudioTap::default();
+    tap.set_format(Some((SAMPLE_RATE, 2)));
+    let path = std::env::temp_dir().join(format!("capturecard_viewer_recording_{}.avi", std::process::id()));
+    let options = RecordOptions { fps: FPS, quality: 80, crop: CropSettings::default(), bars: BarInsets::default(), orientation: Orientation::default() };
+    let recorder = Recorder::start(path.clone(), &video.frame_reader(), Some(&tap), options).unwrap();
+    let started = Instant::now();
+    let mut pushed = 0u64;
+    while started.elapsed() < Duration::from_secs(1) {
+        let due = (started.elapsed().as_secs_f64() * SAMPLE_RATE as f64) as u64;
+        tap.push(std::iter::repeat_n(0.25, ((due - pushed) * 2) as usize));
+        pushed = due;
+        std::thread::sleep(Duration::from_millis(10));
+    }
+    let summary = recorder.stop();
+    video.stop_capture();
+    let bytes = std::fs::read(&path);
+    let _ = std::fs::remove_file(&path);
+    let summary = summary.unwrap();
+    let info = read_avi(&bytes.unwrap());
+
+    assert_eq!(info.fps, FPS);
+    assert_eq!(info.total_frames, summary.frames);
+    assert_eq!(info.video_chunks, info.total_frames as usize);
+    // 最初のフレームは 1 フレーム以内に届くので、長さは 1 秒から 2 フレーム分まで
+    let seconds = info.total_frames as f64 / info.fps.max(1) as f64;
+    assert!((1.0 - 2.0 / FPS as f64..=1.0 + 1.0 / FPS as f64).contains(&seconds),
+        "video is {:.3}s ({} frames), expected about 1s", seconds, info.total_frames);
+    assert!((info.audio_seconds - seconds).abs() <= 0.1, "audio is {:.3}s but video is {:.3}s", info.audio_seconds, seconds);
+    // 繰り返しで埋めたのが一部だけであること (テストパターンは指定どおりの間隔で届く)
+    assert!(summary.repeated <= info.total_frames / 4, "{} of {} frames were repeats", summary.repeated, info.total_frames);
+    let image = image::load_from_memory_with_format(&info.first_frame, image::ImageFormat::Jpeg).expect("first frame is not a valid JPEG");
+    assert_eq!((image.width(), image.height()), (320, 240));
+}
@@ -0,0 +1,84 @@
+// テストパターンを VideoCapture に流し、フレームが届くことと
+// カラーバーが期待色で表示されることを確認する
+use std::time::{Duration, Instant};
+use capturecard_viewer::convert::PixelFormat;
+use capturecard_viewer::test_pattern::{self, TEST_PATTERN_DEVICE};
+use capturecard_viewer::video::{self, VideoCapture, VideoFrame};
+
+// 最低 frames 枚受信するまで待ち、最新のフレームを返す
+fn wait_for_frames(video: &VideoCapture, frames: u64) -> VideoFrame {
+    let deadline = Instant::now() + Duration::from_secs(5);
+    let mut first_generation = None;
+    let mut received = 0;
+    loop {
+        assert!(Instant::now() < deadline, "timed out after {} frames", received);
+        if let Some(frame) = video.get_latest_frame() {
+            let first = *first_generation.get_or_insert(frame.generation);
+            received = frame.generation - first;
+            if received >= frames {
+                return frame;
+            }
+        }
+        std::thread::sleep(Duration::from_millis(5));
+    }
+}
+
+fn check_bars(format: PixelFormat, w: u32, h: u32) {
+    let mut video = VideoCapture::new();
+    video.start_capture(Some(TEST_PATTERN_DEVICE), None, Some((w, h)), Some(format.name()), Some(60)).unwrap();
+    let frame = wait_for_frames(&video, 5);
+    video.stop_capture();
+
+    assert_eq!((frame.width, frame.height), (w as usize, h as usize), "{}", format.name());
+    let y = frame.height / 3;
+    for (i, expected) in test_pattern::BARS.iter().enumerate() {
+        let px = frame.image.pixels[y * frame.width + test_pattern::bar_center_x(frame.width, i)];
+        let actual = [px.r(), px.g(), px.b()];
+        // YUV の量子化誤差分は許容
+        assert!(
+            actual.iter().zip(expected).all(|(a, e)| a.abs_diff(*e) <= 3),
+            "{} {}x{}: bar {} is {:?}, expected {:?}", format.name(), w, h, i, actual, expected
+        );
+    }
+}
+
+#[test]
+fn color_bars_sd() {
+    for format in test_pattern::FORMATS {
+        check_bars(format, 640, 480);
+    }
+}
+
+#[test]
+fn color_bars_hd() {
+    for format in test_pattern::FORMATS {
+        check_bars(format, 1280, 720);
+    }
+}
+
+// 途中で 1920x1200 に切り替わるソースで、サイズの食い違いの検出と
+// 対応モードからの選び直し (1920x1200 はないので 1920x1080)、開き直し後の表示を確認する
+#[test]
+fn renegotiates_after_resolution_change() {
+    let mut video = VideoCapture::new();
+    let switching = test_pattern::TestPatternSource::new(Some((1280, 720)), None, Some(60)).with_resolution_change(10, (1920, 1200));
+    video.start_source(Box::new(switching)).unwrap();
+    let deadline = Instant::now() + Duration::from_secs(5);
+    let change = loop {
+        assert!(Instant::now() < deadline, "resolution change was not detected");
+        if let Some(change) = video.take_mode_change() {
+            break change;
+        }
+        std::thread::sleep(Duration::from_millis(5));
+    };
+    assert_eq!(change.reported, (1280, 720));
+    let (w, h) = change.detected.expect("new size was not detected");
+    assert_eq!((w, h), (1920, 1200));
+    let (w, h, fps) = video::closest_mode(&test_pattern::capabilities(), change.format.name(), w as u32, h as u32, 60).unwrap();
+    assert_eq!((w, h), (1920, 1080));
+
+    video.start_capture(Some(TEST_PATTERN_DEVICE), None, Some((w, h)), Some(change.format.name()), Some(fps)).unwrap();
+    let frame = wait_for_frames(&video, 1);
+    video.stop_capture();
+    assert_eq!((frame.width, frame.height), (1920, 1080));
+}
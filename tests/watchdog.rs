@@ -0,0 +1,31 @@
+// 途中で止まるテストパターンで信号断の検知と再接続後の復帰を確認する
+use std::time::{Duration, Instant};
+use capturecard_viewer::test_pattern::TestPatternSource;
+use capturecard_viewer::video::VideoCapture;
+use capturecard_viewer::watchdog::SignalWatchdog;
+
+fn stalling_source() -> Box<TestPatternSource> {
+    Box::new(TestPatternSource::new(Some((320, 240)), None, Some(60)).with_stall_after(20))
+}
+
+#[test]
+fn reconnects_after_stall() {
+    let mut video = VideoCapture::new();
+    let mut watchdog = SignalWatchdog::new(Duration::from_millis(300));
+    video.start_source(stalling_source()).unwrap();
+
+    let deadline = Instant::now() + Duration::from_secs(5);
+    let mut detected = false;
+    loop {
+        let now = Instant::now();
+        assert!(now < deadline, "{}", if detected { "signal did not come back after reconnect" } else { "stall was not detected" });
+        if watchdog.poll(video.signal_state(), now) {
+            detected = true;
+            video.start_source(stalling_source()).unwrap();
+        } else if detected && !watchdog.is_lost() {
+            break;
+        }
+        std::thread::sleep(Duration::from_millis(5));
+    }
+    video.stop_capture();
+}